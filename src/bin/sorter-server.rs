@@ -0,0 +1,59 @@
+//! Serves the [JSON API](sorter_backend::server) on localhost.
+//!
+//! ```text
+//! sorter-server [--port PORT] [DIRECTORY]
+//! ```
+
+use sorter_backend::{server, Backend};
+use std::env;
+use std::net::{Ipv4Addr, TcpListener};
+use std::process::ExitCode;
+
+const DEFAULT_PORT: u16 = 7878;
+
+fn main() -> ExitCode {
+    let mut port = DEFAULT_PORT;
+    let mut directory = None;
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--port" => match arguments.next().and_then(|port| port.parse().ok()) {
+                Some(parsed_port) => port = parsed_port,
+                None => {
+                    eprintln!("error: --port needs a port number");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("usage: sorter-server [--port PORT] [DIRECTORY]");
+                return ExitCode::SUCCESS;
+            }
+            _ => directory = Some(argument),
+        }
+    }
+
+    let mut backend = Backend::new();
+    if let Some(directory) = directory {
+        if let Err(error) = backend.load_folders_and_files(directory) {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // only bind to localhost, the API can move and delete files
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("listening on http://{}:{}", Ipv4Addr::LOCALHOST, port);
+    match server::serve(listener, &mut backend) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,689 @@
+//! A terminal frontend for [Backend].
+//!
+//! ```text
+//! sorter [--script] [--images] [--depth DEPTH [--exclude-folders]] [--on-collision POLICY]
+//!        [--duplicates] [--copy] [--rules FILE] [DIRECTORY]
+//! ```
+//!
+//! Folders are listed with numbers and a number moves the current file into that folder. Several
+//! numbers send it into all of those folders at once, copying it into all but the last one. Every
+//! other action is a single letter, optionally followed by a path. Run `h` for the full list.
+//!
+//! With `--script`, commands are read from stdin without any prompts or listings so that files
+//! can be sorted from shell pipelines. Errors are reported on stderr and the exit status is
+//! non-zero if any command failed.
+//!
+//! With `--images`, only files that look like images are loaded. With `--depth`, files in
+//! subfolders up to that many levels down are loaded too, and `--exclude-folders` leaves out the
+//! ones already in the folders to sort into.
+//!
+//! `--on-collision` decides what happens when a folder already has a file with the same name:
+//! `fail` (the default), `rename` with a number, rename with a content `hash`, `skip` the file or
+//! `overwrite` the other file, which is put in the trash.
+//!
+//! With `--duplicates`, a file is not moved into a folder that already has a file with the same
+//! content. The copy is shown instead and `d` deletes the file as a duplicate.
+//!
+//! With `--copy`, files are copied into folders instead of moved, which leaves the loaded
+//! directory as it is. `t` switches between moving, copying and linking during a session, where
+//! links are a `hardlink`, a `symlink` to the absolute path or a `relative_symlink`.
+//!
+//! `y` moves the current file into a folder named after when it was taken, such as
+//! `y {root}/{exif.year}/{exif.month}`, see [FolderTemplate]. Folders are created as needed and
+//! `u` removes them again if they are left empty.
+//!
+//! `w` renames the current file, which stays the current one, either to a name such as
+//! `w Sunset.jpg` or by a template such as `w {date}_{counter}{ext}`, see [NameTemplate]. Names
+//! with braces are read as templates, where `{{` and `}}` stand for the braces themselves.
+//!
+//! With `--rules`, rules that sort files automatically are loaded from a file once the directory
+//! is loaded, and the folders they sort into have to be among the loaded folders. `p` lists what
+//! they would sort and `m` sorts it as one action that `u` undoes.
+
+use sorter_backend::{
+    Backend, CollisionPolicy, FileFilter, FolderTemplate, MoveOutcome, NameTemplate, SortKey,
+    SortOrder, TransferMode,
+};
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "sorter [--script] [--images] [--depth DEPTH [--exclude-folders]] \
+                     [--on-collision POLICY] [--duplicates] [--copy] [--rules FILE] [DIRECTORY]";
+
+const HELP: &str = "\
+commands:
+  <number>      move, copy or link the current file into the numbered folder
+  <number>...   send the current file into several folders at once, copying
+                it into all but the last one when moving
+  n <count> <number>
+                sort the next <count> files into the numbered folder at once
+  y <template>  move the current file into the folder the template gives it,
+                such as {root}/{exif.year}/{exif.month}, creating it if needed
+  w <name>      rename the current file to <name>, or by a template such as
+                {date}_{counter}{ext} if it has braces
+  s             skip the current file
+  d             delete the current file
+  u             undo the previous action
+  r             redo the action most recently undone
+  a <path>      add a folder to sort into
+  k <name>      create a folder named <name> in the loaded directory and add it
+  e <path>      load the folders in <path> to sort into
+  l <path>      load the folders and files in <path>
+  i <path>      include the files in <path> as well
+  x <path>      exclude the unsorted files in <path> again
+  o <order>     sort the files left by path, name, modified, created, size,
+                captured or random [<seed>], reversed with a leading -
+  t <mode>      move, copy, hardlink, symlink or relative_symlink files into
+                folders from now on
+  p [<path>]    list the files the rules would move, after loading the rules
+                in <path> if given
+  m             move the files the rules match
+  f             list the folders
+  c             show the current file
+  h             show this help
+  q             quit";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Move(usize),
+    SortInto(Vec<usize>),
+    SortNext(usize, usize),
+    MoveByTemplate(FolderTemplate),
+    Rename(String),
+    RenameByTemplate(NameTemplate),
+    Skip,
+    Delete,
+    Undo,
+    Redo,
+    AddFolder(String),
+    CreateFolder(String),
+    LoadExternalFolders(String),
+    LoadFoldersAndFiles(String),
+    AddSource(String),
+    RemoveSource(String),
+    Sort(SortOrder),
+    SetTransferMode(TransferMode),
+    PlanRules(Option<String>),
+    ApplyRules,
+    ListFolders,
+    ShowCurrentFile,
+    Help,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (line, ""),
+    };
+    let path_argument = || match argument.is_empty() {
+        true => Err(format!("`{}` needs a path", name)),
+        false => Ok(argument.to_owned()),
+    };
+
+    let command = match name {
+        "n" => match parse_folder_numbers(argument).as_deref() {
+            Some([count, number]) => Command::SortNext(*count, *number),
+            _ => return Err("`n` needs a number of files and a folder number".to_owned()),
+        },
+        "y" => match FolderTemplate::new(argument) {
+            Ok(template) if !argument.is_empty() => Command::MoveByTemplate(template),
+            Ok(_) => return Err("`y` needs a folder template".to_owned()),
+            Err(error) => return Err(error.to_string()),
+        },
+        "w" if argument.is_empty() => return Err("`w` needs a name".to_owned()),
+        "w" if argument.contains(['{', '}']) => match NameTemplate::new(argument) {
+            Ok(template) => Command::RenameByTemplate(template),
+            Err(error) => return Err(error.to_string()),
+        },
+        "w" => Command::Rename(argument.to_owned()),
+        "s" => Command::Skip,
+        "d" => Command::Delete,
+        "u" => Command::Undo,
+        "r" => Command::Redo,
+        "a" => Command::AddFolder(path_argument()?),
+        "k" => match argument.is_empty() {
+            true => return Err("`k` needs a folder name".to_owned()),
+            false => Command::CreateFolder(argument.to_owned()),
+        },
+        "e" => Command::LoadExternalFolders(path_argument()?),
+        "l" => Command::LoadFoldersAndFiles(path_argument()?),
+        "i" => Command::AddSource(path_argument()?),
+        "x" => Command::RemoveSource(path_argument()?),
+        "o" => Command::Sort(parse_sort_order(argument)?),
+        "t" => match TransferMode::from_name(argument) {
+            Some(transfer_mode) => Command::SetTransferMode(transfer_mode),
+            None => {
+                return Err(
+                    "`t` needs move, copy, hardlink, symlink or relative_symlink".to_owned(),
+                )
+            }
+        },
+        "p" => Command::PlanRules(Some(argument.to_owned()).filter(|path| !path.is_empty())),
+        "m" => Command::ApplyRules,
+        "f" => Command::ListFolders,
+        "c" => Command::ShowCurrentFile,
+        "h" => Command::Help,
+        "q" => Command::Quit,
+        _ => match parse_folder_numbers(line) {
+            Some(numbers) if numbers.len() == 1 => Command::Move(numbers[0]),
+            Some(numbers) => Command::SortInto(numbers),
+            None => return Err(format!("unknown command `{}`", line)),
+        },
+    };
+
+    Ok(Some(command))
+}
+
+fn parse_folder_numbers(line: &str) -> Option<Vec<usize>> {
+    line.split_whitespace()
+        .map(|number| number.parse().ok().filter(|number| *number > 0))
+        .collect()
+}
+
+fn parse_sort_order(argument: &str) -> Result<SortOrder, String> {
+    let (name, seed) = match argument.split_once(char::is_whitespace) {
+        Some((name, seed)) => match seed.trim().parse() {
+            Ok(seed) => (name, seed),
+            Err(_) => return Err(format!("`{}` is not a seed", seed.trim())),
+        },
+        None => (argument, 0),
+    };
+    let (name, descending) = match name.strip_prefix('-') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+
+    match SortKey::from_name(name, seed) {
+        Some(key) => Ok(SortOrder { key, descending }),
+        None => Err(format!("unknown order `{}`", argument)),
+    }
+}
+
+fn execute(backend: &mut Backend, command: Command) -> Result<(), io::Error> {
+    match command {
+        Command::Move(number) => {
+            let folder = folder_by_number(backend, number)?;
+            let file_name = current_file_name(backend);
+            print_move_outcome(&backend.sort_file(folder)?, file_name);
+            Ok(())
+        }
+        Command::MoveByTemplate(template) => {
+            match backend.move_file_by_template(&template)? {
+                MoveOutcome::Moved(destination) => println!("moved to {}", destination.display()),
+                outcome => print_move_outcome(&outcome, None),
+            }
+            Ok(())
+        }
+        Command::Rename(name) => {
+            let renamed = backend.rename_current(&name)?;
+            println!("renamed to {}", renamed.display());
+            Ok(())
+        }
+        Command::RenameByTemplate(template) => {
+            let renamed = backend.rename_current_by_template(&template)?;
+            println!("renamed to {}", renamed.display());
+            Ok(())
+        }
+        Command::SortInto(numbers) => {
+            let mut destinations = Vec::new();
+            for (position, number) in numbers.iter().enumerate() {
+                let transfer_mode = match backend.transfer_mode {
+                    TransferMode::Move if position + 1 < numbers.len() => TransferMode::Copy,
+                    transfer_mode => transfer_mode,
+                };
+                destinations.push((folder_by_number(backend, *number)?, transfer_mode));
+            }
+            let outcomes = backend.sort_into(&destinations)?;
+            for (number, outcome) in numbers.iter().zip(outcomes) {
+                match outcome {
+                    MoveOutcome::Moved(_) => {}
+                    MoveOutcome::Skipped => println!(
+                        "skipped folder {}, it already has a file with that name",
+                        number
+                    ),
+                    MoveOutcome::Duplicate(duplicate) => println!(
+                        "not sent to folder {}, {} has the same content",
+                        number,
+                        duplicate.display()
+                    ),
+                }
+            }
+            Ok(())
+        }
+        Command::SortNext(count, number) => {
+            let folder = folder_by_number(backend, number)?;
+            let outcomes = backend.sort_next(count, folder)?;
+            let left_count = outcomes
+                .iter()
+                .filter(|outcome| !matches!(outcome, MoveOutcome::Moved(_)))
+                .count();
+            if left_count > 0 {
+                println!("skipped {} of {} files", left_count, outcomes.len());
+            }
+            Ok(())
+        }
+        Command::Skip => backend.skip(),
+        Command::Delete => backend.delete_file(),
+        Command::Undo => backend.undo(),
+        Command::Redo => backend.redo(),
+        Command::AddFolder(path) => backend.add_folder(path),
+        Command::CreateFolder(name) => {
+            let parent = PathBuf::from(backend.pwd.trim());
+            backend.create_folder(parent, &name).map(|_| ())
+        }
+        Command::LoadExternalFolders(path) => backend.load_external_folders(path),
+        Command::LoadFoldersAndFiles(path) => load_folders_and_files(backend, path),
+        Command::AddSource(path) => {
+            let excluded_count = backend.add_source(path)?;
+            report_excluded(excluded_count);
+            Ok(())
+        }
+        Command::RemoveSource(path) => backend.remove_source(path),
+        Command::Sort(sort_order) => backend.sort_files(sort_order),
+        Command::SetTransferMode(transfer_mode) => {
+            backend.transfer_mode = transfer_mode;
+            Ok(())
+        }
+        Command::PlanRules(path) => {
+            if let Some(path) = path {
+                backend.load_rules(path)?;
+            }
+            let proposed_moves = backend.plan_rules()?;
+            if proposed_moves.is_empty() {
+                println!("the rules match none of the files left");
+            }
+            for proposed in proposed_moves {
+                println!(
+                    "{} -> {}",
+                    proposed.file.display(),
+                    proposed.destination.display()
+                );
+            }
+            Ok(())
+        }
+        Command::ApplyRules => {
+            let applied = backend.apply_rules()?;
+            let moved_count = applied
+                .iter()
+                .filter(|(_, outcome)| matches!(outcome, MoveOutcome::Moved(_)))
+                .count();
+            println!("moved {} of {} matching files", moved_count, applied.len());
+            Ok(())
+        }
+        Command::ListFolders | Command::ShowCurrentFile | Command::Help | Command::Quit => Ok(()),
+    }
+}
+
+fn load_folders_and_files(backend: &mut Backend, directory: String) -> Result<(), io::Error> {
+    let excluded_count = backend.load_folders_and_files(directory)?;
+    report_excluded(excluded_count);
+    for (file, format) in backend.extension_mismatches() {
+        eprintln!(
+            "warning: {} has {} content that doesn't match its extension",
+            file.display(),
+            format.as_str()
+        );
+    }
+
+    Ok(())
+}
+
+fn report_excluded(excluded_count: usize) {
+    if excluded_count > 0 {
+        eprintln!("excluded {} files by the filter", excluded_count);
+    }
+}
+
+fn folder_by_number(backend: &Backend, number: usize) -> Result<PathBuf, io::Error> {
+    match backend.folders.get(number - 1) {
+        Some(folder) => Ok(folder.clone()),
+        None => Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("there is no folder {}", number),
+        )),
+    }
+}
+
+fn print_folders(backend: &Backend) {
+    if backend.folders.is_empty() {
+        println!("no folders loaded");
+    }
+    for (index, folder) in backend.folders.iter().enumerate() {
+        println!("{:>3}: {}", index + 1, folder.display());
+    }
+}
+
+fn current_file_name(backend: &Backend) -> Option<OsString> {
+    backend
+        .get_current_file()
+        .and_then(|file| file.file_name().map(ToOwned::to_owned))
+}
+
+/// Reports a move that didn't go as expected, given the name the file had before it.
+fn print_move_outcome(outcome: &MoveOutcome, file_name: Option<OsString>) {
+    match outcome {
+        MoveOutcome::Moved(destination) if destination.file_name() != file_name.as_deref() => {
+            println!("renamed to {}", destination.display())
+        }
+        MoveOutcome::Moved(_) => {}
+        MoveOutcome::Skipped => println!("skipped, the folder already has a file with that name"),
+        MoveOutcome::Duplicate(duplicate) => println!(
+            "not moved, {} has the same content, `d` deletes this file",
+            duplicate.display()
+        ),
+    }
+}
+
+fn print_current_file(backend: &Backend) {
+    match backend.get_current_file() {
+        Some(file) => println!(
+            "[{}/{}] {}",
+            backend.current_file_index + 1,
+            backend.file_count(),
+            file.display()
+        ),
+        None => println!("no file to sort"),
+    }
+}
+
+/// The end of the files is reported as an error by the backend, but is expected when sorting.
+fn is_failure(error: &io::Error) -> bool {
+    error.kind() != ErrorKind::UnexpectedEof
+}
+
+fn run_interactive(backend: &mut Backend) -> Result<bool, io::Error> {
+    let stdin = io::stdin();
+    let mut had_failure = false;
+    print_folders(backend);
+    print_current_file(backend);
+
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(had_failure);
+        }
+
+        let command = match parse_command(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(message) => {
+                println!("{} (`h` for help)", message);
+                continue;
+            }
+        };
+        match command {
+            Command::Quit => return Ok(had_failure),
+            Command::Help => println!("{}", HELP),
+            Command::ListFolders => print_folders(backend),
+            _ => {
+                let lists_folders = matches!(
+                    command,
+                    Command::AddFolder(_)
+                        | Command::CreateFolder(_)
+                        | Command::LoadExternalFolders(_)
+                        | Command::LoadFoldersAndFiles(_)
+                );
+                match execute(backend, command) {
+                    Err(error) if is_failure(&error) => {
+                        had_failure = true;
+                        println!("error: {}", error);
+                    }
+                    Err(_) => println!("reached the end of the files"),
+                    Ok(()) if lists_folders => print_folders(backend),
+                    Ok(()) => {}
+                }
+            }
+        }
+        print_current_file(backend);
+    }
+}
+
+fn run_script(backend: &mut Backend) -> Result<bool, io::Error> {
+    let mut had_failure = false;
+
+    for (line_number, line) in io::stdin().lock().lines().enumerate() {
+        let line = line?;
+        let command = match parse_command(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(message) => {
+                had_failure = true;
+                eprintln!("line {}: {}", line_number + 1, message);
+                continue;
+            }
+        };
+        match command {
+            Command::Quit => break,
+            Command::Help => println!("{}", HELP),
+            Command::ListFolders => print_folders(backend),
+            Command::ShowCurrentFile => print_current_file(backend),
+            command => match execute(backend, command) {
+                Err(error) if is_failure(&error) => {
+                    had_failure = true;
+                    eprintln!("line {}: {}", line_number + 1, error);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    Ok(had_failure)
+}
+
+fn main() -> ExitCode {
+    let mut script_mode = false;
+    let mut directory = None;
+    let mut rules = None;
+    let mut backend = Backend::new();
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--script" => script_mode = true,
+            "--images" => backend.file_filter = FileFilter::images(),
+            "--depth" => match arguments.next().and_then(|depth| depth.parse().ok()) {
+                Some(depth) => backend.recursion.max_depth = depth,
+                None => {
+                    eprintln!("error: --depth needs a number of levels");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--exclude-folders" => backend.recursion.exclude_folders = true,
+            "--duplicates" => backend.detect_duplicates = true,
+            "--copy" => backend.transfer_mode = TransferMode::Copy,
+            "--rules" => match arguments.next() {
+                Some(path) => rules = Some(path),
+                None => {
+                    eprintln!("error: --rules needs a file");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--on-collision" => {
+                match arguments
+                    .next()
+                    .as_deref()
+                    .and_then(CollisionPolicy::from_name)
+                {
+                    Some(policy) => backend.collision_policy = policy,
+                    None => {
+                        eprintln!("error: --on-collision needs one of fail, rename, hash, skip or overwrite");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("usage: {}\n\n{}", USAGE, HELP);
+                return ExitCode::SUCCESS;
+            }
+            _ => directory = Some(argument),
+        }
+    }
+
+    if let Some(directory) = directory {
+        if let Err(error) = load_folders_and_files(&mut backend, directory) {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(rules) = rules {
+        if let Err(error) = backend.load_rules(rules) {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let result = match script_mode {
+        true => run_script(&mut backend),
+        false => run_interactive(&mut backend),
+    };
+    match result {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_command, Command};
+    use sorter_backend::{
+        FolderTemplate, LinkKind, NameTemplate, SortKey, SortOrder, TransferMode,
+    };
+
+    #[test]
+    fn ensure_commands_are_parsed() {
+        assert_eq!(parse_command("3"), Ok(Some(Command::Move(3))));
+        assert_eq!(
+            parse_command("1 3  5"),
+            Ok(Some(Command::SortInto(vec![1, 3, 5])))
+        );
+        assert!(parse_command("1 x").is_err());
+        assert_eq!(parse_command("n 20 2"), Ok(Some(Command::SortNext(20, 2))));
+        assert_eq!(parse_command("p"), Ok(Some(Command::PlanRules(None))));
+        assert_eq!(
+            parse_command("p team.rules"),
+            Ok(Some(Command::PlanRules(Some("team.rules".to_owned()))))
+        );
+        assert_eq!(parse_command("m"), Ok(Some(Command::ApplyRules)));
+        assert!(parse_command("n 20").is_err());
+        assert!(parse_command("1 0").is_err());
+        assert_eq!(
+            parse_command("y {root}/{exif.year}"),
+            Ok(Some(Command::MoveByTemplate(
+                FolderTemplate::new("{root}/{exif.year}").unwrap()
+            )))
+        );
+        assert!(parse_command("y {root}/{year}").is_err());
+        assert!(parse_command("y").is_err());
+        assert_eq!(
+            parse_command("w Sunset at the beach.jpg"),
+            Ok(Some(Command::Rename("Sunset at the beach.jpg".to_owned())))
+        );
+        assert_eq!(
+            parse_command("w {date}_{counter}{ext}"),
+            Ok(Some(Command::RenameByTemplate(
+                NameTemplate::new("{date}_{counter}{ext}").unwrap()
+            )))
+        );
+        assert!(parse_command("w {day}.jpg").is_err());
+        assert!(parse_command("w").is_err());
+        assert_eq!(parse_command(" s \n"), Ok(Some(Command::Skip)));
+        assert_eq!(parse_command("d"), Ok(Some(Command::Delete)));
+        assert_eq!(parse_command("u"), Ok(Some(Command::Undo)));
+        assert_eq!(parse_command("r"), Ok(Some(Command::Redo)));
+        assert_eq!(
+            parse_command("a ./images/test Folder"),
+            Ok(Some(Command::AddFolder("./images/test Folder".to_owned())))
+        );
+        assert_eq!(
+            parse_command("k New Category"),
+            Ok(Some(Command::CreateFolder("New Category".to_owned())))
+        );
+        assert!(parse_command("k").is_err());
+        assert_eq!(
+            parse_command("e ./images"),
+            Ok(Some(Command::LoadExternalFolders("./images".to_owned())))
+        );
+        assert_eq!(
+            parse_command("l ./images"),
+            Ok(Some(Command::LoadFoldersAndFiles("./images".to_owned())))
+        );
+        assert_eq!(
+            parse_command("i ./phone"),
+            Ok(Some(Command::AddSource("./phone".to_owned())))
+        );
+        assert_eq!(
+            parse_command("x ./phone"),
+            Ok(Some(Command::RemoveSource("./phone".to_owned())))
+        );
+        assert_eq!(parse_command("q"), Ok(Some(Command::Quit)));
+    }
+
+    #[test]
+    fn ensure_sort_orders_are_parsed() {
+        assert_eq!(
+            parse_command("o name"),
+            Ok(Some(Command::Sort(SortOrder::ascending(
+                SortKey::NaturalName
+            ))))
+        );
+        assert_eq!(
+            parse_command("o -size"),
+            Ok(Some(Command::Sort(SortOrder::descending(SortKey::Size))))
+        );
+        assert_eq!(
+            parse_command("o random 42"),
+            Ok(Some(Command::Sort(SortOrder::ascending(SortKey::Random {
+                seed: 42
+            }))))
+        );
+        assert!(parse_command("o").is_err());
+        assert!(parse_command("o random seed").is_err());
+    }
+
+    #[test]
+    fn ensure_transfer_modes_are_parsed() {
+        assert_eq!(
+            parse_command("t copy"),
+            Ok(Some(Command::SetTransferMode(TransferMode::Copy)))
+        );
+        assert_eq!(
+            parse_command("t move"),
+            Ok(Some(Command::SetTransferMode(TransferMode::Move)))
+        );
+        assert_eq!(
+            parse_command("t relative_symlink"),
+            Ok(Some(Command::SetTransferMode(TransferMode::Link(
+                LinkKind::RelativeSymbolic
+            ))))
+        );
+    }
+
+    #[test]
+    fn ensure_blank_lines_and_comments_are_ignored() {
+        assert_eq!(parse_command("   "), Ok(None));
+        assert_eq!(parse_command("# sort the holiday photos"), Ok(None));
+    }
+
+    #[test]
+    fn ensure_invalid_commands_are_rejected() {
+        assert!(parse_command("0").is_err());
+        assert!(parse_command("x").is_err());
+        assert!(parse_command("a").is_err());
+        assert!(parse_command("t link").is_err());
+        assert!(parse_command("t").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! What happens when a file is moved onto a name that is already taken.
+
+use crate::filesystem::FilesystemIO;
+use crate::hash;
+use std::ffi::OsString;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+/// How [Backend::move_file](crate::Backend::move_file) handles a destination that already
+/// exists.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Fail with an [AlreadyExists](std::io::ErrorKind::AlreadyExists) error.
+    #[default]
+    Fail,
+    /// Add the first free number to the name, e.g. `name (1).jpg`.
+    Rename,
+    /// Add a hash of the file's content to the name, e.g. `name-0123456789abcdef.jpg`, followed
+    /// by a number if that is taken as well.
+    RenameWithHash,
+    /// Leave the file where it is and [skip](crate::Backend::skip) it.
+    Skip,
+    /// Put the existing file in the trash and take its place.
+    Overwrite,
+}
+
+impl CollisionPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollisionPolicy::Fail => "fail",
+            CollisionPolicy::Rename => "rename",
+            CollisionPolicy::RenameWithHash => "hash",
+            CollisionPolicy::Skip => "skip",
+            CollisionPolicy::Overwrite => "overwrite",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CollisionPolicy> {
+        let policy = match name {
+            "fail" => CollisionPolicy::Fail,
+            "rename" => CollisionPolicy::Rename,
+            "hash" => CollisionPolicy::RenameWithHash,
+            "skip" => CollisionPolicy::Skip,
+            "overwrite" => CollisionPolicy::Overwrite,
+            _ => return None,
+        };
+
+        Some(policy)
+    }
+}
+
+/// What [Backend::move_file](crate::Backend::move_file) did with the current file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveOutcome {
+    /// The file was moved to this path, which differs from the requested one if it was renamed.
+    Moved(PathBuf),
+    /// The destination was taken, so the file was skipped instead.
+    Skipped,
+    /// The folder already has a file with the same content, so the file was left where it is.
+    Duplicate(PathBuf),
+}
+
+/// Returns the destination with the first number that makes it a free name.
+pub fn numbered_destination(destination: &Path, filesystem_helper: &dyn FilesystemIO) -> PathBuf {
+    (1..)
+        .map(|number| with_suffix(destination, &format!(" ({})", number)))
+        .find(|candidate| !filesystem_helper.exists(candidate))
+        .unwrap_or_default()
+}
+
+/// Returns the destination with a hash of the source's content added to its name, numbered if
+/// that name is taken as well.
+pub fn hashed_destination(
+    destination: &Path,
+    source: &Path,
+    filesystem_helper: &dyn FilesystemIO,
+) -> Result<PathBuf, Error> {
+    let hash = hash::hash_file(source)?;
+    let hashed = with_suffix(destination, &format!("-{:016x}", hash));
+    match filesystem_helper.exists(&hashed) {
+        true => Ok(numbered_destination(&hashed, filesystem_helper)),
+        false => Ok(hashed),
+    }
+}
+
+/// Adds the suffix to the file's name, before its extension.
+fn with_suffix(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(file.file_stem().unwrap_or_default());
+    name.push(suffix);
+    if let Some(extension) = file.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+
+    file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collision::{hashed_destination, numbered_destination};
+    use crate::filesystem::Filesystem;
+    use std::fs::{self, File};
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_first_free_number_is_used() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let taken = dir.path().join("file1.jpg");
+        File::create(&taken).unwrap();
+        File::create(dir.path().join("file1 (1).jpg")).unwrap();
+
+        assert_eq!(
+            numbered_destination(&taken, &Filesystem::new()),
+            dir.path().join("file1 (2).jpg")
+        );
+        assert_eq!(
+            numbered_destination(&dir.path().join("README"), &Filesystem::new()),
+            dir.path().join("README (1)")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_broken_links_are_taken_names() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let taken = dir.path().join("file1.jpg");
+        File::create(&taken).unwrap();
+        std::os::unix::fs::symlink(
+            dir.path().join("missing.jpg"),
+            dir.path().join("file1 (1).jpg"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            numbered_destination(&taken, &Filesystem::new()),
+            dir.path().join("file1 (2).jpg")
+        );
+    }
+
+    #[test]
+    fn ensure_content_hash_is_added_to_the_name() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let source = dir.path().join("source.jpg");
+        fs::write(&source, b"a").unwrap();
+        let taken = dir.path().join("file1.jpg");
+
+        let first = hashed_destination(&taken, &source, &Filesystem::new()).unwrap();
+        File::create(&first).unwrap();
+        let second = hashed_destination(&taken, &source, &Filesystem::new()).unwrap();
+
+        assert_eq!(first, dir.path().join("file1-af63dc4c8601ec8c.jpg"));
+        assert_eq!(second, dir.path().join("file1-af63dc4c8601ec8c (1).jpg"));
+    }
+}
@@ -1,62 +1,1173 @@
-use crate::filesystem::{Filesystem, FilesystemIO};
-use std::io::Error;
+use crate::encoding::{self, escape, unescape, FIELD_SEPARATOR};
+use crate::filesystem::FilesystemIO;
+use crate::transfer::LinkKind;
+use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The kind of an action that can be undone and redone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    Move,
+    Skip,
+    Delete,
+    Copy,
+    Link(LinkKind),
+    /// Several actions on the same file, performed and undone as one.
+    Compound,
+    /// Actions on several files in a row, performed and undone as one.
+    Batch,
+    /// A new folder, which is only removed again by undoing if it is empty.
+    CreateFolder,
+    /// A new name for a file, which stays where it is.
+    Rename,
+}
+
+impl ActionKind {
+    /// Returns the stable name of the kind, as used in encoded actions.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::Move => "move",
+            ActionKind::Skip => "skip",
+            ActionKind::Delete => "delete",
+            ActionKind::Copy => "copy",
+            ActionKind::Link(kind) => kind.as_str(),
+            ActionKind::Compound => "compound",
+            ActionKind::Batch => "batch",
+            ActionKind::CreateFolder => "create_folder",
+            ActionKind::Rename => "rename",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ActionKind> {
+        match name {
+            "move" => Some(ActionKind::Move),
+            "skip" => Some(ActionKind::Skip),
+            "delete" => Some(ActionKind::Delete),
+            "copy" => Some(ActionKind::Copy),
+            "compound" => Some(ActionKind::Compound),
+            "batch" => Some(ActionKind::Batch),
+            "create_folder" => Some(ActionKind::CreateFolder),
+            "rename" => Some(ActionKind::Rename),
+            name => LinkKind::from_name(name).map(ActionKind::Link),
+        }
+    }
+}
+
+/// Describes a performed action so that it can be listed or saved.
+///
+/// Descriptions have a textual [encoding](ActionDescription::encode) that can be
+/// [decoded](ActionDescription::decode) back into the same description, and from which the action
+/// itself can be rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionDescription {
+    pub kind: ActionKind,
+    /// The file the action was performed on.
+    pub source: PathBuf,
+    /// Where the file ended up, if the action put it somewhere.
+    pub destination: Option<PathBuf>,
+    /// Where the file that was at the destination before was trashed, if the action overwrote
+    /// it.
+    pub replaced: Option<PathBuf>,
+    /// The actions a [compound](ActionKind::Compound) or [batch](ActionKind::Batch) action is
+    /// made of, in the order they were performed.
+    pub parts: Vec<ActionDescription>,
+    /// When the action was last performed.
+    pub timestamp: SystemTime,
+}
+
+impl ActionDescription {
+    /// Encodes the description as a single line of tab separated fields: the kind, the source,
+    /// the destination (empty if there is none), the timestamp as seconds since the unix epoch
+    /// and, only if a file was overwritten, where it was trashed. Compound and batch actions have
+    /// the escaped encodings of their parts instead.
+    pub fn encode(&self) -> String {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let destination = match &self.destination {
+            Some(destination) => encoding::escape_path(destination),
+            None => String::new(),
+        };
+
+        let mut fields = vec![
+            self.kind.as_str().to_owned(),
+            encoding::escape_path(&self.source),
+            destination,
+            format!("{}.{:09}", timestamp.as_secs(), timestamp.subsec_nanos()),
+        ];
+        if let Some(replaced) = &self.replaced {
+            fields.push(encoding::escape_path(replaced));
+        }
+        fields.extend(self.parts.iter().map(|part| escape(&part.encode())));
+
+        fields.join(&FIELD_SEPARATOR.to_string())
+    }
+
+    /// Decodes a description from its [encoding](ActionDescription::encode).
+    ///
+    /// # Errors
+    ///
+    /// If the text isn't a valid encoding, an [InvalidData](ErrorKind::InvalidData) error is
+    /// returned.
+    pub fn decode(encoded: &str) -> Result<ActionDescription, Error> {
+        let invalid = || Error::from(ErrorKind::InvalidData);
+        let fields: Vec<&str> = encoded.split(FIELD_SEPARATOR).collect();
+        let (kind, source, destination, timestamp, rest) = match fields.as_slice() {
+            [kind, source, destination, timestamp, rest @ ..] => {
+                (kind, source, destination, timestamp, rest)
+            }
+            _ => return Err(invalid()),
+        };
+        let kind = ActionKind::from_name(kind).ok_or_else(invalid)?;
+        let (replaced, parts) = match (kind, rest) {
+            (ActionKind::Compound | ActionKind::Batch, parts) => (
+                None,
+                parts
+                    .iter()
+                    .map(|part| ActionDescription::decode(&unescape(part)?))
+                    .collect::<Result<Vec<ActionDescription>, Error>>()?,
+            ),
+            (_, []) => (None, Vec::new()),
+            (_, [replaced]) => (Some(encoding::unescape_path(replaced)?), Vec::new()),
+            _ => return Err(invalid()),
+        };
+
+        let (seconds, nanos) = timestamp.split_once('.').ok_or_else(invalid)?;
+        let nanos: u32 = nanos.parse().map_err(|_| invalid())?;
+        if nanos >= 1_000_000_000 {
+            return Err(invalid());
+        }
+        let timestamp = UNIX_EPOCH
+            .checked_add(Duration::new(
+                seconds.parse().map_err(|_| invalid())?,
+                nanos,
+            ))
+            .ok_or_else(invalid)?;
+        let destination = match destination.is_empty() {
+            true => None,
+            false => Some(encoding::unescape_path(destination)?),
+        };
+
+        Ok(ActionDescription {
+            kind,
+            source: encoding::unescape_path(source)?,
+            destination,
+            replaced,
+            parts,
+            timestamp,
+        })
+    }
+
+    /// Rebuilds the described action without performing it.
+    ///
+    /// # Errors
+    ///
+    /// If the description is missing a path that its kind of action needs, an
+    /// [InvalidData](ErrorKind::InvalidData) error is returned.
+    pub fn into_action(
+        self,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> Result<Box<dyn Controllable>, Error> {
+        if let ActionKind::Compound | ActionKind::Batch = self.kind {
+            let parts = self
+                .parts
+                .into_iter()
+                .map(|part| part.into_action(Rc::clone(&filesystem_helper)))
+                .collect::<Result<Vec<Box<dyn Controllable>>, Error>>()?;
+            let mut action: Box<dyn Controllable> = match self.kind {
+                ActionKind::Compound => Box::new(Compound::new(self.source, parts)),
+                _ if parts.is_empty() => return Err(Error::from(ErrorKind::InvalidData)),
+                _ => Box::new(Batch::new(parts)),
+            };
+            action.set_timestamp(self.timestamp);
+            return Ok(action);
+        }
+        let mut action: Box<dyn Controllable> = match (self.kind, self.destination, self.replaced) {
+            (ActionKind::Move, Some(destination), replaced) => {
+                let mut action = Move::new(self.source, destination, filesystem_helper);
+                action.replaced_file_location = replaced;
+                Box::new(action)
+            }
+            (ActionKind::Copy, Some(destination), replaced) => {
+                let mut action = CopyFile::new(self.source, destination, filesystem_helper);
+                action.replaced_file_location = replaced;
+                Box::new(action)
+            }
+            (ActionKind::Link(kind), Some(destination), replaced) => {
+                let mut action = Link::new(self.source, destination, kind, filesystem_helper);
+                action.replaced_file_location = replaced;
+                Box::new(action)
+            }
+            (ActionKind::Delete, Some(destination), None) => {
+                Box::new(Delete::new(self.source, destination, filesystem_helper))
+            }
+            (ActionKind::Skip, None, None) => Box::new(Skip::new(self.source)),
+            (ActionKind::CreateFolder, None, None) => {
+                Box::new(CreateFolder::new(self.source, filesystem_helper))
+            }
+            (ActionKind::Rename, Some(destination), None) => {
+                Box::new(Rename::new(self.source, destination, filesystem_helper))
+            }
+            _ => return Err(Error::from(ErrorKind::InvalidData)),
+        };
+        action.set_timestamp(self.timestamp);
+
+        Ok(action)
+    }
+}
 
 pub struct Move {
     pub current_file_location: PathBuf,
     pub previous_file_location: PathBuf,
-    pub filesystem_helper: Box<dyn FilesystemIO>, // TODO: figure out how to take in ref to parent's helper
+    /// Where the file that was overwritten by the move is kept in the trash, so that it can be
+    /// put back when undoing.
+    pub replaced_file_location: Option<PathBuf>,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
 }
 
 impl Move {
-    pub fn new(current_location: PathBuf, previous_location: PathBuf) -> Move {
+    pub fn new(
+        current_location: PathBuf,
+        previous_location: PathBuf,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> Move {
         Move {
             current_file_location: current_location,
             previous_file_location: previous_location,
-            filesystem_helper: Box::new(Filesystem::new()),
+            replaced_file_location: None,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
         }
     }
 }
 
 impl Controllable for Move {
-    fn undo(&self) -> Result<(), Error> {
+    fn undo(&mut self) -> Result<(), Error> {
         self.filesystem_helper
             .move_file(&self.previous_file_location, &self.current_file_location)?;
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper
+                .restore_file(replaced_file_location, &self.previous_file_location)?;
+        }
 
         Ok(())
     }
 
-    fn redo(&self) -> Result<(), Error> {
+    fn redo(&mut self) -> Result<(), Error> {
+        if self.replaced_file_location.is_some() {
+            self.replaced_file_location = Some(
+                self.filesystem_helper
+                    .trash_file(&self.previous_file_location)?,
+            );
+        }
         self.filesystem_helper
             .move_file(&self.current_file_location, &self.previous_file_location)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper.purge_file(replaced_file_location)?;
+        }
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Move,
+            source: self.current_file_location.clone(),
+            destination: Some(self.previous_file_location.clone()),
+            replaced: self.replaced_file_location.clone(),
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+}
+
+/// A copy of a file into a folder, which leaves the original untouched.
+///
+/// Undoing removes the copy for good, since the original is still there to copy again.
+pub struct CopyFile {
+    pub original_file_location: PathBuf,
+    pub copy_file_location: PathBuf,
+    /// Where the file that was overwritten by the copy is kept in the trash, so that it can be
+    /// put back when undoing.
+    pub replaced_file_location: Option<PathBuf>,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
+}
+
+impl CopyFile {
+    pub fn new(
+        original_location: PathBuf,
+        copy_location: PathBuf,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> CopyFile {
+        CopyFile {
+            original_file_location: original_location,
+            copy_file_location: copy_location,
+            replaced_file_location: None,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for CopyFile {
+    fn undo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper
+            .delete_file(&self.copy_file_location)?;
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper
+                .restore_file(replaced_file_location, &self.copy_file_location)?;
+        }
+
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        if self.replaced_file_location.is_some() {
+            self.replaced_file_location = Some(
+                self.filesystem_helper
+                    .trash_file(&self.copy_file_location)?,
+            );
+        }
+        self.filesystem_helper
+            .copy_file(&self.original_file_location, &self.copy_file_location)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper.purge_file(replaced_file_location)?;
+        }
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Copy,
+            source: self.original_file_location.clone(),
+            destination: Some(self.copy_file_location.clone()),
+            replaced: self.replaced_file_location.clone(),
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+}
+
+/// A link to a file put in a folder, which leaves the file itself untouched.
+///
+/// Undoing only removes the link.
+pub struct Link {
+    pub file_location: PathBuf,
+    pub link_location: PathBuf,
+    pub kind: LinkKind,
+    /// Where the file that was overwritten by the link is kept in the trash, so that it can be
+    /// put back when undoing.
+    pub replaced_file_location: Option<PathBuf>,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
+}
+
+impl Link {
+    pub fn new(
+        file_location: PathBuf,
+        link_location: PathBuf,
+        kind: LinkKind,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> Link {
+        Link {
+            file_location,
+            link_location,
+            kind,
+            replaced_file_location: None,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for Link {
+    fn undo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper.delete_file(&self.link_location)?;
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper
+                .restore_file(replaced_file_location, &self.link_location)?;
+        }
+
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        if self.replaced_file_location.is_some() {
+            self.replaced_file_location =
+                Some(self.filesystem_helper.trash_file(&self.link_location)?);
+        }
+        self.filesystem_helper
+            .link_file(&self.file_location, &self.link_location, self.kind)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        if let Some(replaced_file_location) = &self.replaced_file_location {
+            self.filesystem_helper.purge_file(replaced_file_location)?;
+        }
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Link(self.kind),
+            source: self.file_location.clone(),
+            destination: Some(self.link_location.clone()),
+            replaced: self.replaced_file_location.clone(),
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+}
+
+/// A folder created to sort files into, such as a dated folder of a
+/// [template](crate::FolderTemplate).
+///
+/// Undoing removes the folder only if it is empty, so that files put there since are never
+/// lost. Creating the folder doesn't move past any file.
+pub struct CreateFolder {
+    pub folder: PathBuf,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
+}
+
+impl CreateFolder {
+    pub fn new(folder: PathBuf, filesystem_helper: Rc<dyn FilesystemIO>) -> CreateFolder {
+        CreateFolder {
+            folder,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for CreateFolder {
+    fn undo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper.remove_empty_folder(&self.folder)
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        match self.filesystem_helper.create_folder(&self.folder) {
+            // a folder that wasn't empty when undoing is still there
+            Err(error) if error.kind() == ErrorKind::AlreadyExists && self.folder.is_dir() => {}
+            result => result?,
+        }
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::CreateFolder,
+            source: self.folder.clone(),
+            destination: None,
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+
+    fn file_count(&self) -> usize {
+        0
+    }
+}
+
+/// A new name for a file in the same folder.
+///
+/// Renaming doesn't move past the file, so that it stays the current one under its new name.
+pub struct Rename {
+    pub original_file_location: PathBuf,
+    pub renamed_file_location: PathBuf,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
+}
+
+impl Rename {
+    pub fn new(
+        original_location: PathBuf,
+        renamed_location: PathBuf,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> Rename {
+        Rename {
+            original_file_location: original_location,
+            renamed_file_location: renamed_location,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for Rename {
+    fn undo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper
+            .rename_file(&self.renamed_file_location, &self.original_file_location)
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper
+            .rename_file(&self.original_file_location, &self.renamed_file_location)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Rename,
+            source: self.original_file_location.clone(),
+            destination: Some(self.renamed_file_location.clone()),
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+
+    fn file_count(&self) -> usize {
+        0
+    }
+}
+
+/// Several actions on the same file, such as copying it into two folders and moving it into a
+/// third, that are performed and undone as one.
+///
+/// The parts are undone in reverse order. If undoing or redoing a part fails, the parts that
+/// were already undone or redone are brought back to how they were, so that the action is never
+/// left halfway.
+pub struct Compound {
+    pub file: PathBuf,
+    pub parts: Vec<Box<dyn Controllable>>,
+    pub timestamp: SystemTime,
+}
+
+impl Compound {
+    pub fn new(file: PathBuf, parts: Vec<Box<dyn Controllable>>) -> Compound {
+        Compound {
+            file,
+            parts,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for Compound {
+    fn undo(&mut self) -> Result<(), Error> {
+        undo_all(&mut self.parts)
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        redo_all(&mut self.parts)?;
+        self.timestamp = SystemTime::now();
 
         Ok(())
     }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        purge_all(&mut self.parts)
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Compound,
+            source: self.file.clone(),
+            destination: None,
+            replaced: None,
+            parts: self.parts.iter().map(|part| part.describe()).collect(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+}
+
+/// Actions on several files in a row, such as moving the next twenty files into the same folder,
+/// that are performed and undone as one.
+///
+/// Each part is the action on one file, or several if it is a batch itself, so that the batch
+/// advances past all of them. Like a [Compound] action, the batch is never left halfway when
+/// undoing or redoing a part fails.
+pub struct Batch {
+    pub parts: Vec<Box<dyn Controllable>>,
+    pub timestamp: SystemTime,
+}
+
+impl Batch {
+    pub fn new(parts: Vec<Box<dyn Controllable>>) -> Batch {
+        Batch {
+            parts,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for Batch {
+    fn undo(&mut self) -> Result<(), Error> {
+        undo_all(&mut self.parts)
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        redo_all(&mut self.parts)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        purge_all(&mut self.parts)
+    }
+
+    fn describe(&self) -> ActionDescription {
+        let source = match self.parts.first() {
+            Some(part) => part.describe().source,
+            None => PathBuf::new(),
+        };
+
+        ActionDescription {
+            kind: ActionKind::Batch,
+            source,
+            destination: None,
+            replaced: None,
+            parts: self.parts.iter().map(|part| part.describe()).collect(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
+
+    fn file_count(&self) -> usize {
+        self.parts.iter().map(|part| part.file_count()).sum()
+    }
+}
+
+/// Undoes the parts in reverse order, redoing the ones already undone if one of them fails.
+fn undo_all(parts: &mut [Box<dyn Controllable>]) -> Result<(), Error> {
+    for index in (0..parts.len()).rev() {
+        if let Err(error) = parts[index].undo() {
+            for part in &mut parts[index + 1..] {
+                let _ = part.redo();
+            }
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Redoes the parts in order, undoing the ones already redone if one of them fails.
+fn redo_all(parts: &mut [Box<dyn Controllable>]) -> Result<(), Error> {
+    for index in 0..parts.len() {
+        if let Err(error) = parts[index].redo() {
+            for part in parts[..index].iter_mut().rev() {
+                let _ = part.undo();
+            }
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+fn purge_all(parts: &mut [Box<dyn Controllable>]) -> Result<(), Error> {
+    for part in parts {
+        part.purge()?;
+    }
+
+    Ok(())
+}
+
+/// A deletion that keeps the file in the trash so that it can be restored.
+///
+/// The file is only removed for good once the action is purged.
+pub struct Delete {
+    pub original_file_location: PathBuf,
+    pub trashed_file_location: PathBuf,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
+    pub timestamp: SystemTime,
+}
+
+impl Delete {
+    pub fn new(
+        original_location: PathBuf,
+        trashed_location: PathBuf,
+        filesystem_helper: Rc<dyn FilesystemIO>,
+    ) -> Delete {
+        Delete {
+            original_file_location: original_location,
+            trashed_file_location: trashed_location,
+            filesystem_helper,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl Controllable for Delete {
+    fn undo(&mut self) -> Result<(), Error> {
+        self.filesystem_helper
+            .restore_file(&self.trashed_file_location, &self.original_file_location)?;
+
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), Error> {
+        self.trashed_file_location = self
+            .filesystem_helper
+            .trash_file(&self.original_file_location)?;
+        self.timestamp = SystemTime::now();
+
+        Ok(())
+    }
+
+    fn purge(&mut self) -> Result<(), Error> {
+        self.filesystem_helper
+            .purge_file(&self.trashed_file_location)?;
+
+        Ok(())
+    }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Delete,
+            source: self.original_file_location.clone(),
+            destination: Some(self.trashed_file_location.clone()),
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
 }
 
 pub struct Skip {
-    // does nothing
+    pub file: PathBuf,
+    pub timestamp: SystemTime,
 }
 
 impl Skip {
-    pub fn new() -> Skip {
-        Skip {}
+    pub fn new(file: PathBuf) -> Skip {
+        Skip {
+            file,
+            timestamp: SystemTime::now(),
+        }
     }
 }
 
 impl Controllable for Skip {
-    fn undo(&self) -> Result<(), Error> {
+    fn undo(&mut self) -> Result<(), Error> {
         // do nothing except decrement pointer on lib
         Ok(())
     }
 
-    fn redo(&self) -> Result<(), Error> {
+    fn redo(&mut self) -> Result<(), Error> {
         // do nothing except increment pointer on lib
+        self.timestamp = SystemTime::now();
         Ok(())
     }
+
+    fn describe(&self) -> ActionDescription {
+        ActionDescription {
+            kind: ActionKind::Skip,
+            source: self.file.clone(),
+            destination: None,
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = timestamp;
+    }
 }
 
 pub trait Controllable {
-    fn undo(&self) -> Result<(), Error>;
-    fn redo(&self) -> Result<(), Error>;
+    fn undo(&mut self) -> Result<(), Error>;
+    fn redo(&mut self) -> Result<(), Error>;
+    /// Permanently drops anything the action keeps around to be able to undo itself.
+    ///
+    /// Called on actions in the undo stack once they can no longer be reached.
+    fn purge(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Returns a description of the action, from which it can be
+    /// [rebuilt](ActionDescription::into_action).
+    fn describe(&self) -> ActionDescription;
+    /// Overrides when the action was performed, used when rebuilding it.
+    fn set_timestamp(&mut self, timestamp: SystemTime);
+    /// Returns how many files the action was performed on, which is how far the current file
+    /// moves when undoing or redoing it.
+    fn file_count(&self) -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_flow::{
+        ActionDescription, ActionKind, Batch, Compound, Controllable, CopyFile, Delete, Link, Move,
+        Rename, Skip,
+    };
+    use crate::filesystem::{Filesystem, FilesystemIO};
+    use crate::transfer::LinkKind;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_file_is_moved_back_and_forth_when_undoing_and_redoing_move() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.txt");
+        let to_file = to_dir.path().join("file1.txt");
+        File::create(&to_file).unwrap();
+        let mut action = Move::new(
+            from_file.clone(),
+            to_file.clone(),
+            Rc::new(Filesystem::new()),
+        );
+
+        action.undo().unwrap();
+        assert!(from_file.exists());
+        assert!(!to_file.exists());
+
+        action.redo().unwrap();
+        assert!(!from_file.exists());
+        assert!(to_file.exists());
+    }
+
+    #[test]
+    fn ensure_deleted_file_is_restored_and_trashed_again_when_undoing_and_redoing_delete() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.txt");
+        fs::write(&file, "content").unwrap();
+        let helper = Rc::new(Filesystem::new());
+        let trashed = helper.trash_file(&file).unwrap();
+        let mut action = Delete::new(file.clone(), trashed.clone(), helper);
+
+        action.undo().unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+        assert!(!trashed.exists());
+
+        action.redo().unwrap();
+        assert!(!file.exists());
+        assert!(action.trashed_file_location.exists());
+
+        action.purge().unwrap();
+        assert!(!action.trashed_file_location.exists());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn ensure_copy_is_removed_and_made_again_when_undoing_and_redoing_copy() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.txt");
+        let to_file = to_dir.path().join("file1.txt");
+        fs::write(&from_file, "content").unwrap();
+        fs::copy(&from_file, &to_file).unwrap();
+        let mut action = CopyFile::new(
+            from_file.clone(),
+            to_file.clone(),
+            Rc::new(Filesystem::new()),
+        );
+
+        action.undo().unwrap();
+        assert!(from_file.exists());
+        assert!(!to_file.exists());
+
+        action.redo().unwrap();
+        assert_eq!(fs::read_to_string(&from_file).unwrap(), "content");
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "content");
+        let description = ActionDescription::decode(&action.describe().encode()).unwrap();
+        assert_eq!(description.kind, ActionKind::Copy);
+        assert_eq!(description, action.describe());
+    }
+
+    #[test]
+    fn ensure_file_gets_its_name_back_when_undoing_rename() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let original_file = dir.path().join("IMG_1234.jpg");
+        let renamed_file = dir.path().join("Sunset.jpg");
+        File::create(&renamed_file).unwrap();
+        let mut action = Rename::new(
+            original_file.clone(),
+            renamed_file.clone(),
+            Rc::new(Filesystem::new()),
+        );
+
+        action.undo().unwrap();
+        assert!(original_file.exists());
+        assert!(!renamed_file.exists());
+
+        action.redo().unwrap();
+        assert!(!original_file.exists());
+        assert!(renamed_file.exists());
+        assert_eq!(action.file_count(), 0);
+        let description = ActionDescription::decode(&action.describe().encode()).unwrap();
+        assert_eq!(description.kind, ActionKind::Rename);
+        assert_eq!(description, action.describe());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_link_is_removed_and_made_again_when_undoing_and_redoing_link() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.txt");
+        let link = dir.path().join("link1.txt");
+        fs::write(&file, "content").unwrap();
+        let helper = Rc::new(Filesystem::new());
+        helper
+            .link_file(&file, &link, LinkKind::RelativeSymbolic)
+            .unwrap();
+        let mut action = Link::new(
+            file.clone(),
+            link.clone(),
+            LinkKind::RelativeSymbolic,
+            helper,
+        );
+
+        action.undo().unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+
+        action.redo().unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("file1.txt"));
+        let description = ActionDescription::decode(&action.describe().encode()).unwrap();
+        assert_eq!(
+            description.kind,
+            ActionKind::Link(LinkKind::RelativeSymbolic)
+        );
+        assert_eq!(description, action.describe());
+    }
+
+    #[test]
+    fn ensure_compound_parts_are_undone_and_redone_together() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1\t.txt");
+        let copy_file = from_dir.path().join("copy\t1.txt");
+        let to_file = to_dir.path().join("file1\t.txt");
+        fs::write(&to_file, "content").unwrap();
+        fs::write(&copy_file, "content").unwrap();
+        let helper: Rc<dyn FilesystemIO> = Rc::new(Filesystem::new());
+        let parts: Vec<Box<dyn Controllable>> = vec![
+            Box::new(Move::new(
+                from_file.clone(),
+                to_file.clone(),
+                Rc::clone(&helper),
+            )),
+            Box::new(CopyFile::new(
+                to_file.clone(),
+                copy_file.clone(),
+                Rc::clone(&helper),
+            )),
+        ];
+        let mut action = Compound::new(from_file.clone(), parts);
+
+        action.undo().unwrap();
+        assert!(from_file.exists());
+        assert!(!to_file.exists());
+        assert!(!copy_file.exists());
+
+        action.redo().unwrap();
+        assert!(!from_file.exists());
+        assert!(to_file.exists());
+        assert!(copy_file.exists());
+
+        let description = action.describe();
+        let rebuilt = ActionDescription::decode(&description.encode())
+            .unwrap()
+            .into_action(helper)
+            .unwrap();
+        assert_eq!(rebuilt.describe(), description);
+        assert_eq!(description.parts[1].kind, ActionKind::Copy);
+    }
+
+    #[test]
+    fn ensure_batch_counts_and_rebuilds_its_files() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.txt");
+        let to_file = to_dir.path().join("file1.txt");
+        let skipped_file = from_dir.path().join("file2.txt");
+        fs::write(&to_file, "content").unwrap();
+        let helper: Rc<dyn FilesystemIO> = Rc::new(Filesystem::new());
+        let parts: Vec<Box<dyn Controllable>> = vec![
+            Box::new(Move::new(
+                from_file.clone(),
+                to_file.clone(),
+                Rc::clone(&helper),
+            )),
+            Box::new(Skip::new(skipped_file.clone())),
+        ];
+        let mut action = Batch::new(vec![Box::new(Batch::new(parts))]);
+        assert_eq!(action.file_count(), 2);
+
+        action.undo().unwrap();
+        assert!(from_file.exists());
+        assert!(!to_file.exists());
+
+        action.redo().unwrap();
+        assert!(to_file.exists());
+
+        let description = action.describe();
+        assert_eq!(description.source, from_file);
+        let rebuilt = ActionDescription::decode(&description.encode())
+            .unwrap()
+            .into_action(helper)
+            .unwrap();
+        assert_eq!(rebuilt.describe(), description);
+        assert_eq!(rebuilt.file_count(), 2);
+    }
+
+    #[test]
+    fn ensure_failed_compound_part_rolls_back_the_others() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let first = dir.path().join("first.txt");
+        let moved = dir.path().join("moved.txt");
+        let missing = dir.path().join("missing.txt");
+        fs::write(&first, "content").unwrap();
+        let helper: Rc<dyn FilesystemIO> = Rc::new(Filesystem::new());
+        let parts: Vec<Box<dyn Controllable>> = vec![
+            Box::new(Move::new(first.clone(), moved.clone(), Rc::clone(&helper))),
+            Box::new(Move::new(
+                missing.clone(),
+                dir.path().join("other.txt"),
+                helper,
+            )),
+        ];
+        let mut action = Compound::new(first.clone(), parts);
+
+        assert!(action.redo().is_err());
+
+        assert!(first.exists());
+        assert!(!moved.exists());
+    }
+
+    #[test]
+    fn ensure_description_survives_an_encoding_round_trip() {
+        let description = ActionDescription {
+            kind: ActionKind::Move,
+            source: PathBuf::from("./images/file\t1.jpg"),
+            destination: Some(PathBuf::from("./images/folder 1/file\t1.jpg")),
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: UNIX_EPOCH + Duration::new(1_700_000_000, 123),
+        };
+
+        let encoded = description.encode();
+
+        assert_eq!(
+            encoded,
+            "move\t./images/file\\t1.jpg\t./images/folder 1/file\\t1.jpg\t1700000000.000000123"
+        );
+        assert_eq!(ActionDescription::decode(&encoded).unwrap(), description);
+    }
+
+    #[test]
+    fn ensure_overwritten_file_is_restored_when_undoing_move() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.txt");
+        let to_file = to_dir.path().join("file1.txt");
+        let helper = Rc::new(Filesystem::new());
+        fs::write(&from_file, "new").unwrap();
+        fs::write(&to_file, "old").unwrap();
+        let replaced = helper.trash_file(&to_file).unwrap();
+        helper.move_file(&from_file, &to_file).unwrap();
+        let mut action = Move::new(from_file.clone(), to_file.clone(), helper);
+        action.replaced_file_location = Some(replaced);
+
+        action.undo().unwrap();
+        assert_eq!(fs::read_to_string(&from_file).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "old");
+
+        action.redo().unwrap();
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "new");
+        let description = ActionDescription::decode(&action.describe().encode()).unwrap();
+        assert_eq!(description, action.describe());
+
+        action.purge().unwrap();
+        assert!(!action.replaced_file_location.unwrap().exists());
+        assert!(!from_file.exists());
+    }
+
+    #[test]
+    fn ensure_action_is_rebuilt_from_its_description() {
+        let mut skip = Skip::new(PathBuf::from("./file1.png"));
+        skip.timestamp = UNIX_EPOCH + Duration::from_secs(10);
+        let description = skip.describe();
+
+        let rebuilt = ActionDescription::decode(&description.encode())
+            .unwrap()
+            .into_action(Rc::new(Filesystem::new()))
+            .unwrap();
+
+        assert_eq!(rebuilt.describe(), description);
+    }
+
+    #[test]
+    fn ensure_invalid_encodings_are_rejected() {
+        assert!(ActionDescription::decode("teleport\ta\tb\t0.0").is_err());
+        assert!(ActionDescription::decode("move\ta\tb").is_err());
+        assert!(ActionDescription::decode("move\ta\tb\tyesterday").is_err());
+        assert!(ActionDescription::decode("move\t/a\t/b\t18446744073709551615.0").is_err());
+        assert!(
+            ActionDescription::decode("move\t/a\t/b\t18446744073709551615.1000000000").is_err()
+        );
+        assert!(ActionDescription::decode("move\t/a\t/b\t0.1000000000").is_err());
+        assert!(ActionDescription::decode("move\ta\t\t0.0")
+            .unwrap()
+            .into_action(Rc::new(Filesystem::new()))
+            .is_err());
+    }
 }
@@ -0,0 +1,133 @@
+//! Just enough of the common image headers to find out how large an image is, without decoding
+//! it.
+
+use crate::format::FileFormat;
+use std::fs::File;
+use std::io::{BufReader, Error, Read};
+use std::path::Path;
+
+/// How much of a JPEG is searched for its frame header, which comes before the image data.
+const MAX_JPEG_HEADER_LENGTH: u64 = 256 * 1024;
+
+/// The number of bytes that hold the size of every format except JPEG.
+const HEADER_LENGTH: u64 = 30;
+
+/// Returns the width and height of a JPEG, PNG, GIF, WebP or BMP image, in pixels.
+///
+/// Other files, and images whose header is cut short, have no dimensions.
+///
+/// # Errors
+///
+/// If the file can't be read, an error variant will be returned.
+pub fn image_dimensions(file: &Path) -> Result<Option<(u32, u32)>, Error> {
+    let mut reader = BufReader::new(File::open(file)?).take(MAX_JPEG_HEADER_LENGTH);
+    let mut header = Vec::new();
+    reader
+        .by_ref()
+        .take(HEADER_LENGTH)
+        .read_to_end(&mut header)?;
+    let bytes = |offset: usize, length: usize| header.get(offset..offset + length);
+    let u16_le = |offset| bytes(offset, 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    let u32_le = |offset| {
+        bytes(offset, 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    let u32_be = |offset| {
+        bytes(offset, 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let dimensions = match FileFormat::detect(&header) {
+        FileFormat::Jpeg => return jpeg_dimensions(&header, &mut reader),
+        FileFormat::Png if header.get(12..16) == Some(b"IHDR") => u32_be(16).zip(u32_be(20)),
+        FileFormat::Gif => u16_le(6).map(u32::from).zip(u16_le(8).map(u32::from)),
+        FileFormat::Bmp => match u32_le(14) {
+            // the original OS/2 header stores the size in 16 bits
+            Some(12) => u16_le(18).map(u32::from).zip(u16_le(20).map(u32::from)),
+            Some(_) => u32_le(18)
+                .zip(u32_le(22))
+                .map(|(width, height)| (width, (height as i32).unsigned_abs())),
+            None => None,
+        },
+        FileFormat::WebP => match header.get(12..16) {
+            Some(b"VP8 ") => u16_le(26)
+                .zip(u16_le(28))
+                .map(|(width, height)| (u32::from(width & 0x3fff), u32::from(height & 0x3fff))),
+            Some(b"VP8L") => header.get(21..25).map(|bits| {
+                let bits = u32::from_le_bytes([bits[0], bits[1], bits[2], bits[3]]);
+                ((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)
+            }),
+            Some(b"VP8X") => header.get(24..30).map(|bytes| {
+                let u24 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+                (u24(&bytes[..3]) + 1, u24(&bytes[3..]) + 1)
+            }),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    Ok(dimensions)
+}
+
+/// Walks the JPEG segments after the header until the frame header, which holds the size.
+fn jpeg_dimensions(header: &[u8], reader: &mut impl Read) -> Result<Option<(u32, u32)>, Error> {
+    let mut data = header[2..].to_vec();
+    reader.read_to_end(&mut data)?;
+    let mut position = 0;
+    while let Some([0xff, marker, length_high, length_low]) = data.get(position..position + 4) {
+        let length = usize::from(u16::from_be_bytes([*length_high, *length_low]));
+        match marker {
+            0xd9 | 0xda => return Ok(None),
+            // start of frame markers, except those that define tables or are reserved
+            0xc0..=0xcf if ![0xc4, 0xc8, 0xcc].contains(marker) => {
+                let frame = match data.get(position + 5..position + 9) {
+                    Some(frame) => frame,
+                    None => return Ok(None),
+                };
+                let height = u16::from_be_bytes([frame[0], frame[1]]);
+                let width = u16::from_be_bytes([frame[2], frame[3]]);
+                return Ok(Some((u32::from(width), u32::from(height))));
+            }
+            _ => position += 2 + length,
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dimensions::image_dimensions;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_dimensions_are_read_from_headers() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let png = dir.path().join("image.png");
+        let gif = dir.path().join("image.gif");
+        let jpeg = dir.path().join("image.jpg");
+        let mut header = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        header.extend_from_slice(&[0, 0, 2, 128, 0, 0, 1, 224]);
+        fs::write(&png, header).unwrap();
+        fs::write(&gif, b"GIF89a\x40\x01\xf0\x00").unwrap();
+        let mut header = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0];
+        header.extend_from_slice(&[0xff, 0xc0, 0, 17, 8, 0x0b, 0xb8, 0x0f, 0xa0]);
+        fs::write(&jpeg, header).unwrap();
+
+        assert_eq!(image_dimensions(&png).unwrap(), Some((640, 480)));
+        assert_eq!(image_dimensions(&gif).unwrap(), Some((320, 240)));
+        assert_eq!(image_dimensions(&jpeg).unwrap(), Some((4000, 3000)));
+    }
+
+    #[test]
+    fn ensure_other_files_have_no_dimensions() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let text = dir.path().join("notes.txt");
+        let truncated = dir.path().join("truncated.jpg");
+        fs::write(&text, "not an image").unwrap();
+        fs::write(&truncated, [0xff, 0xd8, 0xff, 0xc0, 0, 17, 8]).unwrap();
+
+        assert_eq!(image_dimensions(&text).unwrap(), None);
+        assert_eq!(image_dimensions(&truncated).unwrap(), None);
+        assert!(image_dimensions(&dir.path().join("missing.png")).is_err());
+    }
+}
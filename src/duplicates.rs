@@ -0,0 +1,210 @@
+//! Finding byte-identical copies of a file in the folders it is sorted into.
+//!
+//! Folders are indexed by file size the first time they are searched, and the hashes of their
+//! files are only computed when a file of the same size is looked up. Both are kept between
+//! lookups so that sorting many files into the same folders doesn't read those folders again and
+//! again. A folder is indexed anew when it changed in a way the index doesn't know about.
+
+use crate::hash;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Default)]
+pub struct DuplicateIndex {
+    folders: HashMap<PathBuf, FolderIndex>,
+    /// Content hashes along with the modification time of the file when it was hashed.
+    hashes: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+struct FolderIndex {
+    /// When the folder was last changed while the index was up to date.
+    modified: SystemTime,
+    files_by_size: HashMap<u64, Vec<PathBuf>>,
+}
+
+impl DuplicateIndex {
+    /// Returns a file in the folder whose content is identical to the file's, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// If the file or the folder can't be read, an error variant will be returned.
+    pub fn find_duplicate(&mut self, file: &Path, folder: &Path) -> Result<Option<PathBuf>, Error> {
+        let size = fs::metadata(file)?.len();
+        let candidates = match self.folder_index(folder)?.files_by_size.get(&size) {
+            Some(candidates) => candidates.clone(),
+            None => return Ok(None),
+        };
+
+        let file_hash = self.hash_of(file)?;
+        for candidate in candidates {
+            if candidate == file {
+                continue;
+            }
+            // files that went away since they were indexed can't be duplicates
+            match self.hash_of(&candidate) {
+                Ok(candidate_hash) if candidate_hash == file_hash => {
+                    if has_same_content(file, &candidate)? {
+                        return Ok(Some(candidate));
+                    }
+                }
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Adds a file that was just put in the folder to its index, if the folder is indexed, so
+    /// that the folder doesn't have to be indexed again.
+    pub fn add_file(&mut self, folder: &Path, file: &Path) {
+        let folder_index = match self.folders.get_mut(folder) {
+            Some(folder_index) => folder_index,
+            None => return,
+        };
+        match (fs::metadata(folder), fs::metadata(file)) {
+            (Ok(folder_metadata), Ok(file_metadata)) => {
+                folder_index.modified =
+                    folder_metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                folder_index
+                    .files_by_size
+                    .entry(file_metadata.len())
+                    .or_default()
+                    .push(file.to_path_buf());
+            }
+            _ => {
+                self.folders.remove(folder);
+            }
+        }
+    }
+
+    fn folder_index(&mut self, folder: &Path) -> Result<&FolderIndex, Error> {
+        let modified = fs::metadata(folder)?
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let is_current = matches!(
+            self.folders.get(folder),
+            Some(folder_index) if folder_index.modified == modified
+        );
+        if !is_current {
+            let mut files_by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+            for dir_entry in fs::read_dir(folder)?.flatten() {
+                if let Ok(metadata) = fs::metadata(dir_entry.path()) {
+                    if metadata.is_file() {
+                        files_by_size
+                            .entry(metadata.len())
+                            .or_default()
+                            .push(dir_entry.path());
+                    }
+                }
+            }
+            self.folders.insert(
+                folder.to_path_buf(),
+                FolderIndex {
+                    modified,
+                    files_by_size,
+                },
+            );
+        }
+
+        Ok(&self.folders[folder])
+    }
+
+    fn hash_of(&mut self, file: &Path) -> Result<u64, Error> {
+        let modified = fs::metadata(file)?
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        match self.hashes.get(file) {
+            Some((hashed_modified, hash)) if *hashed_modified == modified => Ok(*hash),
+            _ => {
+                let hash = hash::hash_file(file)?;
+                self.hashes.insert(file.to_path_buf(), (modified, hash));
+                Ok(hash)
+            }
+        }
+    }
+}
+
+/// Compares two files byte by byte, since equal hashes only make identical content likely.
+fn has_same_content(first: &Path, second: &Path) -> Result<bool, Error> {
+    let mut first = File::open(first)?;
+    let mut second = File::open(second)?;
+    let mut first_buffer = [0; 64 * 1024];
+    let mut second_buffer = [0; 64 * 1024];
+    loop {
+        let read = first.read(&mut first_buffer)?;
+        if read == 0 {
+            return Ok(second.read(&mut second_buffer[..1])? == 0);
+        }
+        if second.read_exact(&mut second_buffer[..read]).is_err()
+            || first_buffer[..read] != second_buffer[..read]
+        {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duplicates::DuplicateIndex;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_identical_file_is_found_in_folder() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let folder = dir.path().join("folder1");
+        fs::create_dir(&folder).unwrap();
+        let file = dir.path().join("IMG_0001.jpg");
+        fs::write(&file, "photo").unwrap();
+        fs::write(folder.join("copy.jpg"), "photo").unwrap();
+        fs::write(folder.join("other.jpg"), "phone").unwrap();
+        fs::write(folder.join("larger.jpg"), "photos").unwrap();
+        let mut index = DuplicateIndex::default();
+
+        let actual = index.find_duplicate(&file, &folder).unwrap();
+
+        assert_eq!(actual, Some(folder.join("copy.jpg")));
+    }
+
+    #[test]
+    fn ensure_no_duplicate_is_found_for_different_content() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let folder = dir.path().join("folder1");
+        fs::create_dir(&folder).unwrap();
+        let file = dir.path().join("IMG_0001.jpg");
+        fs::write(&file, "photo").unwrap();
+        fs::write(folder.join("other.jpg"), "phone").unwrap();
+        let mut index = DuplicateIndex::default();
+
+        assert_eq!(index.find_duplicate(&file, &folder).unwrap(), None);
+        assert!(index
+            .find_duplicate(&file, &dir.path().join("missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn ensure_added_and_removed_files_are_noticed() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let folder = dir.path().join("folder1");
+        fs::create_dir(&folder).unwrap();
+        let file = dir.path().join("IMG_0001.jpg");
+        fs::write(&file, "photo").unwrap();
+        let mut index = DuplicateIndex::default();
+        assert_eq!(index.find_duplicate(&file, &folder).unwrap(), None);
+
+        fs::write(folder.join("copy.jpg"), "photo").unwrap();
+        index.add_file(&folder, &folder.join("copy.jpg"));
+        assert_eq!(
+            index.find_duplicate(&file, &folder).unwrap(),
+            Some(folder.join("copy.jpg"))
+        );
+
+        fs::remove_file(folder.join("copy.jpg")).unwrap();
+        assert_eq!(index.find_duplicate(&file, &folder).unwrap(), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Escaping used by the textual encodings of actions and journal entries.
+//!
+//! Encoded values are tab separated fields on a single line, so backslashes, tabs and line
+//! breaks inside a field are escaped. Bytes of paths that aren't valid UTF-8 are kept as `\xHH`
+//! escapes so that every path survives a round trip.
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+pub const FIELD_SEPARATOR: char = '\t';
+
+pub fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for character in field.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+pub fn unescape(field: &str) -> Result<String, Error> {
+    let bytes = unescape_bytes(field)?;
+    String::from_utf8(bytes).map_err(|_| Error::from(ErrorKind::InvalidData))
+}
+
+fn unescape_bytes(field: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::from(ErrorKind::InvalidData);
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut characters = field.bytes();
+    while let Some(byte) = characters.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match characters.next().ok_or_else(invalid)? {
+            b'\\' => bytes.push(b'\\'),
+            b't' => bytes.push(b'\t'),
+            b'n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b'x' => {
+                let high = characters.next().ok_or_else(invalid)?;
+                let low = characters.next().ok_or_else(invalid)?;
+                let hex = [high, low];
+                let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Escapes a path, keeping bytes that aren't valid UTF-8 as `\xHH` escapes.
+#[cfg(unix)]
+pub fn escape_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut escaped = String::new();
+    for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+        escaped.push_str(&escape(chunk.valid()));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+#[cfg(not(unix))]
+pub fn escape_path(path: &Path) -> String {
+    escape(&path.to_string_lossy())
+}
+
+#[cfg(unix)]
+pub fn unescape_path(field: &str) -> Result<PathBuf, Error> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    Ok(PathBuf::from(OsString::from_vec(unescape_bytes(field)?)))
+}
+
+#[cfg(not(unix))]
+pub fn unescape_path(field: &str) -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(unescape(field)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::{escape, escape_path, unescape, unescape_path};
+    use std::path::PathBuf;
+
+    #[test]
+    fn ensure_special_characters_survive_a_round_trip() {
+        let field = "tab\there\nnew line\\backslash\r";
+
+        let escaped = escape(field);
+
+        assert!(!escaped.contains('\t'));
+        assert!(!escaped.contains('\n'));
+        assert_eq!(unescape(&escaped).unwrap(), field);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_paths_that_are_not_utf8_survive_a_round_trip() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let path = PathBuf::from(OsString::from_vec(b"./images/caf\xe9.jpg".to_vec()));
+
+        let escaped = escape_path(&path);
+
+        assert_eq!(escaped, "./images/caf\\xe9.jpg");
+        assert_eq!(unescape_path(&escaped).unwrap(), path);
+    }
+
+    #[test]
+    fn ensure_unknown_escapes_are_rejected() {
+        assert!(unescape("\\q").is_err());
+        assert!(unescape("trailing\\").is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Just enough of an EXIF reader to describe how, when and where a photo was taken.
+//!
+//! EXIF data is stored as a TIFF structure: a byte order mark, followed by directories of tagged
+//! entries that may point at further directories. The [metadata](crate::metadata) module finds
+//! that structure in the different file formats.
+
+use crate::metadata::{GpsPosition, Metadata};
+use crate::trash;
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const IMAGE_WIDTH_TAG: u16 = 0x0100;
+const IMAGE_HEIGHT_TAG: u16 = 0x0101;
+const MAKE_TAG: u16 = 0x010f;
+const MODEL_TAG: u16 = 0x0110;
+const ORIENTATION_TAG: u16 = 0x0112;
+const DATE_TIME_TAG: u16 = 0x0132;
+const EXIF_DIRECTORY_TAG: u16 = 0x8769;
+const GPS_DIRECTORY_TAG: u16 = 0x8825;
+
+const EXPOSURE_TIME_TAG: u16 = 0x829a;
+const F_NUMBER_TAG: u16 = 0x829d;
+const ISO_TAG: u16 = 0x8827;
+const DATE_TIME_ORIGINAL_TAG: u16 = 0x9003;
+const FOCAL_LENGTH_TAG: u16 = 0x920a;
+const PIXEL_WIDTH_TAG: u16 = 0xa002;
+const PIXEL_HEIGHT_TAG: u16 = 0xa003;
+const LENS_MODEL_TAG: u16 = 0xa434;
+
+const LATITUDE_REF_TAG: u16 = 0x0001;
+const LATITUDE_TAG: u16 = 0x0002;
+const LONGITUDE_REF_TAG: u16 = 0x0003;
+const LONGITUDE_TAG: u16 = 0x0004;
+const ALTITUDE_REF_TAG: u16 = 0x0005;
+const ALTITUDE_TAG: u16 = 0x0006;
+
+const BYTE_TYPE: u16 = 1;
+const ASCII_TYPE: u16 = 2;
+const SHORT_TYPE: u16 = 3;
+const LONG_TYPE: u16 = 4;
+const RATIONAL_TYPE: u16 = 5;
+
+/// Reads the EXIF fields of a TIFF structure into the metadata. Fields the structure doesn't
+/// have, or that are malformed, are left as they are.
+pub fn read_tiff(data: &[u8], metadata: &mut Metadata) {
+    let tiff = match Tiff::new(data) {
+        Some(tiff) => tiff,
+        None => return,
+    };
+    let first_directory = match tiff.u32_at(4) {
+        Some(offset) => offset as usize,
+        None => return,
+    };
+    let directory_at = |tag| {
+        tiff.find_entry(first_directory, tag)
+            .filter(|entry| entry.field_type == LONG_TYPE)
+            .map(|entry| entry.value_offset as usize)
+    };
+    let exif_directory = directory_at(EXIF_DIRECTORY_TAG);
+    let gps_directory = directory_at(GPS_DIRECTORY_TAG);
+    let first = |tag| tiff.find_entry(first_directory, tag);
+    let exif = |tag| exif_directory.and_then(|directory| tiff.find_entry(directory, tag));
+
+    let date = exif(DATE_TIME_ORIGINAL_TAG)
+        .or_else(|| first(DATE_TIME_TAG))
+        .and_then(|entry| tiff.ascii(&entry))
+        .filter(|date| is_capture_date(date) && !date.starts_with("0000"));
+    let text = |entry: Option<Entry>| {
+        entry
+            .and_then(|entry| tiff.ascii(&entry))
+            .map(|text| text.trim().to_owned())
+            .filter(|text| !text.is_empty())
+    };
+    let number = |entry: Option<Entry>| {
+        entry
+            .and_then(|entry| tiff.rational(&entry, 0))
+            .filter(|(_, denominator)| *denominator != 0)
+            .map(|(numerator, denominator)| f64::from(numerator) / f64::from(denominator))
+    };
+    let dimensions = first(IMAGE_WIDTH_TAG)
+        .zip(first(IMAGE_HEIGHT_TAG))
+        .or_else(|| exif(PIXEL_WIDTH_TAG).zip(exif(PIXEL_HEIGHT_TAG)))
+        .and_then(|(width, height)| tiff.unsigned(&width).zip(tiff.unsigned(&height)));
+
+    fill(&mut metadata.capture_date, date);
+    fill(&mut metadata.camera_make, text(first(MAKE_TAG)));
+    fill(&mut metadata.camera_model, text(first(MODEL_TAG)));
+    fill(&mut metadata.lens_model, text(exif(LENS_MODEL_TAG)));
+    fill(
+        &mut metadata.exposure_time,
+        exif(EXPOSURE_TIME_TAG)
+            .and_then(|entry| tiff.rational(&entry, 0))
+            .filter(|(_, denominator)| *denominator != 0),
+    );
+    fill(&mut metadata.f_number, number(exif(F_NUMBER_TAG)));
+    fill(
+        &mut metadata.iso,
+        exif(ISO_TAG).and_then(|entry| tiff.unsigned(&entry)),
+    );
+    fill(&mut metadata.focal_length, number(exif(FOCAL_LENGTH_TAG)));
+    fill(
+        &mut metadata.orientation,
+        first(ORIENTATION_TAG)
+            .and_then(|entry| tiff.unsigned(&entry))
+            .and_then(|orientation| u16::try_from(orientation).ok())
+            .filter(|orientation| (1..=8).contains(orientation)),
+    );
+    fill(
+        &mut metadata.gps,
+        gps_directory.and_then(|directory| read_gps(&tiff, directory)),
+    );
+    fill(&mut metadata.dimensions, dimensions);
+}
+
+fn fill<T>(field: &mut Option<T>, value: Option<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+/// Reads the position from the GPS directory, as signed degrees and meters above sea level.
+fn read_gps(tiff: &Tiff, directory: usize) -> Option<GpsPosition> {
+    let degrees = |tag, ref_tag, negative_ref: &str| {
+        let entry = tiff.find_entry(directory, tag)?;
+        let mut degrees = 0.0;
+        // degrees, minutes and seconds
+        for (index, scale) in [1.0, 60.0, 3600.0].iter().enumerate() {
+            let (numerator, denominator) = tiff.rational(&entry, index)?;
+            if denominator != 0 {
+                degrees += f64::from(numerator) / f64::from(denominator) / scale;
+            }
+        }
+        let reference = tiff
+            .find_entry(directory, ref_tag)
+            .and_then(|entry| tiff.ascii(&entry));
+        match reference.as_deref().map(str::trim) {
+            Some(reference) if reference.eq_ignore_ascii_case(negative_ref) => Some(-degrees),
+            _ => Some(degrees),
+        }
+    };
+    let altitude = tiff
+        .find_entry(directory, ALTITUDE_TAG)
+        .and_then(|entry| tiff.rational(&entry, 0))
+        .filter(|(_, denominator)| *denominator != 0)
+        .map(|(numerator, denominator)| f64::from(numerator) / f64::from(denominator))
+        .map(|altitude| {
+            let below_sea_level = tiff
+                .find_entry(directory, ALTITUDE_REF_TAG)
+                .and_then(|entry| tiff.unsigned(&entry))
+                == Some(1);
+            match below_sea_level {
+                true => -altitude,
+                false => altitude,
+            }
+        });
+
+    Some(GpsPosition {
+        latitude: degrees(LATITUDE_TAG, LATITUDE_REF_TAG, "S")?,
+        longitude: degrees(LONGITUDE_TAG, LONGITUDE_REF_TAG, "W")?,
+        altitude,
+    })
+}
+
+/// Returns whether a date is written as `YYYY:MM:DD HH:MM:SS` with digits only, since capture
+/// dates end up in folder and file names.
+fn is_capture_date(date: &str) -> bool {
+    date.len() == 19
+        && date.bytes().enumerate().all(|(index, byte)| match index {
+            4 | 7 | 13 | 16 => byte == b':',
+            10 => byte == b' ',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+/// Formats a time like capture dates are written, as `YYYY:MM:DD HH:MM:SS` in local time, so
+/// that times of files without a capture date can stand in for it. Cameras write the capture
+/// date in their local time as well.
+pub fn format_time(time: SystemTime) -> String {
+    format_time_at_offset(time, trash::local_utc_offset(time))
+}
+
+/// Formats a time like [format_time], shifted from UTC by the offset in seconds.
+fn format_time_at_offset(time: SystemTime, utc_offset: i64) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => 0,
+    } + utc_offset;
+    let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let (year, month, day) = trash::civil_from_days(days);
+
+    format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// A tagged entry in a TIFF directory.
+struct Entry {
+    field_type: u16,
+    count: u32,
+    /// The value itself if it fits in four bytes, otherwise where it is stored.
+    value_offset: u32,
+    /// Where the entry's value field is, for values small enough to be stored inline.
+    position: usize,
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let little_endian = match data.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+
+        Some(Tiff {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Some(match self.little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn find_entry(&self, directory: usize, tag: u16) -> Option<Entry> {
+        let entry_count = usize::from(self.u16_at(directory)?);
+        (0..entry_count)
+            .map(|index| directory + 2 + index * 12)
+            .find(|position| self.u16_at(*position) == Some(tag))
+            .and_then(|position| {
+                Some(Entry {
+                    field_type: self.u16_at(position + 2)?,
+                    count: self.u32_at(position + 4)?,
+                    value_offset: self.u32_at(position + 8)?,
+                    position: position + 8,
+                })
+            })
+    }
+
+    /// Returns where the entry's values start, given the size of each of them.
+    fn value_start(&self, entry: &Entry, value_size: usize) -> Option<usize> {
+        match (entry.count as usize).checked_mul(value_size)? <= 4 {
+            true => Some(entry.position),
+            false => Some(entry.value_offset as usize),
+        }
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<String> {
+        if entry.field_type != ASCII_TYPE {
+            return None;
+        }
+        let length = entry.count as usize;
+        let start = self.value_start(entry, 1)?;
+        let bytes = self.data.get(start..start.checked_add(length)?)?;
+        let bytes = bytes.split(|byte| *byte == 0).next().unwrap_or_default();
+
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    /// Returns the first value of a byte, short or long entry.
+    fn unsigned(&self, entry: &Entry) -> Option<u32> {
+        if entry.count == 0 {
+            return None;
+        }
+        match entry.field_type {
+            BYTE_TYPE => self.data.get(entry.position).map(|byte| u32::from(*byte)),
+            SHORT_TYPE => self.u16_at(entry.position).map(u32::from),
+            LONG_TYPE => Some(entry.value_offset),
+            _ => None,
+        }
+    }
+
+    /// Returns a value of a rational entry as its numerator and denominator.
+    fn rational(&self, entry: &Entry, index: usize) -> Option<(u32, u32)> {
+        if entry.field_type != RATIONAL_TYPE || index >= entry.count as usize {
+            return None;
+        }
+        let start = self.value_start(entry, 8)? + index * 8;
+
+        Some((self.u32_at(start)?, self.u32_at(start + 4)?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::exif::{format_time, format_time_at_offset, read_tiff};
+    use crate::metadata::{GpsPosition, Metadata};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Builds a little endian TIFF structure whose EXIF directory has the capture date.
+    pub(crate) fn build_tiff(date: &str) -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        // the first directory only points at the EXIF directory at offset 26
+        tiff.extend_from_slice(&[1, 0, 0x69, 0x87, 4, 0, 1, 0, 0, 0, 26, 0, 0, 0]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        // the EXIF directory, with the date stored after it at offset 44
+        tiff.extend_from_slice(&[1, 0, 0x03, 0x90, 2, 0, 20, 0, 0, 0, 44, 0, 0, 0]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(date.as_bytes());
+        tiff.push(0);
+        tiff
+    }
+
+    /// Builds a big endian TIFF structure with a camera, an exposure and a GPS position.
+    fn build_camera_tiff() -> Vec<u8> {
+        let mut tiff = b"MM\0*\0\0\0\x08".to_vec();
+        let entry = |tiff: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: u32| {
+            tiff.extend_from_slice(&tag.to_be_bytes());
+            tiff.extend_from_slice(&field_type.to_be_bytes());
+            tiff.extend_from_slice(&count.to_be_bytes());
+            tiff.extend_from_slice(&value.to_be_bytes());
+        };
+        // the first directory at 8 has 4 entries and ends at 62
+        tiff.extend_from_slice(&[0, 4]);
+        entry(&mut tiff, 0x0110, 2, 4, u32::from_be_bytes(*b"X1 \0"));
+        entry(&mut tiff, 0x0112, 3, 1, 6 << 16);
+        entry(&mut tiff, 0x8769, 4, 1, 62);
+        entry(&mut tiff, 0x8825, 4, 1, 104);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        // the EXIF directory at 62 has 3 entries and ends at 104, with values after the GPS one
+        tiff.extend_from_slice(&[0, 3]);
+        entry(&mut tiff, 0x829a, 5, 1, 170);
+        entry(&mut tiff, 0x829d, 5, 1, 178);
+        entry(&mut tiff, 0x8827, 3, 1, 400 << 16);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        // the GPS directory at 104 has 5 entries and ends at 170
+        tiff.extend_from_slice(&[0, 5]);
+        entry(&mut tiff, 0x0001, 2, 2, u32::from_be_bytes(*b"S\0\0\0"));
+        entry(&mut tiff, 0x0002, 5, 3, 186);
+        entry(&mut tiff, 0x0003, 2, 2, u32::from_be_bytes(*b"E\0\0\0"));
+        entry(&mut tiff, 0x0004, 5, 3, 210);
+        entry(&mut tiff, 0x0006, 5, 1, 234);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        for (numerator, denominator) in [
+            (1, 250),
+            (28, 10),
+            (33, 1),
+            (51, 1),
+            (36, 1),
+            (151, 1),
+            (12, 1),
+            (0, 1),
+            (58, 1),
+        ] {
+            tiff.extend_from_slice(&u32::to_be_bytes(numerator));
+            tiff.extend_from_slice(&u32::to_be_bytes(denominator));
+        }
+        tiff
+    }
+
+    #[test]
+    fn ensure_capture_date_is_read() {
+        let mut metadata = Metadata::default();
+
+        read_tiff(&build_tiff("1999:12:31 23:59:59"), &mut metadata);
+
+        assert_eq!(
+            metadata.capture_date.as_deref(),
+            Some("1999:12:31 23:59:59")
+        );
+        assert_eq!(metadata.camera_model, None);
+    }
+
+    #[test]
+    fn ensure_malformed_capture_dates_are_ignored() {
+        for date in [
+            "x/..:..:.. 00:00:00",
+            "2021:07:0\u{e9}18:30:00",
+            "2021-07-04 18:30:00",
+            "    :  :     :  :  ",
+        ] {
+            let mut metadata = Metadata::default();
+
+            read_tiff(&build_tiff(date), &mut metadata);
+
+            assert_eq!(metadata.capture_date, None, "{}", date);
+        }
+    }
+
+    #[test]
+    fn ensure_camera_exposure_and_position_are_read() {
+        let mut metadata = Metadata::default();
+
+        read_tiff(&build_camera_tiff(), &mut metadata);
+
+        assert_eq!(metadata.camera_model.as_deref(), Some("X1"));
+        assert_eq!(metadata.orientation, Some(6));
+        assert_eq!(metadata.exposure_time, Some((1, 250)));
+        assert_eq!(metadata.f_number, Some(2.8));
+        assert_eq!(metadata.iso, Some(400));
+        let GpsPosition {
+            latitude,
+            longitude,
+            altitude,
+        } = metadata.gps.unwrap();
+        assert!((latitude + 33.86).abs() < 1e-9);
+        assert!((longitude - 151.2).abs() < 1e-9);
+        assert_eq!(altitude, Some(58.0));
+    }
+
+    #[test]
+    fn ensure_malformed_structures_are_ignored() {
+        let mut metadata = Metadata::default();
+        let mut truncated = build_tiff("2021:07:04 18:30:00");
+        truncated.truncate(40);
+
+        read_tiff(b"not a tiff", &mut metadata);
+        read_tiff(&truncated, &mut metadata);
+
+        assert_eq!(metadata, Metadata::default());
+    }
+
+    #[test]
+    fn ensure_times_are_formatted_like_capture_dates() {
+        assert_eq!(format_time_at_offset(UNIX_EPOCH, 0), "1970:01:01 00:00:00");
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661);
+        assert_eq!(format_time_at_offset(time, 0), "2000:02:29 01:01:01");
+        let time = UNIX_EPOCH + Duration::from_secs(1_735_689_599);
+        assert_eq!(format_time_at_offset(time, 0), "2024:12:31 23:59:59");
+    }
+
+    #[test]
+    fn ensure_times_are_formatted_in_local_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_735_689_599);
+        assert_eq!(format_time_at_offset(time, 3600), "2025:01:01 00:59:59");
+        assert_eq!(
+            format_time_at_offset(UNIX_EPOCH, -3600),
+            "1969:12:31 23:00:00"
+        );
+        assert_eq!(
+            format_time(time),
+            format_time_at_offset(time, crate::trash::local_utc_offset(time))
+        );
+    }
+}
@@ -1,23 +1,79 @@
-use std::fs;
-use std::io::{Error, ErrorKind};
-use std::path::{Path, PathBuf};
+use crate::hash;
+use crate::transfer::LinkKind;
+use crate::trash::{self, TrashMode, TRASH_FOLDER_NAME};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, File, FileTimes, OpenOptions};
+use std::io::{self, Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Default, Clone)]
-pub struct Filesystem {}
+pub struct Filesystem {
+    /// Where deleted files are put.
+    trash_mode: RefCell<TrashMode>,
+}
+
+/// How deep [Backend::load_folders_and_files](crate::Backend::load_folders_and_files) looks for
+/// files in subfolders.
+///
+/// Files found this way are ordered directory by directory: the files directly in a directory
+/// come first, sorted by name, followed by the files of each of its subfolders in name order.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct Recursion {
+    /// How many levels of subfolders are searched. `0` only loads the files directly in the
+    /// directory and [usize::MAX] searches every level.
+    pub max_depth: usize,
+    /// Don't search the loaded folders, since they're where files are sorted into.
+    pub exclude_folders: bool,
+}
 
 pub trait FilesystemIO {
     fn load_filesystem_elements(
         &self,
         directory: &Path,
     ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error>;
+    /// Returns the files in a directory and in its subfolders down to the maximum depth, except
+    /// for those in the excluded folders. Each folder is only visited once, even if symbolic
+    /// links lead back to it.
+    fn load_files_recursively(
+        &self,
+        directory: &Path,
+        max_depth: usize,
+        excluded_folders: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, Error>;
     fn delete_file(&self, file: &Path) -> Result<(), Error>;
     fn move_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error>;
+    /// Gives a file a new name, failing if another file already has it. Only changing the case
+    /// of the name is allowed even where the filesystem ignores case.
+    fn rename_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error>;
+    /// Returns whether anything is at a path, including a symbolic link that leads nowhere.
+    fn exists(&self, path: &Path) -> bool;
+    /// Copies a file along with its modification time and permissions, without replacing an
+    /// existing file.
+    fn copy_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error>;
+    /// Creates a link to a file, without replacing an existing file.
+    fn link_file(&self, from_file: &Path, to_file: &Path, kind: LinkKind) -> Result<(), Error>;
     fn add_folder(&self, folder: &str) -> Result<PathBuf, Error>;
+    /// Creates a folder in an existing one, failing if it already exists.
+    fn create_folder(&self, folder: &Path) -> Result<(), Error>;
+    /// Removes a folder if it is empty, leaving it as it is otherwise.
+    fn remove_empty_folder(&self, folder: &Path) -> Result<(), Error>;
+    /// Moves a file into the trash and returns where it ended up.
+    fn trash_file(&self, file: &Path) -> Result<PathBuf, Error>;
+    /// Moves a previously trashed file back to its original location.
+    fn restore_file(&self, trashed_file: &Path, original_file: &Path) -> Result<(), Error>;
+    /// Permanently removes a previously trashed file.
+    fn purge_file(&self, trashed_file: &Path) -> Result<(), Error>;
+    /// Sets where files trashed from now on are put. Files that were already trashed are still
+    /// restored from and purged in the trash they were put in.
+    fn set_trash_mode(&self, trash_mode: TrashMode);
 }
 
 impl Filesystem {
     pub fn new() -> Filesystem {
-        Filesystem {}
+        Filesystem {
+            trash_mode: RefCell::new(TrashMode::Staging),
+        }
     }
 }
 
@@ -26,23 +82,23 @@ impl FilesystemIO for Filesystem {
         &self,
         directory: &Path,
     ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
-        let mut files: Vec<PathBuf> = Vec::new();
-        let mut folders: Vec<PathBuf> = Vec::new();
-        let paths = fs::read_dir(directory)?;
-
-        for dir_entry in paths.flatten() {
-            if let Ok(file_type) = dir_entry.file_type() {
-                match file_type.is_file() {
-                    true => files.push(dir_entry.path()),
-                    false => folders.push(dir_entry.path())
-                }
-            }
-        }
-
-        folders.sort();
-        files.sort();
+        read_folder(directory)
+    }
 
-        Ok((folders, files))
+    fn load_files_recursively(
+        &self,
+        directory: &Path,
+        max_depth: usize,
+        excluded_folders: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut visited: HashSet<PathBuf> = excluded_folders
+            .iter()
+            .filter_map(|folder| fs::canonicalize(folder).ok())
+            .collect();
+        let mut files = Vec::new();
+        visit_folder(directory, max_depth, &mut visited, &mut files)?;
+
+        Ok(files)
     }
 
     fn delete_file(&self, file: &Path) -> Result<(), Error> {
@@ -51,11 +107,55 @@ impl FilesystemIO for Filesystem {
     }
 
     fn move_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error> {
-        if to_file.exists() {
+        if self.exists(to_file) {
             return Err(Error::from(ErrorKind::AlreadyExists));
         }
-        fs::rename(from_file, to_file)?;
-        Ok(())
+        match fs::rename(from_file, to_file) {
+            Err(error) if error.kind() == ErrorKind::CrossesDevices => {
+                copy_and_remove(from_file, to_file)
+            }
+            result => result,
+        }
+    }
+
+    fn rename_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error> {
+        // a name that only differs in case may lead back to the file itself
+        let taken = match fs::symlink_metadata(to_file) {
+            Ok(metadata) if metadata.file_type().is_symlink() => true,
+            Ok(_) => fs::canonicalize(to_file)? != fs::canonicalize(from_file)?,
+            Err(_) => false,
+        };
+        if taken {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", to_file.display()),
+            ));
+        }
+
+        fs::rename(from_file, to_file)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok()
+    }
+
+    fn copy_file(&self, from_file: &Path, to_file: &Path) -> Result<(), Error> {
+        copy_verified(from_file, to_file)
+    }
+
+    fn link_file(&self, from_file: &Path, to_file: &Path, kind: LinkKind) -> Result<(), Error> {
+        let target = match kind {
+            LinkKind::Hard => return fs::hard_link(from_file, to_file),
+            LinkKind::Symbolic => fs::canonicalize(from_file)?,
+            LinkKind::RelativeSymbolic => {
+                let folder = to_file
+                    .parent()
+                    .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+                relative_path(&fs::canonicalize(folder)?, &fs::canonicalize(from_file)?)
+            }
+        };
+
+        symbolic_link(&target, to_file)
     }
 
     fn add_folder(&self, folder: &str) -> Result<PathBuf, Error> {
@@ -65,14 +165,185 @@ impl FilesystemIO for Filesystem {
             false => Err(Error::from(ErrorKind::NotFound)),
         }
     }
+
+    fn create_folder(&self, folder: &Path) -> Result<(), Error> {
+        fs::create_dir(folder)
+    }
+
+    fn remove_empty_folder(&self, folder: &Path) -> Result<(), Error> {
+        match fs::read_dir(folder)?.next().is_none() {
+            true => fs::remove_dir(folder),
+            false => Ok(()),
+        }
+    }
+
+    fn trash_file(&self, file: &Path) -> Result<PathBuf, Error> {
+        match &*self.trash_mode.borrow() {
+            TrashMode::Staging => trash::stage_file(file),
+            TrashMode::FreeDesktop { data_home } => trash::free_desktop_trash_file(file, data_home),
+        }
+    }
+
+    fn restore_file(&self, trashed_file: &Path, original_file: &Path) -> Result<(), Error> {
+        match trash::is_staged(trashed_file) {
+            true => trash::restore_staged_file(trashed_file, original_file),
+            false => trash::free_desktop_restore_file(trashed_file, original_file),
+        }
+    }
+
+    fn purge_file(&self, trashed_file: &Path) -> Result<(), Error> {
+        match trash::is_staged(trashed_file) {
+            true => trash::purge_staged_file(trashed_file),
+            // the desktop's trash owns the file now
+            false => Ok(()),
+        }
+    }
+
+    fn set_trash_mode(&self, trash_mode: TrashMode) {
+        *self.trash_mode.borrow_mut() = trash_mode;
+    }
+}
+
+/// Moves a file to another filesystem, where it can't simply be renamed. The original is only
+/// removed once its copy is known to be complete.
+fn copy_and_remove(from_file: &Path, to_file: &Path) -> Result<(), Error> {
+    copy_verified(from_file, to_file)?;
+    fs::remove_file(from_file)
+}
+
+/// Copies a file and syncs the copy to disk, then checks that it has the same size and content.
+/// The copy keeps the original's modification time and permissions. If anything fails, the copy
+/// is removed again.
+fn copy_verified(from_file: &Path, to_file: &Path) -> Result<(), Error> {
+    let mut source = File::open(from_file)?;
+    let metadata = source.metadata()?;
+    let mut destination = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to_file)?;
+
+    let copied = io::copy(&mut source, &mut destination)
+        .and_then(|_| {
+            let mut times = FileTimes::new().set_modified(metadata.modified()?);
+            if let Ok(accessed) = metadata.accessed() {
+                times = times.set_accessed(accessed);
+            }
+            destination.set_times(times)
+        })
+        .and_then(|_| destination.set_permissions(metadata.permissions()))
+        .and_then(|_| destination.sync_all())
+        .and_then(|_| verify_copy(from_file, to_file, metadata.len()));
+    drop(destination);
+    if let Err(error) = copied {
+        let _ = fs::remove_file(to_file);
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn verify_copy(from_file: &Path, to_file: &Path, length: u64) -> Result<(), Error> {
+    if fs::metadata(to_file)?.len() != length
+        || hash::hash_file(from_file)? != hash::hash_file(to_file)?
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("the copy of {} differs from it", from_file.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symbolic_link(target: &Path, link: &Path) -> Result<(), Error> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symbolic_link(target: &Path, link: &Path) -> Result<(), Error> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symbolic_link(_target: &Path, _link: &Path) -> Result<(), Error> {
+    Err(Error::from(ErrorKind::Unsupported))
+}
+
+/// Returns the path that leads from a folder to the target. Both paths must be canonical.
+fn relative_path(folder: &Path, target: &Path) -> PathBuf {
+    let folder: Vec<Component> = folder.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    let common_length = folder
+        .iter()
+        .zip(&target)
+        .take_while(|(folder_component, target_component)| folder_component == target_component)
+        .count();
+
+    let mut path = PathBuf::new();
+    for _ in common_length..folder.len() {
+        path.push(Component::ParentDir);
+    }
+    path.extend(&target[common_length..]);
+
+    path
+}
+
+/// Collects the files in a folder and its subfolders depth-first. Folders are compared by their
+/// canonical path so that symbolic link loops are only followed once.
+/// Returns the (folders, files) directly in a folder, sorted by name. Symbolic links count as
+/// what they point to, and those that lead nowhere are left out.
+fn read_folder(folder: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
+    let mut folders = Vec::new();
+    let mut files = Vec::new();
+    for dir_entry in fs::read_dir(folder)?.flatten() {
+        if dir_entry.file_name() == TRASH_FOLDER_NAME {
+            continue;
+        }
+        match fs::metadata(dir_entry.path()) {
+            Ok(metadata) if metadata.is_file() => files.push(dir_entry.path()),
+            Ok(metadata) if metadata.is_dir() => folders.push(dir_entry.path()),
+            _ => {}
+        }
+    }
+    folders.sort();
+    files.sort();
+
+    Ok((folders, files))
+}
+
+fn visit_folder(
+    folder: &Path,
+    depth_left: usize,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    if !visited.insert(fs::canonicalize(folder)?) {
+        return Ok(());
+    }
+
+    let (subfolders, mut folder_files) = read_folder(folder)?;
+    files.append(&mut folder_files);
+
+    if depth_left > 0 {
+        for subfolder in subfolders {
+            // a subfolder that can't be read shouldn't stop the rest from loading
+            let _ = visit_folder(&subfolder, depth_left - 1, visited, files);
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::filesystem::{Filesystem, FilesystemIO};
+    use crate::filesystem::{copy_and_remove, relative_path, Filesystem, FilesystemIO};
+    use crate::transfer::LinkKind;
+    use crate::trash::{TrashMode, TRASH_FOLDER_NAME};
     use std::fs::File;
     use std::io::ErrorKind;
     use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
     use std::{fs, io::Error};
     use tempdir::TempDir;
 
@@ -110,6 +381,96 @@ mod tests {
         assert_filesystem_elements(actual_files, expected_files);
     }
 
+    #[test]
+    fn ensure_files_are_loaded_recursively_in_order_up_to_max_depth() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let nested = dir.path().join("DCIM").join("100CANON");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(dir.path().join("Album")).unwrap();
+        File::create(dir.path().join("b.jpg")).unwrap();
+        File::create(dir.path().join("a.jpg")).unwrap();
+        File::create(dir.path().join("Album").join("c.jpg")).unwrap();
+        File::create(dir.path().join("DCIM").join("d.jpg")).unwrap();
+        File::create(nested.join("e.jpg")).unwrap();
+        let filesystem = Filesystem::new();
+
+        let shallow = filesystem
+            .load_files_recursively(dir.path(), 1, &[])
+            .unwrap();
+        let deep = filesystem
+            .load_files_recursively(dir.path(), usize::MAX, &[dir.path().join("Album")])
+            .unwrap();
+
+        assert_eq!(
+            shallow,
+            vec![
+                dir.path().join("a.jpg"),
+                dir.path().join("b.jpg"),
+                dir.path().join("Album").join("c.jpg"),
+                dir.path().join("DCIM").join("d.jpg"),
+            ]
+        );
+        assert_eq!(
+            deep,
+            vec![
+                dir.path().join("a.jpg"),
+                dir.path().join("b.jpg"),
+                dir.path().join("DCIM").join("d.jpg"),
+                nested.join("e.jpg"),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_symbolic_link_loops_are_followed_once() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        File::create(nested.join("file1.jpg")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), nested.join("loop")).unwrap();
+
+        let actual = Filesystem::new()
+            .load_files_recursively(dir.path(), usize::MAX, &[])
+            .unwrap();
+
+        assert_eq!(actual, vec![nested.join("file1.jpg")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_symbolic_links_to_files_are_loaded_as_files_at_any_depth() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new("unit_test").unwrap();
+        let target = TempDir::new("unit_test").unwrap();
+        let target_file = target.path().join("target.jpg");
+        File::create(&target_file).unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        symlink(&target_file, dir.path().join("link1.jpg")).unwrap();
+        symlink(&target_file, nested.join("link2.jpg")).unwrap();
+        symlink(
+            dir.path().join("missing.jpg"),
+            dir.path().join("broken.jpg"),
+        )
+        .unwrap();
+
+        let (folders, files) = Filesystem::new()
+            .load_filesystem_elements(dir.path())
+            .unwrap();
+        let recursive_files = Filesystem::new()
+            .load_files_recursively(dir.path(), 1, &[])
+            .unwrap();
+
+        assert_eq!(folders, vec![nested.clone()]);
+        assert_eq!(files, vec![dir.path().join("link1.jpg")]);
+        assert_eq!(
+            recursive_files,
+            vec![dir.path().join("link1.jpg"), nested.join("link2.jpg")]
+        );
+    }
+
     #[test]
     fn ensure_invalid_folders_are_caught() {
         let expected_error = ErrorKind::NotFound;
@@ -130,9 +491,9 @@ mod tests {
         File::create(dir.path().join(file1)).unwrap();
         File::create(dir.path().join(file2)).unwrap();
 
-        assert!(!Filesystem::new()
+        assert!(Filesystem::new()
             .delete_file(&dir.path().join(file1))
-            .is_err());
+            .is_ok());
 
         assert!(fs::read(dir.path().join(file1)).is_err());
         assert!(fs::read(dir.path().join(file2)).is_ok());
@@ -174,9 +535,9 @@ mod tests {
         File::create(from_dir.path().join(file1)).unwrap();
         File::create(from_dir.path().join(file2)).unwrap();
 
-        assert!(!Filesystem::new()
+        assert!(Filesystem::new()
             .move_file(&from_dir.path().join(file1), &to_dir.path().join(file1))
-            .is_err());
+            .is_ok());
 
         assert!(fs::read(from_dir.path().join(file1)).is_err());
         assert!(fs::read(to_dir.path().join(file1)).is_ok());
@@ -184,6 +545,136 @@ mod tests {
         assert!(fs::read(to_dir.path().join(file2)).is_err());
     }
 
+    #[test]
+    fn ensure_file_is_renamed_unless_the_name_is_taken() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("IMG_1234.jpg");
+        let taken = dir.path().join("Beach.jpg");
+        let renamed = dir.path().join("Sunset.jpg");
+        fs::write(&file, "photo").unwrap();
+        fs::write(&taken, "other photo").unwrap();
+
+        let error = Filesystem::new().rename_file(&file, &taken).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&taken).unwrap(), b"other photo");
+
+        Filesystem::new().rename_file(&file, &renamed).unwrap();
+        assert!(!file.exists());
+        assert_eq!(fs::read(&renamed).unwrap(), b"photo");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_broken_links_exist() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let link = dir.path().join("link.jpg");
+        std::os::unix::fs::symlink(dir.path().join("missing.jpg"), &link).unwrap();
+
+        assert!(Filesystem::new().exists(&link));
+        assert!(!Filesystem::new().exists(&dir.path().join("missing.jpg")));
+        let file = dir.path().join("file1.jpg");
+        File::create(&file).unwrap();
+        let actual_error = Filesystem::new().move_file(&file, &link).unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn ensure_file_is_copied_with_its_metadata_and_removed() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.jpg");
+        let to_file = to_dir.path().join("file1.jpg");
+        fs::write(&from_file, [7; 100_000]).unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(&from_file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let mut permissions = fs::metadata(&from_file).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&from_file, permissions).unwrap();
+
+        copy_and_remove(&from_file, &to_file).unwrap();
+
+        assert!(!from_file.exists());
+        let metadata = fs::metadata(&to_file).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert!(metadata.permissions().readonly());
+        assert_eq!(fs::read(&to_file).unwrap(), vec![7; 100_000]);
+    }
+
+    #[test]
+    fn ensure_copy_does_not_replace_existing_file() {
+        let from_dir = TempDir::new("unit_test").unwrap();
+        let to_dir = TempDir::new("unit_test").unwrap();
+        let from_file = from_dir.path().join("file1.jpg");
+        let to_file = to_dir.path().join("file1.jpg");
+        fs::write(&from_file, "new").unwrap();
+        fs::write(&to_file, "old").unwrap();
+
+        let actual_error = copy_and_remove(&from_file, &to_file).unwrap_err();
+
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&from_file).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to_file).unwrap(), "old");
+    }
+
+    #[test]
+    fn ensure_relative_paths_lead_to_the_target() {
+        assert_eq!(
+            relative_path(
+                Path::new("/photos/sorted/cats"),
+                Path::new("/photos/inbox/a.jpg")
+            ),
+            PathBuf::from("../../inbox/a.jpg")
+        );
+        assert_eq!(
+            relative_path(Path::new("/photos"), Path::new("/photos/a.jpg")),
+            PathBuf::from("a.jpg")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_links_point_at_the_file() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let folder = dir.path().join("folder1");
+        fs::create_dir(&folder).unwrap();
+        let file = dir.path().join("file1.jpg");
+        fs::write(&file, "content").unwrap();
+        let filesystem = Filesystem::new();
+
+        for (kind, name) in [
+            (LinkKind::Hard, "hard.jpg"),
+            (LinkKind::Symbolic, "absolute.jpg"),
+            (LinkKind::RelativeSymbolic, "relative.jpg"),
+        ] {
+            filesystem
+                .link_file(&file, &folder.join(name), kind)
+                .unwrap();
+            assert_eq!(fs::read_to_string(folder.join(name)).unwrap(), "content");
+        }
+
+        assert!(!fs::symlink_metadata(folder.join("hard.jpg"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_link(folder.join("absolute.jpg"))
+            .unwrap()
+            .is_absolute());
+        assert_eq!(
+            fs::read_link(folder.join("relative.jpg")).unwrap(),
+            PathBuf::from("../file1.jpg")
+        );
+        let actual_error = filesystem
+            .link_file(&file, &folder.join("hard.jpg"), LinkKind::Symbolic)
+            .unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+    }
+
     #[test]
     fn ensure_no_file_is_moved_when_file_not_found() {
         let from_dir = TempDir::new("unit_test").unwrap();
@@ -209,7 +700,7 @@ mod tests {
 
         let actual_folders = (
             vec![Filesystem::new()
-                .add_folder(&"./images/testFolder".to_owned())
+                .add_folder("./images/testFolder")
                 .expect("Found empty list!")],
             vec![],
         );
@@ -222,10 +713,134 @@ mod tests {
         let expected_error = ErrorKind::NotFound;
 
         let actual_error = Filesystem::new()
-            .add_folder(&"./images/bad_folder".to_owned())
+            .add_folder("./images/bad_folder")
             .err()
             .unwrap();
 
         assert_eq!(actual_error.kind(), expected_error);
     }
+
+    #[test]
+    fn ensure_folder_is_created_and_only_removed_while_empty() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let folder = dir.path().join("2021");
+        let filesystem = Filesystem::new();
+
+        filesystem.create_folder(&folder).unwrap();
+        let created_again = filesystem.create_folder(&folder);
+        File::create(folder.join("file1.jpg")).unwrap();
+        filesystem.remove_empty_folder(&folder).unwrap();
+        let kept = folder.exists();
+        fs::remove_file(folder.join("file1.jpg")).unwrap();
+        filesystem.remove_empty_folder(&folder).unwrap();
+
+        assert_eq!(created_again.unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert!(kept);
+        assert!(!folder.exists());
+    }
+
+    #[test]
+    fn ensure_trashed_file_is_staged_next_to_the_original() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        File::create(&file1).unwrap();
+
+        let trashed = Filesystem::new().trash_file(&file1).unwrap();
+
+        assert!(!file1.exists());
+        assert!(trashed.exists());
+        assert_eq!(
+            trashed.parent().unwrap(),
+            dir.path().join(TRASH_FOLDER_NAME)
+        );
+    }
+
+    #[test]
+    fn ensure_trashed_files_with_the_same_name_do_not_collide() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        let filesystem = Filesystem::new();
+        fs::write(&file1, "first").unwrap();
+        let first_trashed = filesystem.trash_file(&file1).unwrap();
+        fs::write(&file1, "second").unwrap();
+
+        let second_trashed = filesystem.trash_file(&file1).unwrap();
+
+        assert_ne!(first_trashed, second_trashed);
+        assert_eq!(fs::read_to_string(first_trashed).unwrap(), "first");
+        assert_eq!(fs::read_to_string(second_trashed).unwrap(), "second");
+    }
+
+    #[test]
+    fn ensure_trash_folder_is_removed_when_last_file_is_restored_or_purged() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        let file2 = dir.path().join("file2.txt");
+        File::create(&file1).unwrap();
+        File::create(&file2).unwrap();
+        let filesystem = Filesystem::new();
+        let trashed1 = filesystem.trash_file(&file1).unwrap();
+        let trashed2 = filesystem.trash_file(&file2).unwrap();
+
+        filesystem.restore_file(&trashed1, &file1).unwrap();
+        assert!(file1.exists());
+        assert!(dir.path().join(TRASH_FOLDER_NAME).exists());
+
+        filesystem.purge_file(&trashed2).unwrap();
+        assert!(!file2.exists());
+        assert!(!dir.path().join(TRASH_FOLDER_NAME).exists());
+    }
+
+    #[test]
+    fn ensure_trash_folder_is_not_loaded() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        let file2 = dir.path().join("file2.txt");
+        File::create(&file1).unwrap();
+        File::create(&file2).unwrap();
+        let filesystem = Filesystem::new();
+        filesystem.trash_file(&file1).unwrap();
+
+        let actual_elements = filesystem.load_filesystem_elements(dir.path()).unwrap();
+
+        assert_filesystem_elements(actual_elements, (vec![], vec![file2]));
+    }
+
+    #[test]
+    fn ensure_free_desktop_trashed_file_is_kept_when_purging() {
+        let data_home = TempDir::new("unit_test").unwrap();
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        File::create(&file1).unwrap();
+        let filesystem = Filesystem::new();
+        filesystem.set_trash_mode(TrashMode::FreeDesktop {
+            data_home: data_home.path().to_path_buf(),
+        });
+
+        let trashed = filesystem.trash_file(&file1).unwrap();
+        filesystem.purge_file(&trashed).unwrap();
+
+        assert!(trashed.starts_with(data_home.path().join("Trash").join("files")));
+        assert!(trashed.exists());
+        assert!(!dir.path().join(TRASH_FOLDER_NAME).exists());
+    }
+
+    #[test]
+    fn ensure_staged_file_is_restored_after_switching_trash_mode() {
+        let data_home = TempDir::new("unit_test").unwrap();
+        let dir = TempDir::new("unit_test").unwrap();
+        let file1 = dir.path().join("file1.txt");
+        File::create(&file1).unwrap();
+        let filesystem = Filesystem::new();
+
+        let trashed = filesystem.trash_file(&file1).unwrap();
+        filesystem.set_trash_mode(TrashMode::FreeDesktop {
+            data_home: data_home.path().to_path_buf(),
+        });
+        filesystem.restore_file(&trashed, &file1).unwrap();
+        let trashed = filesystem.trash_file(&file1).unwrap();
+
+        assert!(trashed.starts_with(data_home.path().join("Trash").join("files")));
+        assert!(!dir.path().join(TRASH_FOLDER_NAME).exists());
+    }
 }
@@ -0,0 +1,156 @@
+//! Filtering of the files that are loaded to be sorted.
+
+use crate::format::FileFormat;
+use std::path::Path;
+
+/// Extensions of the image formats whose content can be [sniffed](FileFilter::sniff_content).
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "jpe", "jfif", "png", "gif", "webp", "heic", "heif", "hif", "avif", "bmp",
+    "dib", "tif", "tiff",
+];
+
+/// Decides which files are loaded to be sorted.
+///
+/// The default filter keeps every file. Extensions are compared case-insensitively and without
+/// the leading dot.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct FileFilter {
+    /// If not empty, only files with one of these extensions are kept.
+    pub allowed_extensions: Vec<String>,
+    /// Files with one of these extensions are excluded.
+    pub denied_extensions: Vec<String>,
+    /// Only keep files whose first bytes look like a JPEG, PNG, GIF, WebP, HEIC, AVIF, BMP or
+    /// TIFF image, regardless of their extension.
+    pub sniff_content: bool,
+    /// Exclude files whose [content](FileFormat::of_file) doesn't match their extension.
+    pub exclude_mismatched: bool,
+}
+
+impl FileFilter {
+    /// Returns a filter that keeps only images, judged by both their extension and content.
+    pub fn images() -> FileFilter {
+        FileFilter {
+            allowed_extensions: IMAGE_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            denied_extensions: Vec::new(),
+            sniff_content: true,
+            exclude_mismatched: false,
+        }
+    }
+
+    /// Returns whether the file should be loaded.
+    ///
+    /// Files that can't be read are excluded when their content is checked.
+    pub fn is_allowed(&self, file: &Path) -> bool {
+        let extension = file
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned());
+        let has_extension_in = |extensions: &[String]| match &extension {
+            Some(extension) => extensions.iter().any(|listed| {
+                listed
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(extension)
+            }),
+            None => false,
+        };
+
+        if !self.allowed_extensions.is_empty() && !has_extension_in(&self.allowed_extensions) {
+            return false;
+        }
+        if has_extension_in(&self.denied_extensions) {
+            return false;
+        }
+        if !self.sniff_content && !self.exclude_mismatched {
+            return true;
+        }
+
+        let format = match FileFormat::of_file(file) {
+            Ok(format) => format,
+            Err(_) => return false,
+        };
+        if self.sniff_content && !format.is_image() {
+            return false;
+        }
+
+        !self.exclude_mismatched || format.matches_extension(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::FileFilter;
+    use std::fs;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_every_file_is_allowed_by_default() {
+        let filter = FileFilter::default();
+
+        assert!(filter.is_allowed(Path::new("./images/file1.jpg")));
+        assert!(filter.is_allowed(Path::new("./images/.DS_Store")));
+    }
+
+    #[test]
+    fn ensure_only_allowed_extensions_are_kept() {
+        let filter = FileFilter {
+            allowed_extensions: vec!["jpg".to_owned(), ".PNG".to_owned()],
+            ..FileFilter::default()
+        };
+
+        assert!(filter.is_allowed(Path::new("./images/file1.JPG")));
+        assert!(filter.is_allowed(Path::new("./images/file2.png")));
+        assert!(!filter.is_allowed(Path::new("./images/notes.txt")));
+        assert!(!filter.is_allowed(Path::new("./images/.DS_Store")));
+    }
+
+    #[test]
+    fn ensure_denied_extensions_are_excluded() {
+        let filter = FileFilter {
+            denied_extensions: vec!["db".to_owned(), "xmp".to_owned()],
+            ..FileFilter::default()
+        };
+
+        assert!(filter.is_allowed(Path::new("./images/file1.jpg")));
+        assert!(!filter.is_allowed(Path::new("./images/Thumbs.db")));
+        assert!(!filter.is_allowed(Path::new("./images/file1.jpg.xmp")));
+    }
+
+    #[test]
+    fn ensure_content_is_sniffed_regardless_of_extension() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let png = dir.path().join("image.txt");
+        let text = dir.path().join("notes.jpg");
+        let webp = dir.path().join("image");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(&text, b"not an image").unwrap();
+        fs::write(&webp, b"RIFF\x24\0\0\0WEBPVP8 ").unwrap();
+        let filter = FileFilter {
+            sniff_content: true,
+            ..FileFilter::default()
+        };
+
+        assert!(filter.is_allowed(&png));
+        assert!(!filter.is_allowed(&text));
+        assert!(filter.is_allowed(&webp));
+        assert!(!filter.is_allowed(&dir.path().join("missing.png")));
+    }
+
+    #[test]
+    fn ensure_mismatched_files_are_excluded() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let png = dir.path().join("image.png");
+        let renamed = dir.path().join("image.jpg");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(&renamed, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let filter = FileFilter {
+            exclude_mismatched: true,
+            ..FileFilter::default()
+        };
+
+        assert!(filter.is_allowed(&png));
+        assert!(!filter.is_allowed(&renamed));
+    }
+}
@@ -0,0 +1,292 @@
+//! Detection of a file's format from its first bytes, so that files with a wrong or missing
+//! extension can still be recognized.
+
+use std::fs::File;
+use std::io::{Error, Read};
+use std::path::Path;
+
+/// The number of bytes read from the start of a file to detect its format.
+const HEADER_LENGTH: u64 = 64;
+
+/// The format of a file, as detected from its content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+    Heic,
+    Avif,
+    Tiff,
+    Bmp,
+    Mp4,
+    QuickTime,
+    WebM,
+    Matroska,
+    Avi,
+    Unknown,
+}
+
+impl FileFormat {
+    /// Reads the start of a file and detects its format.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be opened or read, an error variant will be returned.
+    pub fn of_file(file: &Path) -> Result<FileFormat, Error> {
+        let mut header = Vec::new();
+        File::open(file)?
+            .take(HEADER_LENGTH)
+            .read_to_end(&mut header)?;
+
+        Ok(FileFormat::detect(&header))
+    }
+
+    /// Detects the format from the first bytes of a file.
+    pub fn detect(header: &[u8]) -> FileFormat {
+        if header.starts_with(&[0xff, 0xd8, 0xff]) {
+            FileFormat::Jpeg
+        } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+            FileFormat::Png
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            FileFormat::Gif
+        } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+            FileFormat::WebP
+        } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"AVI ") {
+            FileFormat::Avi
+        } else if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+            FileFormat::Tiff
+        } else if header.starts_with(b"BM") {
+            FileFormat::Bmp
+        } else if header.get(4..8) == Some(b"ftyp") {
+            detect_iso_media(header)
+        } else if header.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+            match header.windows(4).any(|window| window == b"webm") {
+                true => FileFormat::WebM,
+                false => FileFormat::Matroska,
+            }
+        } else {
+            FileFormat::Unknown
+        }
+    }
+
+    /// Returns the format that files with this extension are expected to have, compared
+    /// case-insensitively and without the leading dot.
+    pub fn from_extension(extension: &str) -> FileFormat {
+        match extension.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" | "jpe" | "jfif" => FileFormat::Jpeg,
+            "png" => FileFormat::Png,
+            "gif" => FileFormat::Gif,
+            "webp" => FileFormat::WebP,
+            "heic" | "heif" | "hif" => FileFormat::Heic,
+            "avif" => FileFormat::Avif,
+            "tif" | "tiff" => FileFormat::Tiff,
+            "bmp" | "dib" => FileFormat::Bmp,
+            "mp4" | "m4v" => FileFormat::Mp4,
+            "mov" | "qt" => FileFormat::QuickTime,
+            "webm" => FileFormat::WebM,
+            "mkv" => FileFormat::Matroska,
+            "avi" => FileFormat::Avi,
+            _ => FileFormat::Unknown,
+        }
+    }
+
+    /// Returns the format expected from the file's extension.
+    pub fn from_path(file: &Path) -> FileFormat {
+        match file.extension() {
+            Some(extension) => FileFormat::from_extension(&extension.to_string_lossy()),
+            None => FileFormat::Unknown,
+        }
+    }
+
+    pub fn is_image(self) -> bool {
+        matches!(
+            self,
+            FileFormat::Jpeg
+                | FileFormat::Png
+                | FileFormat::Gif
+                | FileFormat::WebP
+                | FileFormat::Heic
+                | FileFormat::Avif
+                | FileFormat::Tiff
+                | FileFormat::Bmp
+        )
+    }
+
+    pub fn is_video(self) -> bool {
+        matches!(
+            self,
+            FileFormat::Mp4
+                | FileFormat::QuickTime
+                | FileFormat::WebM
+                | FileFormat::Matroska
+                | FileFormat::Avi
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileFormat::Jpeg => "jpeg",
+            FileFormat::Png => "png",
+            FileFormat::Gif => "gif",
+            FileFormat::WebP => "webp",
+            FileFormat::Heic => "heic",
+            FileFormat::Avif => "avif",
+            FileFormat::Tiff => "tiff",
+            FileFormat::Bmp => "bmp",
+            FileFormat::Mp4 => "mp4",
+            FileFormat::QuickTime => "quicktime",
+            FileFormat::WebM => "webm",
+            FileFormat::Matroska => "matroska",
+            FileFormat::Avi => "avi",
+            FileFormat::Unknown => "unknown",
+        }
+    }
+
+    /// Returns the MIME type of the format.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            FileFormat::Jpeg => "image/jpeg",
+            FileFormat::Png => "image/png",
+            FileFormat::Gif => "image/gif",
+            FileFormat::WebP => "image/webp",
+            FileFormat::Heic => "image/heic",
+            FileFormat::Avif => "image/avif",
+            FileFormat::Tiff => "image/tiff",
+            FileFormat::Bmp => "image/bmp",
+            FileFormat::Mp4 => "video/mp4",
+            FileFormat::QuickTime => "video/quicktime",
+            FileFormat::WebM => "video/webm",
+            FileFormat::Matroska => "video/x-matroska",
+            FileFormat::Avi => "video/x-msvideo",
+            FileFormat::Unknown => "application/octet-stream",
+        }
+    }
+
+    /// Returns whether the file's extension is the one expected for this format.
+    ///
+    /// A file without a known extension only matches if its format is unknown as well.
+    pub fn matches_extension(self, file: &Path) -> bool {
+        self == FileFormat::from_path(file)
+    }
+}
+
+/// Tells apart the formats built on ISO base media files by the brands in their `ftyp` box.
+fn detect_iso_media(header: &[u8]) -> FileFormat {
+    let box_length = match header.get(0..4) {
+        Some(length) => u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize,
+        None => return FileFormat::Unknown,
+    };
+    // the major brand, then the minor version, then the compatible brands
+    let brands = header
+        .get(8..box_length.min(header.len()))
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(index, _)| *index != 1)
+        .map(|(_, brand)| brand);
+
+    let mut format = FileFormat::Unknown;
+    for (index, brand) in brands.enumerate() {
+        let brand_format = match brand {
+            b"avif" | b"avis" => FileFormat::Avif,
+            b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1" => {
+                FileFormat::Heic
+            }
+            b"qt  " => FileFormat::QuickTime,
+            _ => FileFormat::Mp4,
+        };
+        match brand_format {
+            // AVIF files usually have a generic HEIF major brand with AVIF as a compatible one
+            FileFormat::Avif => return FileFormat::Avif,
+            _ if index == 0 => format = brand_format,
+            _ => {}
+        }
+    }
+
+    format
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::FileFormat;
+    use std::fs;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_image_formats_are_detected() {
+        assert_eq!(
+            FileFormat::detect(b"\xff\xd8\xff\xe0\0\x10JFIF"),
+            FileFormat::Jpeg
+        );
+        assert_eq!(
+            FileFormat::detect(b"\x89PNG\r\n\x1a\n\0\0\0\r"),
+            FileFormat::Png
+        );
+        assert_eq!(FileFormat::detect(b"GIF89a\x01\0"), FileFormat::Gif);
+        assert_eq!(
+            FileFormat::detect(b"RIFF\x24\0\0\0WEBPVP8 "),
+            FileFormat::WebP
+        );
+        assert_eq!(FileFormat::detect(b"II*\0\x08\0\0\0"), FileFormat::Tiff);
+        assert_eq!(FileFormat::detect(b"BM\x36\0\0\0"), FileFormat::Bmp);
+    }
+
+    #[test]
+    fn ensure_iso_media_formats_are_told_apart_by_brand() {
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        let avif = b"\0\0\0\x1cftypmif1\0\0\0\0mif1avifmiaf";
+        let mp4 = b"\0\0\0\x18ftypisom\0\0\x02\0isomiso2";
+        let quicktime = b"\0\0\0\x14ftypqt  \0\0\x02\0qt  ";
+
+        assert_eq!(FileFormat::detect(heic), FileFormat::Heic);
+        assert_eq!(FileFormat::detect(avif), FileFormat::Avif);
+        assert_eq!(FileFormat::detect(mp4), FileFormat::Mp4);
+        assert_eq!(FileFormat::detect(quicktime), FileFormat::QuickTime);
+    }
+
+    #[test]
+    fn ensure_video_containers_are_detected() {
+        let webm = b"\x1a\x45\xdf\xa3\x9f\x42\x86\x81\x01\x42\x82\x84webm";
+        let matroska = b"\x1a\x45\xdf\xa3\xa3\x42\x86\x81\x01\x42\x82\x88matroska";
+
+        assert_eq!(FileFormat::detect(webm), FileFormat::WebM);
+        assert_eq!(FileFormat::detect(matroska), FileFormat::Matroska);
+        assert_eq!(FileFormat::detect(b"RIFF\0\0\0\0AVI LIST"), FileFormat::Avi);
+        assert!(FileFormat::Avi.is_video());
+    }
+
+    #[test]
+    fn ensure_unrecognized_content_is_unknown() {
+        assert_eq!(FileFormat::detect(b""), FileFormat::Unknown);
+        assert_eq!(FileFormat::detect(b"just some text"), FileFormat::Unknown);
+        assert!(!FileFormat::Unknown.is_image());
+    }
+
+    #[test]
+    fn ensure_file_content_is_detected_regardless_of_extension() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("IMG_0001.txt");
+        fs::write(&file, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+
+        assert_eq!(FileFormat::of_file(&file).unwrap(), FileFormat::Png);
+        assert_eq!(FileFormat::from_path(&file), FileFormat::Unknown);
+        assert!(!FileFormat::Png.matches_extension(&file));
+        assert!(FileFormat::of_file(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn ensure_extensions_are_mapped_to_formats() {
+        assert_eq!(FileFormat::from_path(Path::new("a.JPG")), FileFormat::Jpeg);
+        assert_eq!(FileFormat::from_path(Path::new("a.heif")), FileFormat::Heic);
+        assert_eq!(
+            FileFormat::from_path(Path::new("a.mov")),
+            FileFormat::QuickTime
+        );
+        assert_eq!(
+            FileFormat::from_path(Path::new("README")),
+            FileFormat::Unknown
+        );
+    }
+}
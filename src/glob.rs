@@ -0,0 +1,206 @@
+//! Shell-like patterns for matching file names.
+//!
+//! `*` matches any run of characters, `?` matches a single character and `[...]` matches one of
+//! the characters or ranges inside, such as `[a-z0-9]`, or any other character when it starts
+//! with `!`. A `\` matches the character after it literally.
+
+use std::io::{Error, ErrorKind};
+
+/// A compiled pattern that file names can be matched against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    AnyCharacter,
+    AnyRun,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    /// Compiles a pattern.
+    ///
+    /// # Errors
+    ///
+    /// If the pattern has an unclosed class, an empty class or ends in a lone `\`, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub fn new(pattern: &str) -> Result<Glob, Error> {
+        Glob::parse(pattern).map_err(|(position, message)| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} at character {} of `{}`", message, position + 1, pattern),
+            )
+        })
+    }
+
+    /// Compiles a pattern, returning the character position of the problem on failure.
+    pub(crate) fn parse(pattern: &str) -> Result<Glob, (usize, &'static str)> {
+        let characters: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut position = 0;
+        while position < characters.len() {
+            let token = match characters[position] {
+                '*' => Token::AnyRun,
+                '?' => Token::AnyCharacter,
+                '\\' => match characters.get(position + 1) {
+                    Some(character) => {
+                        position += 1;
+                        Token::Literal(*character)
+                    }
+                    None => return Err((position, "nothing to escape")),
+                },
+                '[' => {
+                    let (token, end) = parse_class(&characters, position)?;
+                    position = end;
+                    token
+                }
+                character => Token::Literal(character),
+            };
+            tokens.push(token);
+            position += 1;
+        }
+
+        Ok(Glob {
+            pattern: pattern.to_owned(),
+            tokens,
+        })
+    }
+
+    /// Returns the pattern the glob was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns whether the whole text matches the pattern.
+    pub fn matches(&self, text: &str) -> bool {
+        let characters: Vec<char> = text.chars().collect();
+        let (mut token, mut character) = (0, 0);
+        // where to resume if the characters after the last `*` stop matching
+        let mut backtrack = None;
+        while character < characters.len() {
+            match self.tokens.get(token) {
+                Some(Token::AnyRun) => {
+                    backtrack = Some((token, character));
+                    token += 1;
+                    continue;
+                }
+                Some(single) if single.matches(characters[character]) => {
+                    token += 1;
+                    character += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            match backtrack {
+                Some((run_token, run_character)) => {
+                    backtrack = Some((run_token, run_character + 1));
+                    token = run_token + 1;
+                    character = run_character + 1;
+                }
+                None => return false,
+            }
+        }
+
+        self.tokens[token..]
+            .iter()
+            .all(|token| *token == Token::AnyRun)
+    }
+}
+
+impl Token {
+    fn matches(&self, character: char) -> bool {
+        match self {
+            Token::Literal(literal) => *literal == character,
+            Token::AnyCharacter => true,
+            Token::AnyRun => false,
+            Token::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(first, last)| (*first..=*last).contains(&character))
+                    != *negated
+            }
+        }
+    }
+}
+
+/// Parses the class starting at `start`, returning it along with the position of its `]`.
+fn parse_class(characters: &[char], start: usize) -> Result<(Token, usize), (usize, &'static str)> {
+    let mut position = start + 1;
+    let negated = characters.get(position) == Some(&'!');
+    if negated {
+        position += 1;
+    }
+    let mut ranges = Vec::new();
+    loop {
+        let first = match characters.get(position) {
+            // a `]` right at the start is part of the class rather than its end
+            Some(']') if !ranges.is_empty() => break,
+            Some('\\') => {
+                position += 1;
+                *characters.get(position).ok_or((start, "unclosed `[`"))?
+            }
+            Some(character) => *character,
+            None => return Err((start, "unclosed `[`")),
+        };
+        let last = match (characters.get(position + 1), characters.get(position + 2)) {
+            (Some('-'), Some(last)) if *last != ']' => {
+                position += 2;
+                *last
+            }
+            _ => first,
+        };
+        if last < first {
+            return Err((position, "range out of order"));
+        }
+        ranges.push((first, last));
+        position += 1;
+    }
+
+    Ok((Token::Class { negated, ranges }, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glob::Glob;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn ensure_wildcards_match_names() {
+        let glob = Glob::new("IMG_*.jp?").unwrap();
+
+        assert!(glob.matches("IMG_1234.jpg"));
+        assert!(glob.matches("IMG_.jpe"));
+        assert!(!glob.matches("IMG_1234.jpeg"));
+        assert!(!glob.matches("img_1234.jpg"));
+        assert!(Glob::new("*a*b").unwrap().matches("xaab"));
+        assert!(!Glob::new("*a*b").unwrap().matches("xaabc"));
+        assert!(Glob::new("**").unwrap().matches(""));
+    }
+
+    #[test]
+    fn ensure_classes_and_escapes_match_names() {
+        let glob = Glob::new("[!.]*[0-9][]x]\\*").unwrap();
+
+        assert!(glob.matches("a7]*"));
+        assert!(glob.matches("photo 2x*"));
+        assert!(!glob.matches(".a7]*"));
+        assert!(!glob.matches("a7]x"));
+    }
+
+    #[test]
+    fn ensure_invalid_patterns_are_reported() {
+        assert_eq!(Glob::parse("ab[cd"), Err((2, "unclosed `[`")));
+        assert_eq!(Glob::parse("[z-a]"), Err((3, "range out of order")));
+        assert_eq!(Glob::parse("a\\"), Err((1, "nothing to escape")));
+        let error = Glob::new("[").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "unclosed `[` at character 1 of `[`");
+    }
+}
@@ -0,0 +1,81 @@
+//! A small, fast, non-cryptographic hash for checksums and telling file contents apart.
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+
+/// 64-bit FNV-1a hash.
+#[derive(Clone, Copy, Debug)]
+pub struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes.iter().fold(self.0, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a::new()
+    }
+}
+
+/// Hashes a slice of bytes in one go.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Hashes the whole content of a file without loading it into memory at once.
+///
+/// # Errors
+///
+/// If the file can't be opened or read, an error variant will be returned.
+pub fn hash_file(file: &Path) -> Result<u64, Error> {
+    let mut file = File::open(file)?;
+    let mut hasher = Fnv1a::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(read) => hasher.write(&buffer[..read]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::{hash_bytes, hash_file};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_known_values_are_hashed() {
+        assert_eq!(hash_bytes(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_bytes(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn ensure_file_hash_matches_its_content_hash() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.bin");
+        let content: Vec<u8> = (0..200_000).map(|index| (index % 251) as u8).collect();
+        fs::write(&file, &content).unwrap();
+
+        assert_eq!(hash_file(&file).unwrap(), hash_bytes(&content));
+        assert!(hash_file(&dir.path().join("missing.bin")).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//! An append-only, on-disk record of everything that changes a [Backend](crate::Backend)'s
+//! session so that the session can be resumed after a crash.
+//!
+//! Changes are recorded after they are made, so a crash in between loses the last change from the
+//! journal even though it happened on the filesystem.
+//!
+//! Each entry is written as a single line of tab separated fields, prefixed with a checksum of the
+//! line, and synced to disk before the call returns. When reading the journal back, a trailing
+//! line that is missing its newline or whose checksum doesn't match is treated as a partially
+//! written entry and dropped.
+
+use crate::control_flow::ActionDescription;
+use crate::encoding::{escape, escape_path, unescape, unescape_path, FIELD_SEPARATOR};
+use crate::hash;
+use crate::trash::TrashMode;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// A single change to a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    /// Files and folders were (re)loaded, which resets the history.
+    Load {
+        pwd: String,
+        current_file_index: usize,
+        /// Whether every file was already sorted, with the index at the last one.
+        end_of_files: bool,
+        folders: Vec<PathBuf>,
+        files: Vec<PathBuf>,
+    },
+    /// The folders to sort into were replaced.
+    Folders(Vec<PathBuf>),
+    /// The files were reordered.
+    Files(Vec<PathBuf>),
+    /// The source directories of the files were replaced, without changing the files.
+    Sources(Vec<PathBuf>),
+    /// A source directory was added and its files were appended to the queue.
+    AddSource {
+        directory: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    /// A source directory was removed along with its files that weren't sorted yet.
+    RemoveSource(PathBuf),
+    /// Deleted files are put in a different trash from now on.
+    TrashMode(TrashMode),
+    /// The trash was purged, which clears the history.
+    Purge,
+    /// An action was performed.
+    Action(ActionDescription),
+    Undo,
+    /// The most recently undone action was redone and is now described by the description.
+    Redo(ActionDescription),
+}
+
+impl Entry {
+    fn to_fields(&self) -> Vec<String> {
+        match self {
+            Entry::Load {
+                pwd,
+                current_file_index,
+                end_of_files,
+                folders,
+                files,
+            } => {
+                let mut fields = vec![
+                    "load".to_owned(),
+                    escape(pwd),
+                    current_file_index.to_string(),
+                    end_of_files.to_string(),
+                    folders.len().to_string(),
+                ];
+                fields.extend(folders.iter().chain(files).map(|path| escape_path(path)));
+                fields
+            }
+            Entry::Folders(folders) => {
+                let mut fields = vec!["folders".to_owned()];
+                fields.extend(folders.iter().map(|path| escape_path(path)));
+                fields
+            }
+            Entry::Files(files) => {
+                let mut fields = vec!["files".to_owned()];
+                fields.extend(files.iter().map(|path| escape_path(path)));
+                fields
+            }
+            Entry::Sources(sources) => {
+                let mut fields = vec!["sources".to_owned()];
+                fields.extend(sources.iter().map(|path| escape_path(path)));
+                fields
+            }
+            Entry::AddSource { directory, files } => {
+                let mut fields = vec!["add_source".to_owned(), escape_path(directory)];
+                fields.extend(files.iter().map(|path| escape_path(path)));
+                fields
+            }
+            Entry::RemoveSource(directory) => {
+                vec!["remove_source".to_owned(), escape_path(directory)]
+            }
+            Entry::TrashMode(TrashMode::Staging) => {
+                vec!["trash_mode".to_owned(), "staging".to_owned()]
+            }
+            Entry::TrashMode(TrashMode::FreeDesktop { data_home }) => vec![
+                "trash_mode".to_owned(),
+                "free_desktop".to_owned(),
+                escape_path(data_home),
+            ],
+            Entry::Purge => vec!["purge".to_owned()],
+            Entry::Action(description) => vec!["action".to_owned(), description.encode()],
+            Entry::Undo => vec!["undo".to_owned()],
+            Entry::Redo(description) => vec!["redo".to_owned(), description.encode()],
+        }
+    }
+
+    fn from_fields(fields: &[&str]) -> Result<Entry, Error> {
+        let invalid = || Error::from(ErrorKind::InvalidData);
+        let entry = match fields {
+            ["load", pwd, index, end_of_files, folder_count, paths @ ..] => {
+                let folder_count: usize = folder_count.parse().map_err(|_| invalid())?;
+                if folder_count > paths.len() {
+                    return Err(invalid());
+                }
+                let paths = paths
+                    .iter()
+                    .map(|path| unescape_path(path))
+                    .collect::<Result<Vec<PathBuf>, Error>>()?;
+                let (folders, files) = paths.split_at(folder_count);
+                Entry::Load {
+                    pwd: unescape(pwd)?,
+                    current_file_index: index.parse().map_err(|_| invalid())?,
+                    end_of_files: end_of_files.parse().map_err(|_| invalid())?,
+                    folders: folders.to_vec(),
+                    files: files.to_vec(),
+                }
+            }
+            ["folders", folders @ ..] => Entry::Folders(
+                folders
+                    .iter()
+                    .map(|path| unescape_path(path))
+                    .collect::<Result<Vec<PathBuf>, Error>>()?,
+            ),
+            ["files", files @ ..] => Entry::Files(
+                files
+                    .iter()
+                    .map(|path| unescape_path(path))
+                    .collect::<Result<Vec<PathBuf>, Error>>()?,
+            ),
+            ["sources", sources @ ..] => Entry::Sources(
+                sources
+                    .iter()
+                    .map(|path| unescape_path(path))
+                    .collect::<Result<Vec<PathBuf>, Error>>()?,
+            ),
+            ["add_source", directory, files @ ..] => Entry::AddSource {
+                directory: unescape_path(directory)?,
+                files: files
+                    .iter()
+                    .map(|path| unescape_path(path))
+                    .collect::<Result<Vec<PathBuf>, Error>>()?,
+            },
+            ["remove_source", directory] => Entry::RemoveSource(unescape_path(directory)?),
+            ["trash_mode", "staging"] => Entry::TrashMode(TrashMode::Staging),
+            ["trash_mode", "free_desktop", data_home] => Entry::TrashMode(TrashMode::FreeDesktop {
+                data_home: unescape_path(data_home)?,
+            }),
+            ["purge"] => Entry::Purge,
+            ["action", description @ ..] => Entry::Action(ActionDescription::decode(
+                &description.join(&FIELD_SEPARATOR.to_string()),
+            )?),
+            ["undo"] => Entry::Undo,
+            ["redo", description @ ..] => Entry::Redo(ActionDescription::decode(
+                &description.join(&FIELD_SEPARATOR.to_string()),
+            )?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(entry)
+    }
+}
+
+/// Makes sure the entry of a new file in its folder is written to disk.
+#[cfg(unix)]
+fn sync_parent_folder(path: &Path) -> Result<(), Error> {
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+
+    File::open(folder)?.sync_all()
+}
+
+/// Other platforms can't open folders to sync them, so only the file itself is synced.
+#[cfg(not(unix))]
+fn sync_parent_folder(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Creates a new, empty journal, replacing any journal already at the path.
+    ///
+    /// The folder the journal is in is synced as well, so that a crash can't lose the journal
+    /// itself.
+    pub fn create(path: &Path) -> Result<Journal, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        file.sync_all()?;
+        sync_parent_folder(path)?;
+
+        Ok(Journal { file })
+    }
+
+    /// Opens an existing journal and returns it along with all entries that were written
+    /// completely.
+    ///
+    /// A partially written trailing entry is cut off so that new entries are appended after the
+    /// last complete one.
+    ///
+    /// # Errors
+    ///
+    /// If an entry other than the last one is damaged, an [InvalidData](ErrorKind::InvalidData)
+    /// error is returned.
+    pub fn open(path: &Path) -> Result<(Journal, Vec<Entry>), Error> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        let mut entries = Vec::new();
+        let mut valid_length = 0;
+        let mut lines = contents.split_inclusive(|byte| *byte == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let is_last = lines.peek().is_none();
+            match parse_line(line) {
+                Ok(entry) => {
+                    entries.push(entry);
+                    valid_length += line.len();
+                }
+                Err(_) if is_last => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if valid_length < contents.len() {
+            file.set_len(valid_length as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok((Journal { file }, entries))
+    }
+
+    /// Appends an entry and waits until it is on disk.
+    pub fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+        let payload = entry.to_fields().join(&FIELD_SEPARATOR.to_string());
+        let line = format!(
+            "{:016x}{}{}\n",
+            checksum(payload.as_bytes()),
+            FIELD_SEPARATOR,
+            payload
+        );
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+}
+
+fn parse_line(line: &[u8]) -> Result<Entry, Error> {
+    let invalid = || Error::from(ErrorKind::InvalidData);
+    let line = line.strip_suffix(b"\n").ok_or_else(invalid)?;
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+    let (expected_checksum, payload) = line.split_once(FIELD_SEPARATOR).ok_or_else(invalid)?;
+    let expected_checksum = u64::from_str_radix(expected_checksum, 16).map_err(|_| invalid())?;
+    if checksum(payload.as_bytes()) != expected_checksum {
+        return Err(invalid());
+    }
+
+    Entry::from_fields(&payload.split(FIELD_SEPARATOR).collect::<Vec<&str>>())
+}
+
+/// 64-bit FNV-1a hash, which is plenty to detect torn writes.
+fn checksum(bytes: &[u8]) -> u64 {
+    hash::hash_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_flow::{ActionDescription, ActionKind};
+    use crate::journal::{Entry, Journal};
+    use crate::trash::TrashMode;
+    use std::fs::{self, OpenOptions};
+    use std::io::{ErrorKind, Write};
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempdir::TempDir;
+
+    fn build_description(kind: ActionKind, destination: Option<&str>) -> ActionDescription {
+        ActionDescription {
+            kind,
+            source: PathBuf::from("./images/file1.jpg"),
+            destination: destination.map(PathBuf::from),
+            replaced: None,
+            parts: Vec::new(),
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+
+    fn build_entries() -> Vec<Entry> {
+        vec![
+            Entry::Load {
+                pwd: "./images".to_owned(),
+                current_file_index: 0,
+                end_of_files: false,
+                folders: vec![PathBuf::from("./images/folder\t1")],
+                files: vec![
+                    PathBuf::from("./images/file1.jpg"),
+                    PathBuf::from("./images/file\\2\n.jpg"),
+                ],
+            },
+            Entry::TrashMode(TrashMode::FreeDesktop {
+                data_home: PathBuf::from("/home/user/.local/share"),
+            }),
+            Entry::Action(build_description(
+                ActionKind::Move,
+                Some("./images/folder\t1/file1.jpg"),
+            )),
+            Entry::Action(build_description(ActionKind::Skip, None)),
+            Entry::Action(build_description(
+                ActionKind::Rename,
+                Some("./images/2021-07-04_1.jpg"),
+            )),
+            Entry::Action(ActionDescription {
+                parts: vec![
+                    build_description(ActionKind::CreateFolder, None),
+                    build_description(ActionKind::Move, Some("./images/2024/file1.jpg")),
+                    build_description(ActionKind::Copy, Some("./images/family\t/file1.jpg")),
+                ],
+                ..build_description(ActionKind::Compound, None)
+            }),
+            Entry::Undo,
+            Entry::Redo(build_description(
+                ActionKind::Delete,
+                Some("./trash/file1.jpg"),
+            )),
+            Entry::Folders(vec![]),
+            Entry::Files(vec![PathBuf::from("./images/file\\2\n.jpg")]),
+            Entry::Sources(vec![PathBuf::from("./images")]),
+            Entry::AddSource {
+                directory: PathBuf::from("./phone"),
+                files: vec![PathBuf::from("./phone/file3.jpg")],
+            },
+            Entry::RemoveSource(PathBuf::from("./phone")),
+            Entry::Purge,
+        ]
+    }
+
+    #[test]
+    fn ensure_entries_are_read_back_when_opening() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let path = dir.path().join("session.journal");
+        let mut journal = Journal::create(&path).unwrap();
+        for entry in build_entries() {
+            journal.append(&entry).unwrap();
+        }
+
+        let (_, actual_entries) = Journal::open(&path).unwrap();
+
+        assert_eq!(actual_entries, build_entries());
+    }
+
+    #[test]
+    fn ensure_partially_written_entry_is_dropped_and_cut_off() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let path = dir.path().join("session.journal");
+        let mut journal = Journal::create(&path).unwrap();
+        journal.append(&Entry::Purge).unwrap();
+        let complete_length = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"0123456789abcdef\taction\tmove\t./fil")
+            .unwrap();
+
+        let (mut journal, actual_entries) = Journal::open(&path).unwrap();
+        assert_eq!(actual_entries, vec![Entry::Purge]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete_length);
+
+        journal.append(&Entry::Undo).unwrap();
+        let (_, actual_entries) = Journal::open(&path).unwrap();
+        assert_eq!(actual_entries, vec![Entry::Purge, Entry::Undo]);
+    }
+
+    #[test]
+    fn ensure_trailing_entry_with_bad_checksum_is_dropped() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let path = dir.path().join("session.journal");
+        let mut journal = Journal::create(&path).unwrap();
+        journal.append(&Entry::Purge).unwrap();
+        journal.append(&Entry::Undo).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 2;
+        contents[last] = b'x';
+        fs::write(&path, contents).unwrap();
+
+        let (_, actual_entries) = Journal::open(&path).unwrap();
+
+        assert_eq!(actual_entries, vec![Entry::Purge]);
+    }
+
+    #[test]
+    fn ensure_damaged_entry_in_the_middle_is_an_error() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let path = dir.path().join("session.journal");
+        let mut journal = Journal::create(&path).unwrap();
+        journal.append(&Entry::Purge).unwrap();
+        journal.append(&Entry::Undo).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        contents[0] = b'x';
+        fs::write(&path, contents).unwrap();
+
+        let actual_error = Journal::open(&path).err().unwrap();
+
+        assert_eq!(actual_error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,384 @@
+//! Just enough JSON for the [server](crate::server): a value type that can be written out and
+//! parsed from request bodies.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::io::{Error, ErrorKind};
+
+/// How deeply arrays and objects can be nested, so that a hostile body can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Builds an object from its keys and values, keeping their order.
+    pub fn object<'a>(members: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the value of a key if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(member_key, _)| member_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Parses a complete JSON document.
+    ///
+    /// # Errors
+    ///
+    /// If the text isn't valid JSON, an [InvalidData](ErrorKind::InvalidData) error is returned.
+    pub fn parse(text: &str) -> Result<Json, Error> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        match parser.position == parser.bytes.len() {
+            true => Ok(value),
+            false => Err(parser.error()),
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Json {
+        Json::String(string.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Json {
+        Json::String(string)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(number: f64) -> Json {
+        Json::Number(number)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(boolean: bool) -> Json {
+        Json::Bool(boolean)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(option: Option<T>) -> Json {
+        match option {
+            Some(value) => value.into(),
+            None => Json::Null,
+        }
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Json::Null => formatter.write_str("null"),
+            Json::Bool(boolean) => write!(formatter, "{}", boolean),
+            Json::Number(number) if number.is_finite() => write!(formatter, "{}", number),
+            Json::Number(_) => formatter.write_str("null"),
+            Json::String(string) => write_string(formatter, string),
+            Json::Array(values) => {
+                formatter.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        formatter.write_char(',')?;
+                    }
+                    write!(formatter, "{}", value)?;
+                }
+                formatter.write_char(']')
+            }
+            Json::Object(members) => {
+                formatter.write_char('{')?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        formatter.write_char(',')?;
+                    }
+                    write_string(formatter, key)?;
+                    write!(formatter, ":{}", value)?;
+                }
+                formatter.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(formatter: &mut Formatter, string: &str) -> fmt::Result {
+    formatter.write_char('"')?;
+    for character in string.chars() {
+        match character {
+            '"' => formatter.write_str("\\\"")?,
+            '\\' => formatter.write_str("\\\\")?,
+            '\n' => formatter.write_str("\\n")?,
+            '\r' => formatter.write_str("\\r")?,
+            '\t' => formatter.write_str("\\t")?,
+            character if (character as u32) < 0x20 => {
+                write!(formatter, "\\u{:04x}", character as u32)?
+            }
+            character => formatter.write_char(character)?,
+        }
+    }
+    formatter.write_char('"')
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// How many arrays and objects the parser is in.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid JSON at byte {}", self.position),
+        )
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), Error> {
+        match self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            true => {
+                self.position += literal.len();
+                Ok(())
+            }
+            false => Err(self.error()),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, Error> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b'[' | b'{') if self.depth == MAX_DEPTH => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "JSON nested deeper than {} at byte {}",
+                    MAX_DEPTH, self.position
+                ),
+            )),
+            Some(b'[') => {
+                self.depth += 1;
+                let array = self.parse_array();
+                self.depth -= 1;
+                array
+            }
+            Some(b'{') => {
+                self.depth += 1;
+                let object = self.parse_object();
+                self.depth -= 1;
+                object
+            }
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, Error> {
+        let start = self.position;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+            self.bytes.get(self.position)
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error())
+    }
+
+    fn parse_string(&mut self) -> Result<String, Error> {
+        self.expect("\"")?;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = *self.bytes.get(self.position).ok_or_else(|| self.error())?;
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.bytes.get(self.position).ok_or_else(|| self.error())?;
+                    self.position += 1;
+                    let character = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error()),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error())
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, Error> {
+        let first = self.parse_hex_quad()?;
+        let code_point = match first {
+            0xd800..=0xdbff => {
+                self.expect("\\u")?;
+                let second = self.parse_hex_quad()?;
+                if !(0xdc00..=0xdfff).contains(&second) {
+                    return Err(self.error());
+                }
+                0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+            }
+            _ => first,
+        };
+        char::from_u32(code_point).ok_or_else(|| self.error())
+    }
+
+    fn parse_hex_quad(&mut self) -> Result<u32, Error> {
+        let hex = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error())?;
+        self.position += 4;
+        Ok(hex)
+    }
+
+    fn parse_array(&mut self) -> Result<Json, Error> {
+        self.expect("[")?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.expect("]").is_ok() {
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.expect(",").is_err() {
+                self.expect("]")?;
+                return Ok(Json::Array(values));
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, Error> {
+        self.expect("{")?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.expect("}").is_ok() {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            if self.expect(",").is_err() {
+                self.expect("}")?;
+                return Ok(Json::Object(members));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+
+    #[test]
+    fn ensure_values_survive_a_round_trip() {
+        let value = Json::object([
+            ("path", Json::from("./images/\"quoted\"\\file\n.jpg")),
+            ("index", Json::from(3)),
+            ("done", Json::from(false)),
+            ("missing", Json::Null),
+            (
+                "list",
+                Json::Array(vec![Json::from("a"), Json::Number(-1.5)]),
+            ),
+        ]);
+
+        let actual = Json::parse(&value.to_string()).unwrap();
+
+        assert_eq!(actual, value);
+    }
+
+    #[test]
+    fn ensure_unicode_escapes_are_parsed() {
+        let actual = Json::parse(r#"{"name": "caf\u00e9 \ud83d\udcf7"}"#).unwrap();
+
+        assert_eq!(actual.get("name").unwrap().as_str(), Some("café 📷"));
+    }
+
+    #[test]
+    fn ensure_invalid_json_is_rejected() {
+        assert!(Json::parse("{\"folder\": }").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("{} trailing").is_err());
+    }
+
+    #[test]
+    fn ensure_deeply_nested_json_is_rejected() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(Json::parse(&nested(64)).is_ok());
+        assert!(Json::parse(&nested(65)).is_err());
+        assert!(Json::parse(&"[{\"a\":".repeat(100_000)).is_err());
+    }
+}
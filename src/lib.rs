@@ -4,25 +4,93 @@
 //! [Backend] allows for several different actions for loading files/folders and moving loaded
 //! files into those folders. These actions include:
 //! - loading all folders and files from a single directory
+//! - adding and removing more source directories of files
 //! - loading just folders from a directory
 //! - adding a single folder by its path
+//! - creating a new folder to sort into
 //! - moving a file
 //! - deleting a file
 //! - skipping a file
+//! - moving, deleting or skipping a selection of files at once
+//! - moving files into folders automatically by [rules](Rule), which can be loaded from a file
+//! - moving files into dated folders by a [template](FolderTemplate), creating the folders
+//! - renaming the current file, either to a given name or by a [template](NameTemplate)
+//! - reading the [metadata](Metadata) of files, such as when, with which camera and where a
+//!   photo was taken
 //!
-//! All operations[^note] that deal with files can be undone and redone. When these
+//! All operations that deal with files can be undone and redone. When these
 //! actions are performed, their respective action is added to an undo stack or a redo stack in
-//! case the user wishes to playback previous actions.
+//! case the user wishes to playback previous actions. Both stacks can be listed as
+//! [descriptions](ActionDescription), e.g. to show a history panel.
 //!
-//! [^note]: Deletions are currently not capable of being undone.
+//! Deleted files are staged in a trash folder instead of being removed right away so that the
+//! deletion can be undone. Staged files are purged once they can no longer be reached by undoing,
+//! i.e. when new files are loaded or when [Backend::purge_trash] is called. Alternatively, deleted
+//! files can be put in the desktop's trash by [setting](Backend::set_trash_mode) the
+//! [FreeDesktop](TrashMode::FreeDesktop) trash mode.
+//!
+//! A session can be [recorded](Backend::start_session) to a journal on disk so that it can be
+//! [resumed](Backend::resume_session), including its undo and redo history, if the frontend
+//! crashes.
+//!
+//! With the `server` feature, the `server` module exposes a [Backend] as a JSON-over-HTTP API so
+//! that it can be driven from a browser.
 
-use crate::control_flow::{Controllable, Move, Skip};
+use crate::control_flow::{
+    Batch, Compound, Controllable, CopyFile, CreateFolder, Delete, Link, Move, Rename, Skip,
+};
+use crate::duplicates::DuplicateIndex;
 use crate::filesystem::{Filesystem, FilesystemIO};
+use crate::journal::{Entry, Journal};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io::{Error, ErrorKind};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
 
+mod collision;
 mod control_flow;
+mod dimensions;
+mod duplicates;
+mod encoding;
+mod exif;
 mod filesystem;
+mod filter;
+mod format;
+mod glob;
+mod hash;
+mod journal;
+#[cfg(feature = "server")]
+mod json;
+mod metadata;
+mod names;
+mod order;
+#[cfg(feature = "perceptual")]
+mod perceptual;
+mod rules;
+#[cfg(feature = "server")]
+pub mod server;
+mod template;
+mod transfer;
+mod trash;
+
+pub use crate::collision::{CollisionPolicy, MoveOutcome};
+pub use crate::control_flow::{ActionDescription, ActionKind};
+pub use crate::filesystem::Recursion;
+pub use crate::filter::{FileFilter, IMAGE_EXTENSIONS};
+pub use crate::format::FileFormat;
+pub use crate::glob::Glob;
+pub use crate::metadata::{GpsPosition, Metadata};
+pub use crate::order::{SortKey, SortOrder};
+#[cfg(feature = "perceptual")]
+pub use crate::perceptual::DEFAULT_THRESHOLD as NEAR_DUPLICATE_THRESHOLD;
+pub use crate::rules::{Bounds, Condition, ProposedMove, Rule, RuleError};
+pub use crate::template::{FolderTemplate, NameTemplate};
+pub use crate::transfer::{LinkKind, TransferMode};
+pub use crate::trash::TrashMode;
 
 pub struct Backend {
     /// Collection of all files loaded to be sorted.
@@ -31,13 +99,36 @@ pub struct Backend {
     pub folders: Vec<PathBuf>,
     /// The current working directory.
     pub pwd: String,
+    /// The directories that [files](Backend::files) were loaded from. The first one is the
+    /// current working directory and the others were [added](Backend::add_source) to it.
+    pub sources: Vec<PathBuf>,
     /// The index to the current file in the [files vector](Backend::files).
     pub current_file_index: usize,
+    /// Decides which files are kept when [loading](Backend::load_folders_and_files).
+    pub file_filter: FileFilter,
+    /// Decides whether files in subfolders are loaded as well.
+    pub recursion: Recursion,
+    /// What happens when a file is [moved](Backend::move_file) onto a name that is taken.
+    pub collision_policy: CollisionPolicy,
+    /// Whether a file is checked against the folder it is [moved](Backend::move_file) to, so
+    /// that byte-identical copies are reported instead of moved.
+    pub detect_duplicates: bool,
+    /// Whether [sorting](Backend::sort_file) a file moves, copies or links it.
+    pub transfer_mode: TransferMode,
+    /// How newly loaded files are ordered. Without one they're kept in the order they were
+    /// found in.
+    pub sort_order: Option<SortOrder>,
+    /// Rules that sort files automatically when [applied](Backend::apply_rules).
+    pub rules: Vec<Rule>,
     undo_stack: Vec<Box<dyn Controllable>>,
     redo_stack: Vec<Box<dyn Controllable>>,
     #[doc(hidden)]
-    pub filesystem_helper: Box<dyn FilesystemIO>,
+    pub filesystem_helper: Rc<dyn FilesystemIO>,
     end_of_files: bool,
+    trash_mode: TrashMode,
+    journal: Option<Journal>,
+    duplicate_index: DuplicateIndex,
+    metadata_cache: RefCell<HashMap<PathBuf, (SystemTime, Metadata)>>,
 }
 
 impl Default for Backend {
@@ -52,11 +143,23 @@ impl Backend {
             files: Vec::new(),
             folders: Vec::new(),
             pwd: String::new(),
+            sources: Vec::new(),
             current_file_index: 0,
+            file_filter: FileFilter::default(),
+            recursion: Recursion::default(),
+            collision_policy: CollisionPolicy::default(),
+            detect_duplicates: false,
+            transfer_mode: TransferMode::default(),
+            sort_order: None,
+            rules: Vec::new(),
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
-            filesystem_helper: Box::new(Filesystem::new()),
+            filesystem_helper: Rc::new(Filesystem::new()),
             end_of_files: false,
+            trash_mode: TrashMode::Staging,
+            journal: None,
+            duplicate_index: DuplicateIndex::default(),
+            metadata_cache: RefCell::new(HashMap::new()),
         }
     }
 
@@ -68,37 +171,292 @@ impl Backend {
     /// Returns an [Option] of &[PathBuf] to the current file.
     ///
     /// A current file may not exist in certain cases, such as no files have been loaded or if the
-    /// index reaches the end of the file list.
+    /// index reaches the end of the file list. Once every file has been sorted, there is no
+    /// current file either, which is how frontends can tell that a batch finished the files.
     pub fn get_current_file(&self) -> Option<&PathBuf> {
-        if self.current_file_index >= self.file_count() {
+        if self.end_of_files || self.current_file_index >= self.file_count() {
             return None;
         }
 
         Some(&self.files[self.current_file_index])
     }
 
+    /// Detects the [format](FileFormat) of a loaded file from its content, so that frontends can
+    /// choose how to show it even if its extension is wrong or missing.
+    ///
+    /// # Errors
+    ///
+    /// If there is no file at the index, a [NotFound](ErrorKind::NotFound) error is returned. If
+    /// the file can't be read, that I/O error is returned.
+    pub fn file_format(&self, index: usize) -> Result<FileFormat, Error> {
+        match self.files.get(index) {
+            Some(file) => FileFormat::of_file(file),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("there is no file at index {}", index),
+            )),
+        }
+    }
+
+    /// Reads the [metadata](Metadata) of a loaded file, such as when, with which camera and where
+    /// a photo was taken.
+    ///
+    /// The metadata is read once and kept until the file is changed or new files are loaded, so
+    /// that frontends can ask for it whenever a file is shown.
+    ///
+    /// # Errors
+    ///
+    /// If there is no file at the index, a [NotFound](ErrorKind::NotFound) error is returned. If
+    /// the file can't be read, that I/O error is returned.
+    pub fn metadata(&self, index: usize) -> Result<Metadata, Error> {
+        let file = self.files.get(index).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("there is no file at index {}", index),
+            )
+        })?;
+        let modified = fs::metadata(file)?.modified()?;
+        if let Some((cached_modified, metadata)) = self.metadata_cache.borrow().get(file) {
+            if *cached_modified == modified {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let metadata = Metadata::of_file(file)?;
+        self.metadata_cache
+            .borrow_mut()
+            .insert(file.clone(), (modified, metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// Returns the loaded files whose content doesn't match their extension, along with the
+    /// format detected from their content. Files that can't be read are left out.
+    pub fn extension_mismatches(&self) -> Vec<(PathBuf, FileFormat)> {
+        self.files
+            .iter()
+            .filter_map(|file| match FileFormat::of_file(file) {
+                Ok(format) if !format.matches_extension(file) => Some((file.clone(), format)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Groups the files left to sort that look alike, such as burst shots or resized copies,
+    /// so that a frontend can present each group as a single decision.
+    ///
+    /// Files are compared by a perceptual hash of their pixels and grouped when their hashes
+    /// differ in at most `threshold` of 64 bits, directly or through other files of the group.
+    /// [NEAR_DUPLICATE_THRESHOLD] is a good start. Groups hold indexes into
+    /// [files](Backend::files) in queue order, and files that can't be decoded are left out.
+    ///
+    /// Every file is decoded, so this takes a while for large queues.
+    #[cfg(feature = "perceptual")]
+    pub fn near_duplicate_groups(&self, threshold: u32) -> Vec<Vec<usize>> {
+        let hashes: Vec<(usize, u64)> = self
+            .files
+            .iter()
+            .enumerate()
+            .skip(self.current_file_index)
+            .filter_map(|(index, file)| Some((index, perceptual::difference_hash(file).ok()?)))
+            .collect();
+
+        perceptual::group_by_distance(&hashes, threshold)
+    }
+
     /// Loads all files and directories in the specified path.
     ///
     /// Files and folders are loaded into their own vectors and kept in the object's state.
     /// Any files and folders that were previously loaded are cleared and replaced with these new
-    /// ones. All other state is cleared as well.
+    /// ones. All other state is cleared as well, which purges any deleted files still staged in
+    /// the trash.
+    ///
+    /// Files in subfolders are loaded as well, in a stable order, if the
+    /// [recursion](Backend::recursion) allows it. Folders are only ever the directories directly
+    /// in the path.
+    ///
+    /// Only files allowed by the [file filter](Backend::file_filter) are loaded. The number of
+    /// files that were excluded by it is returned.
     ///
     /// # Errors
     ///
-    /// If there are any I/O errors reading from the specified directory, an error variant will be
-    /// returned.
-    pub fn load_folders_and_files(&mut self, directory: String) -> Result<(), Error> {
+    /// If there are any I/O errors reading from the specified directory or purging the trash, an
+    /// error variant will be returned.
+    pub fn load_folders_and_files(&mut self, directory: String) -> Result<usize, Error> {
         let clean_directory = directory.trim();
 
-        (self.folders, self.files) = self
+        let (folders, files) = self
             .filesystem_helper
             .load_filesystem_elements(Path::new(&clean_directory))?;
+        let mut files = self.walk_files(Path::new(&clean_directory), files, &folders)?;
+        let loaded_count = files.len();
+        files.retain(|file| self.file_filter.is_allowed(file));
+        if let Some(sort_order) = &self.sort_order {
+            sort_order.sort(&mut files);
+        }
+        self.purge_trash()?;
+        self.metadata_cache.get_mut().clear();
+        self.folders = folders;
+        self.files = files;
+        self.sources = vec![PathBuf::from(clean_directory)];
         self.pwd = directory;
         self.current_file_index = 0;
-        self.undo_stack = Vec::new();
+        self.end_of_files = false;
+
+        self.record(self.load_entry())?;
+        Ok(loaded_count - self.file_count())
+    }
+
+    /// Returns the files in subfolders of the directory as well if the
+    /// [recursion](Backend::recursion) allows it, otherwise the files directly in it.
+    fn walk_files(
+        &self,
+        directory: &Path,
+        files: Vec<PathBuf>,
+        folders: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, Error> {
+        if self.recursion.max_depth == 0 {
+            return Ok(files);
+        }
+        let excluded_folders = match self.recursion.exclude_folders {
+            true => folders,
+            false => &[],
+        };
+
+        self.filesystem_helper.load_files_recursively(
+            directory,
+            self.recursion.max_depth,
+            excluded_folders,
+        )
+    }
+
+    /// Adds another source directory and appends its files to the end of the queue.
+    ///
+    /// The current file and the undo and redo history are kept. Files that are already queued,
+    /// such as those in a source nested in another one, are not queued twice. The
+    /// [recursion](Backend::recursion) and [file filter](Backend::file_filter) apply like they do
+    /// when [loading](Backend::load_folders_and_files), and the number of files excluded by the
+    /// filter is returned.
+    ///
+    /// # Errors
+    ///
+    /// If the directory is already a source, an [AlreadyExists](ErrorKind::AlreadyExists) error
+    /// is returned. If there are any I/O errors reading from the directory, an error variant will
+    /// be returned.
+    pub fn add_source(&mut self, directory: String) -> Result<usize, Error> {
+        let directory = PathBuf::from(directory.trim());
+        if self.sources.contains(&directory) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} is already a source", directory.display()),
+            ));
+        }
+
+        let (_, files) = self
+            .filesystem_helper
+            .load_filesystem_elements(&directory)?;
+        let mut files = self.walk_files(&directory, files, &self.folders)?;
+        let loaded_count = files.len();
+        files.retain(|file| self.file_filter.is_allowed(file));
+        let excluded_count = loaded_count - files.len();
+        let queued: HashSet<&PathBuf> = self.files.iter().collect();
+        files.retain(|file| !queued.contains(file));
+        if let Some(sort_order) = &self.sort_order {
+            sort_order.sort(&mut files);
+        }
+
+        self.append_source(directory.clone(), files.clone());
+        self.record(Entry::AddSource { directory, files })?;
+        Ok(excluded_count)
+    }
+
+    fn append_source(&mut self, directory: PathBuf, mut files: Vec<PathBuf>) {
+        // the last file was already sorted, so the first new one becomes the current file
+        if self.end_of_files && !files.is_empty() {
+            self.current_file_index += 1;
+            self.end_of_files = false;
+        }
+        self.sources.push(directory);
+        self.files.append(&mut files);
+    }
+
+    /// Removes a source directory along with its files that haven't been sorted yet.
+    ///
+    /// Files that were already sorted stay in the queue so that their actions can still be
+    /// undone, and the current file stays current unless it came from the removed source. Since
+    /// the undone actions may refer to removed files, the redo history is cleared.
+    ///
+    /// # Errors
+    ///
+    /// If the directory isn't a source, a [NotFound](ErrorKind::NotFound) error is returned.
+    pub fn remove_source(&mut self, directory: String) -> Result<(), Error> {
+        let directory = PathBuf::from(directory.trim());
+        if !self.sources.contains(&directory) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} is not a source", directory.display()),
+            ));
+        }
+
+        self.remove_source_files(&directory);
+        self.record(Entry::RemoveSource(directory))
+    }
+
+    fn remove_source_files(&mut self, directory: &Path) {
+        let sorted_count = match self.end_of_files {
+            true => self.current_file_index + 1,
+            false => self.current_file_index,
+        };
+        let files = std::mem::take(&mut self.files);
+        self.files = files
+            .into_iter()
+            .enumerate()
+            .filter(|(index, file)| {
+                *index < sorted_count || self.source_of(file) != Some(directory)
+            })
+            .map(|(_, file)| file)
+            .collect();
+        self.sources.retain(|source| source != directory);
         self.redo_stack = Vec::new();
+    }
 
-        Ok(())
+    /// Sorts the files that haven't been sorted into folders yet.
+    ///
+    /// Only the files after the current one are reordered, so the current file stays current
+    /// and every action can still be undone. Files that an undone action can be redone on keep
+    /// their place as well.
+    ///
+    /// # Errors
+    ///
+    /// If a session is being recorded and writing to its journal fails, an error variant will be
+    /// returned.
+    pub fn sort_files(&mut self, sort_order: SortOrder) -> Result<(), Error> {
+        let fixed_count = match self.end_of_files {
+            true => self.file_count(),
+            false => {
+                let redo_file_count: usize =
+                    self.redo_stack.iter().map(|item| item.file_count()).sum();
+                self.current_file_index + redo_file_count.max(1)
+            }
+        };
+        if fixed_count >= self.file_count() {
+            return Ok(());
+        }
+
+        let mut unsorted = self.files.split_off(fixed_count);
+        sort_order.sort(&mut unsorted);
+        self.files.append(&mut unsorted);
+
+        self.record(Entry::Files(self.files.clone()))
+    }
+
+    /// Returns the source directory that a file was loaded from, which is the longest source
+    /// that contains it.
+    pub fn source_of(&self, file: &Path) -> Option<&Path> {
+        self.sources
+            .iter()
+            .filter(|source| file.starts_with(source))
+            .max_by_key(|source| source.components().count())
+            .map(PathBuf::as_path)
     }
 
     /// Loads directories in the specified path.
@@ -118,7 +476,7 @@ impl Backend {
             .load_filesystem_elements(Path::new(&directory.trim()))?
             .0;
 
-        Ok(())
+        self.record(Entry::Folders(self.folders.clone()))
     }
 
     /// Adds a specified folder to the list of folders where files can be sorted into.
@@ -131,465 +489,2718 @@ impl Backend {
         let new_folder = self.filesystem_helper.add_folder(directory.trim())?;
         self.folders.push(new_folder);
 
+        self.record(Entry::Folders(self.folders.clone()))
+    }
+
+    /// Creates a new folder named `name` in `parent` and adds it to the
+    /// [folders](Backend::folders), so that a new category can be made without leaving the
+    /// frontend. Returns the new folder.
+    ///
+    /// Names have to work on any common filesystem, so characters and names that Windows doesn't
+    /// allow are rejected everywhere. Creating the folder is an action of its own, whose undo
+    /// removes the folder again if it is still empty.
+    ///
+    /// # Errors
+    ///
+    /// If the name isn't allowed, an [InvalidInput](ErrorKind::InvalidInput) error is returned
+    /// that says why. If the folder already exists, an [AlreadyExists](ErrorKind::AlreadyExists)
+    /// error is returned. If there are any I/O errors creating the folder, an error variant will
+    /// be returned.
+    pub fn create_folder(&mut self, parent: PathBuf, name: &str) -> Result<PathBuf, Error> {
+        names::validate_name(name)?;
+        let folder = parent.join(name);
+        // not through the action, whose redo accepts a folder that is already there
+        match self.filesystem_helper.create_folder(&folder) {
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} already exists", folder.display()),
+                ))
+            }
+            result => result?,
+        }
+
+        let action = CreateFolder::new(folder.clone(), Rc::clone(&self.filesystem_helper));
+        self.push_action(Box::new(action))?;
+        self.folders.push(folder.clone());
+        self.record(Entry::Folders(self.folders.clone()))?;
+
+        Ok(folder)
+    }
+
+    /// Loads the [rules](Backend::rules) from a rule file, replacing the ones loaded before.
+    ///
+    /// Rule files have one rule per line: the conditions a file has to match, separated by
+    /// spaces, followed by `->` and the folder to sort it into. Lines that are empty or start
+    /// with `#` are ignored.
+    ///
+    /// ```text
+    /// # screenshots of any size, and large photos from one camera for a year
+    /// name:"Screenshot *" ext:png,jpg -> Screenshots
+    /// camera:"Canon EOS R5" date:2024 width:4000.. -> Canon/2024
+    /// size:..100k -> Small
+    /// ```
+    ///
+    /// The conditions are `name` with a [glob](Glob), `regex` with a regular expression for the
+    /// name (with the `regex` feature), `ext` with a list of extensions, `size` in bytes with an
+    /// optional `k`, `M` or `G` suffix, `width` and `height` in pixels, `camera` with the camera
+    /// model and `date` with a `YYYY`, `YYYY-MM` or `YYYY-MM-DD` date. Sizes, dimensions and dates
+    /// can be a single value or a range like `a..b`, `a..` or `..b`, where both ends are
+    /// included. Values with spaces are put in double quotes, in which `\"` and `\\` stand for a
+    /// quote and a backslash. A rule with the same conditions as an earlier one is rejected,
+    /// since it could never sort a file. See [RuleError] for the conflicts that are found.
+    ///
+    /// Relative destinations are resolved against the current working directory, and they have
+    /// to be among the [folders](Backend::folders) already, such as by
+    /// [adding](Backend::add_folder) them first.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be parsed, an [InvalidData](ErrorKind::InvalidData) error is returned.
+    /// If a destination isn't one of the folders, a [NotFound](ErrorKind::NotFound) error is
+    /// returned that says whether it exists at all, and nothing is loaded. Either error wraps a
+    /// [RuleError] that points at the problem.
+    pub fn load_rules(&mut self, path: String) -> Result<(), Error> {
+        let text = fs::read_to_string(path.trim())?;
+        let parsed_rules = rules::parse_rules(&text, Path::new(self.pwd.trim()))?;
+
+        if let Some(parsed) = parsed_rules
+            .iter()
+            .find(|parsed| !self.folders.contains(&parsed.rule.destination))
+        {
+            let destination = &parsed.rule.destination;
+            let message = match self.filesystem_helper.exists(destination) {
+                true => format!("{} is not a folder to sort into", destination.display()),
+                false => format!("{} doesn't exist", destination.display()),
+            };
+            let error = RuleError {
+                line: parsed.line,
+                column: parsed.destination_column,
+                message,
+            };
+            return Err(Error::new(ErrorKind::NotFound, error));
+        }
+        self.rules = parsed_rules.into_iter().map(|parsed| parsed.rule).collect();
+
         Ok(())
     }
 
     /// Clears the currently loaded folders.
-    pub fn clear_folders(&mut self) {
+    ///
+    /// # Errors
+    ///
+    /// If a session is being recorded and writing to its journal fails, an error variant will be
+    /// returned.
+    pub fn clear_folders(&mut self) -> Result<(), Error> {
         self.folders = Vec::new();
+
+        self.record(Entry::Folders(Vec::new()))
     }
 
     /// Deletes the current file.
     ///
+    /// A `control_flow` action that moves the current file into the trash and increments the
+    /// index like [moving](Backend::move_file) does. The file stays in the trash until it can no
+    /// longer be undone, so undoing the deletion restores it.
+    ///
     /// # Errors
     ///
     /// If there are any I/O errors deleting from the specified file, an error variant will be
     /// returned.
-    // TODO: shouldn't this increment like move/skip?
     pub fn delete_file(&mut self) -> Result<(), Error> {
         if let Some(file) = self.get_current_file() {
-            match self.filesystem_helper.delete_file(file) {
-                Ok(_) => {
-                    self.undo_stack.push(Box::new(Skip::new()));
-                }
-                Err(error) => { return Err(error) },
-            }
+            let trashed_file = self.filesystem_helper.trash_file(file)?;
+
+            self.push_action(Box::new(Delete::new(
+                file.clone(),
+                trashed_file,
+                Rc::clone(&self.filesystem_helper),
+            )))?;
+            self.increment()?;
         }
 
         Ok(())
     }
 
-    /// Moves the current file to a specified path.
+    /// Sets where files deleted from now on are put.
     ///
-    /// A `control_flow` action that moves the current file to the specified path. It should be
-    /// noted that this method takes a [PathBuf] instead of a [String] like the loading methods.
-    /// This is because it is expected that the `to_folder` comes from the selected folder's path,
-    /// which is already a [PathBuf].
+    /// Deletions that were already performed keep restoring from and purging the trash they
+    /// were put in.
     ///
     /// # Errors
     ///
-    /// If there are any I/O errors moving the file, an error variant will be
+    /// If a session is being recorded and writing to its journal fails, an error variant will be
     /// returned.
-    pub fn move_file(&mut self, to_folder: PathBuf) -> Result<(), Error> {
-        if self.files.is_empty() {
-            return Err(Error::from(ErrorKind::NotFound));
-        }
+    pub fn set_trash_mode(&mut self, trash_mode: TrashMode) -> Result<(), Error> {
+        self.filesystem_helper.set_trash_mode(trash_mode.clone());
+        self.trash_mode = trash_mode.clone();
 
-        if let Some(from_file) = self.get_current_file() {
-            let destination = Self::build_destination(to_folder, from_file)?;
+        self.record(Entry::TrashMode(trash_mode))
+    }
+
+    /// Permanently removes all deleted files that are staged in the trash.
+    ///
+    /// Since purged deletions can no longer be undone, the undo and redo history is cleared.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors removing the staged files, an error variant will be returned.
+    /// Actions that were not purged yet are kept.
+    pub fn purge_trash(&mut self) -> Result<(), Error> {
+        self.redo_stack = Vec::new();
+        while let Some(mut item) = self.undo_stack.pop() {
+            if let Err(error) = item.purge() {
+                self.undo_stack.push(item);
+                return Err(error);
+            }
+        }
 
-            self.filesystem_helper.move_file(from_file, &destination)?;
+        self.record(Entry::Purge)
+    }
 
-            println!("incrementing {}", self.current_file_index);
-            self.undo_stack
-                .push(Box::new(Move::new(from_file.clone(), destination)));
-            self.increment()?;
+    /// Pushes a newly performed action onto the undo stack.
+    ///
+    /// The redo stack is cleared since its actions can no longer be replayed on top of the new
+    /// one. Actions on the redo stack have already been undone, so there is nothing to purge.
+    ///
+    /// The action has already been performed, so it is written to the session's journal after
+    /// the fact. If it can't be written, it is undone again so that the session doesn't go on
+    /// without it. A crash in between still leaves a change the journal doesn't know about.
+    fn push_action(&mut self, mut action: Box<dyn Controllable>) -> Result<(), Error> {
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.append(&Entry::Action(action.describe())) {
+                action.undo()?;
+                return Err(error);
+            }
         }
+        self.redo_stack = Vec::new();
+        self.undo_stack.push(action);
 
         Ok(())
     }
 
-    fn build_destination(mut to_folder: PathBuf, from_file: &Path) -> Result<PathBuf, Error> {
-        let file_name = match from_file.file_name() {
-            Some(some_file) => some_file,
-            None => {
-                return Err(Error::from(ErrorKind::InvalidData));
-            }
-        };
-        to_folder.push(file_name);
+    /// Appends an entry to the session's journal, if a session is being recorded.
+    fn record(&mut self, entry: Entry) -> Result<(), Error> {
+        match &mut self.journal {
+            Some(journal) => journal.append(&entry),
+            None => Ok(()),
+        }
+    }
 
-        Ok(to_folder)
+    fn load_entry(&self) -> Entry {
+        Entry::Load {
+            pwd: self.pwd.clone(),
+            current_file_index: self.current_file_index,
+            end_of_files: self.end_of_files,
+            folders: self.folders.clone(),
+            files: self.files.clone(),
+        }
     }
 
-    fn increment(&mut self) -> Result<(), Error> {
-        self.is_end_of_files(self.current_file_index, self.file_count())?;
-        self.current_file_index += 1;
+    /// Starts recording the session to a journal at the specified path.
+    ///
+    /// Every change from now on is appended to the journal and synced to disk right after it is
+    /// made, so that the session can be [resumed](Backend::resume_session) after a crash. A crash
+    /// while a change is being made can leave it out of the journal, even though the file was
+    /// already moved or deleted. Any existing journal at
+    /// the path is replaced. The currently loaded files and folders are recorded as the start of
+    /// the session; undo and redo history from before that is not.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors creating or writing the journal, an error variant will be
+    /// returned.
+    pub fn start_session(&mut self, path: PathBuf) -> Result<(), Error> {
+        let mut journal = Journal::create(&path)?;
+        journal.append(&self.load_entry())?;
+        journal.append(&Entry::Sources(self.sources.clone()))?;
+        journal.append(&Entry::TrashMode(self.trash_mode.clone()))?;
+        self.journal = Some(journal);
 
         Ok(())
     }
 
-    /// Skips the current file.
+    /// Stops recording the session. The journal is left on disk.
+    pub fn end_session(&mut self) {
+        self.journal = None;
+    }
+
+    /// Resumes a session from the journal at the specified path.
     ///
-    /// A `control_flow` action that increments the index that points to the current file forward.
-    pub fn skip(&mut self) -> Result<(), Error> {
-        self.increment()?;
-        self.undo_stack.push(Box::new(Skip::new()));
+    /// The files, folders, current directory, current file and both the undo and redo stacks
+    /// are rebuilt as they were when the last complete entry was written. Nothing on the
+    /// filesystem is touched since the journal only records what already happened. The session
+    /// keeps being recorded to the same journal afterwards.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors reading the journal or it is damaged anywhere but in its last
+    /// entry, an error variant will be returned.
+    pub fn resume_session(&mut self, path: PathBuf) -> Result<(), Error> {
+        let (journal, entries) = Journal::open(&path)?;
+
+        self.journal = None;
+        self.files = Vec::new();
+        self.folders = Vec::new();
+        self.pwd = String::new();
+        self.sources = Vec::new();
+        self.current_file_index = 0;
+        self.end_of_files = false;
+        self.undo_stack = Vec::new();
+        self.redo_stack = Vec::new();
+        for entry in entries {
+            self.replay(entry)?;
+        }
+        self.journal = Some(journal);
 
         Ok(())
     }
 
-    fn is_end_of_files(&mut self, file_index: usize, file_count: usize) -> Result<(), Error> {
-        match file_index + 1 >= file_count {
-            true => {
-                self.end_of_files = true;
-                Err(Error::from(ErrorKind::UnexpectedEof))
+    fn replay(&mut self, entry: Entry) -> Result<(), Error> {
+        match entry {
+            Entry::Load {
+                pwd,
+                current_file_index,
+                end_of_files,
+                folders,
+                files,
+            } => {
+                self.sources = vec![PathBuf::from(pwd.trim())];
+                self.pwd = pwd;
+                self.current_file_index = current_file_index;
+                self.folders = folders;
+                self.files = files;
+                self.end_of_files = end_of_files;
+                self.undo_stack = Vec::new();
+                self.redo_stack = Vec::new();
+            }
+            Entry::Folders(folders) => self.folders = folders,
+            Entry::Sources(sources) => self.sources = sources,
+            Entry::Files(files) => self.files = files,
+            Entry::AddSource { directory, files } => self.append_source(directory, files),
+            Entry::RemoveSource(directory) => self.remove_source_files(&directory),
+            Entry::TrashMode(trash_mode) => self.set_trash_mode(trash_mode)?,
+            Entry::Purge => {
+                self.undo_stack = Vec::new();
+                self.redo_stack = Vec::new();
+            }
+            Entry::Undo => {
+                if let Some(item) = self.undo_stack.pop() {
+                    self.step_back(item.file_count());
+                    self.redo_stack.push(item);
+                }
+            }
+            Entry::Redo(description) => {
+                self.redo_stack.pop();
+                let item = description.into_action(Rc::clone(&self.filesystem_helper))?;
+                let file_count = item.file_count();
+                self.undo_stack.push(item);
+                let _ = self.advance(file_count); // the end of files is expected here
+            }
+            Entry::Action(description) => {
+                let item = description.into_action(Rc::clone(&self.filesystem_helper))?;
+                let file_count = item.file_count();
+                self.push_action(item)?;
+                let _ = self.advance(file_count); // the end of files is expected here
             }
-            false => Ok(()),
         }
+
+        Ok(())
     }
 
-    /// Undoes the previous action.
+    /// Moves the current file to a specified path.
     ///
-    /// Undoes the previous `control_flow` action and pushes a redo action onto the `redo_stack`.
+    /// A `control_flow` action that moves the current file to the specified path. It should be
+    /// noted that this method takes a [PathBuf] instead of a [String] like the loading methods.
+    /// This is because it is expected that the `to_folder` comes from the selected folder's path,
+    /// which is already a [PathBuf].
+    ///
+    /// If a file with the same name is already in the folder, the
+    /// [collision policy](Backend::collision_policy) decides what happens. The returned
+    /// [outcome](MoveOutcome) tells where the file ended up or that it was skipped, and the
+    /// recorded action keeps the exact names so that undoing restores them.
+    ///
+    /// When [detecting duplicates](Backend::detect_duplicates), a file whose content is already
+    /// in the folder is left where it is and the copy is returned as a
+    /// [duplicate](MoveOutcome::Duplicate), so that it can be deleted instead.
     ///
     /// # Errors
     ///
-    /// If there are any I/O errors while undoing, an error variant will be
+    /// If there are any I/O errors moving the file, an error variant will be
     /// returned.
-    pub fn undo(&mut self) -> Result<(), Error> {
-        match self.undo_stack.pop() {
-            Some(item) => {
-                let result = item.undo();
-                self.redo_stack.push(item);
-                if self.end_of_files {
-                    self.end_of_files = false;
-                } else {
-                    self.current_file_index -= 1;
-                }
+    pub fn move_file(&mut self, to_folder: PathBuf) -> Result<MoveOutcome, Error> {
+        self.transfer_file(to_folder, TransferMode::Move)
+    }
 
-                result
-            }
-            None => Ok(()),
-        }
+    /// Copies the current file into a folder, leaving the original where it is.
+    ///
+    /// Works just like [moving](Backend::move_file) the file, including how taken names and
+    /// duplicates are handled. The copy keeps the modification time and permissions of the
+    /// original. Undoing removes the copy.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors copying the file, an error variant will be returned.
+    pub fn copy_file(&mut self, to_folder: PathBuf) -> Result<MoveOutcome, Error> {
+        self.transfer_file(to_folder, TransferMode::Copy)
     }
 
-    /// Redoes the action most recently undone.
+    /// Puts a link to the current file into a folder, leaving the file where it is.
     ///
-    /// Redoes the last `control_flow` action on the `redo_stack` and pushes an undo action
-    /// onto the `undo_stack`. The `redo_stack` gets cleared when any action that isn't a
-    /// redo or an undo is performed.
+    /// Works just like [moving](Backend::move_file) the file, including how taken names and
+    /// duplicates are handled. Links let a file be sorted into several folders at once without
+    /// copying it. Undoing removes the link.
     ///
     /// # Errors
     ///
-    /// If there are any I/O errors redoing, an error variant will be
-    /// returned.
-    pub fn redo(&mut self) -> Result<(), Error> {
-        match self.redo_stack.pop() {
-            Some(item) => {
-                let result = item.redo();
-                self.undo_stack.push(item);
-                self.increment()?;
-                result
-            }
-            None => Ok(()),
-        }
+    /// If there are any I/O errors creating the link, an error variant will be returned. Hard
+    /// links fail if the folder is on another filesystem.
+    pub fn link_file(&mut self, to_folder: PathBuf, kind: LinkKind) -> Result<MoveOutcome, Error> {
+        self.transfer_file(to_folder, TransferMode::Link(kind))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::control_flow::Move;
-    use crate::filesystem::FilesystemIO;
-    use crate::Backend;
-    use std::io::{Error, ErrorKind};
-    use std::path::{Path, PathBuf};
+    /// Puts the current file into a folder the way the [transfer mode](Backend::transfer_mode)
+    /// says, so that frontends can offer the same controls for every mode.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors, an error variant will be returned.
+    pub fn sort_file(&mut self, to_folder: PathBuf) -> Result<MoveOutcome, Error> {
+        self.transfer_file(to_folder, self.transfer_mode)
+    }
 
-    struct FilesystemMock {
-        folders: Vec<PathBuf>,
-        files: Vec<PathBuf>,
+    /// Returns the folder a [template](FolderTemplate) puts a loaded file in, from when the file
+    /// was taken or else when it was last modified, so that frontends can show where it would go.
+    ///
+    /// # Errors
+    ///
+    /// If there is no file at the index, a [NotFound](ErrorKind::NotFound) error is returned. If
+    /// the file can't be read, that I/O error is returned. If the folder isn't inside the loaded
+    /// directory, an [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub fn template_folder(
+        &self,
+        template: &FolderTemplate,
+        index: usize,
+    ) -> Result<PathBuf, Error> {
+        let date = self.capture_date(index)?;
+
+        template.resolve(Path::new(self.pwd.trim()), &date)
     }
 
-    impl FilesystemMock {
-        fn new() -> FilesystemMock {
-            FilesystemMock {
-                folders: Vec::new(),
-                files: Vec::new(),
-            }
+    /// Returns when a loaded file was taken, or else when it was last modified in local time,
+    /// written as `YYYY:MM:DD HH:MM:SS`.
+    fn capture_date(&self, index: usize) -> Result<String, Error> {
+        match self.metadata(index)?.capture_date {
+            Some(date) => Ok(date),
+            None => Ok(exif::format_time(
+                fs::metadata(&self.files[index])?.modified()?,
+            )),
         }
     }
 
-    impl FilesystemIO for FilesystemMock {
-        fn load_filesystem_elements(
-            &self,
-            _directory: &Path,
-        ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
-            Ok((self.folders.clone(), self.files.clone()))
-        }
-        fn delete_file(&self, _file: &Path) -> Result<(), Error> {
-            Ok(())
-        }
-        fn move_file(&self, _from_file: &Path, _to_file: &Path) -> Result<(), Error> {
-            Ok(())
+    /// Moves the current file into the folder a [template](FolderTemplate) gives it, such as
+    /// `{root}/{exif.year}/{exif.month}`, creating that folder and any of its parents that are
+    /// missing.
+    ///
+    /// Otherwise this works just like [moving](Backend::move_file) the file. The folders are
+    /// created as part of the move, so undoing it removes them again if they were left empty.
+    ///
+    /// # Errors
+    ///
+    /// If a folder can't be created or there are any I/O errors moving the file, the folders
+    /// that were already created are removed again and the error is returned.
+    pub fn move_file_by_template(
+        &mut self,
+        template: &FolderTemplate,
+    ) -> Result<MoveOutcome, Error> {
+        let from_file = match self.current_file_to_sort()? {
+            Some(from_file) => from_file,
+            None => return Ok(MoveOutcome::Skipped),
+        };
+        let folder = self.template_folder(template, self.current_file_index)?;
+
+        let mut parts = Vec::new();
+        let result = self
+            .create_missing_folders(&folder, &mut parts)
+            .and_then(|()| self.perform_transfer(&from_file, folder, TransferMode::Move));
+
+        let (outcome, action) = match result {
+            Ok((outcome, Some(action))) => (outcome, action),
+            result => {
+                // the failure matters more than whether rolling back went cleanly
+                for part in parts.iter_mut().rev() {
+                    let _ = part.undo();
+                }
+                let (outcome, _) = result?;
+                if outcome == MoveOutcome::Skipped {
+                    self.skip()?;
+                }
+                return Ok(outcome);
+            }
+        };
+        let action: Box<dyn Controllable> = match parts.is_empty() {
+            true => action,
+            false => {
+                parts.push(action);
+                Box::new(Compound::new(from_file, parts))
+            }
+        };
+        self.push_action(action)?;
+        self.increment()?;
+
+        Ok(outcome)
+    }
+
+    /// Creates the folder and any of its parents that are missing, adding an action for each
+    /// folder that was created to the parts.
+    fn create_missing_folders(
+        &self,
+        folder: &Path,
+        parts: &mut Vec<Box<dyn Controllable>>,
+    ) -> Result<(), Error> {
+        let missing_folders: Vec<&Path> = folder
+            .ancestors()
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .collect();
+        for missing_folder in missing_folders.into_iter().rev() {
+            let mut action = CreateFolder::new(
+                missing_folder.to_path_buf(),
+                Rc::clone(&self.filesystem_helper),
+            );
+            action.redo()?;
+            parts.push(Box::new(action));
         }
-        fn add_folder(&self, _folder: &str) -> Result<PathBuf, Error> {
-            match self.folders.len() == 1 {
-                true => Ok(self.folders[0].clone()),
-                false => Err(Error::from(ErrorKind::NotFound)),
+
+        Ok(())
+    }
+
+    /// Gives the current file a new name in the same folder, such as `Sunset.jpg` instead of
+    /// `IMG_1234.jpg`, and returns where it is now.
+    ///
+    /// The file stays the current one under its new name, so that it can be sorted next.
+    /// Renaming it to the name it already has does nothing.
+    ///
+    /// # Errors
+    ///
+    /// If there is no current file, a [NotFound](ErrorKind::NotFound) error is returned. If the
+    /// name isn't allowed, an [InvalidInput](ErrorKind::InvalidInput) error is returned that says
+    /// why. If another file already has the name, an [AlreadyExists](ErrorKind::AlreadyExists)
+    /// error is returned. If there are any I/O errors renaming the file, an error variant will be
+    /// returned.
+    pub fn rename_current(&mut self, new_name: &str) -> Result<PathBuf, Error> {
+        names::validate_name(new_name)?;
+        let from_file = self
+            .get_current_file()
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "there is no current file"))?;
+        let to_file = from_file.with_file_name(new_name);
+        if to_file == from_file {
+            return Ok(to_file);
+        }
+
+        let mut action = Rename::new(
+            from_file,
+            to_file.clone(),
+            Rc::clone(&self.filesystem_helper),
+        );
+        action.redo()?;
+        self.push_action(Box::new(action))?;
+        self.files[self.current_file_index] = to_file.clone();
+        self.record(Entry::Files(self.files.clone()))?;
+
+        Ok(to_file)
+    }
+
+    /// Renames the current file by a [template](NameTemplate), such as `{date}_{counter}{ext}`,
+    /// from when the file was taken or else when it was last modified, and returns where it is
+    /// now.
+    ///
+    /// The counter is the lowest number that gives a name no other file in the folder has.
+    /// Otherwise this works just like [renaming](Backend::rename_current) the file.
+    ///
+    /// # Errors
+    ///
+    /// See [rename_current](Backend::rename_current). The current file also has to be readable
+    /// to find out when it was taken.
+    pub fn rename_current_by_template(
+        &mut self,
+        template: &NameTemplate,
+    ) -> Result<PathBuf, Error> {
+        let file = self
+            .get_current_file()
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "there is no current file"))?;
+        let date = self.capture_date(self.current_file_index)?;
+
+        let mut counter = 1;
+        let name = loop {
+            let name = template.resolve(&file, &date, counter);
+            let renamed_file = file.with_file_name(&name);
+            if !template.has_counter()
+                || renamed_file == file
+                || !self.filesystem_helper.exists(&renamed_file)
+            {
+                break name;
+            }
+            counter += 1;
+        };
+
+        self.rename_current(&name)
+    }
+
+    /// Sends the current file to several folders in one step, such as copying it into two
+    /// folders and moving it into a third, and advances to the next file.
+    ///
+    /// Each destination is handled like [moving](Backend::move_file),
+    /// [copying](Backend::copy_file) or [linking](Backend::link_file) the file on its own, and an
+    /// outcome is returned for each of them in the same order. The move, if there is one, is
+    /// done first so that copies and links are made from where the file ends up. Everything
+    /// that was done is recorded as a single action, so that one undo reverses all of it.
+    ///
+    /// If nothing was done because the file was a duplicate, the file stays the current one. The
+    /// outcomes are returned for the last file as well, after which there is no
+    /// [current file](Backend::get_current_file).
+    ///
+    /// # Errors
+    ///
+    /// If there are no destinations or more than one move, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned. If any destination fails, the
+    /// ones already done are undone and the error is returned.
+    pub fn sort_into(
+        &mut self,
+        destinations: &[(PathBuf, TransferMode)],
+    ) -> Result<Vec<MoveOutcome>, Error> {
+        let move_count = destinations
+            .iter()
+            .filter(|(_, transfer_mode)| *transfer_mode == TransferMode::Move)
+            .count();
+        if destinations.is_empty() || move_count > 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "a file needs at least one destination and can only be moved once",
+            ));
+        }
+        let file = match self.current_file_to_sort()? {
+            Some(file) => file,
+            None => return Ok(vec![MoveOutcome::Skipped; destinations.len()]),
+        };
+
+        let mut order: Vec<usize> = (0..destinations.len()).collect();
+        order.sort_by_key(|index| destinations[*index].1 != TransferMode::Move);
+        let mut outcomes = vec![MoveOutcome::Skipped; destinations.len()];
+        let mut parts: Vec<Box<dyn Controllable>> = Vec::new();
+        let mut source = file.clone();
+        for index in order {
+            let (to_folder, transfer_mode) = &destinations[index];
+            match self.perform_transfer(&source, to_folder.clone(), *transfer_mode) {
+                Ok((outcome, action)) => {
+                    if let (MoveOutcome::Moved(destination), TransferMode::Move) =
+                        (&outcome, transfer_mode)
+                    {
+                        source = destination.clone();
+                    }
+                    parts.extend(action);
+                    outcomes[index] = outcome;
+                }
+                Err(error) => {
+                    // the failure matters more than whether rolling back went cleanly
+                    for part in parts.iter_mut().rev() {
+                        let _ = part.undo();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        match parts.len() {
+            0 if outcomes
+                .iter()
+                .all(|outcome| *outcome == MoveOutcome::Skipped) =>
+            {
+                self.skip()?
+            }
+            0 => {}
+            1 => {
+                self.push_action(parts.remove(0))?;
+                self.advance_past_batch(1)?;
+            }
+            _ => {
+                self.push_action(Box::new(Compound::new(file, parts)))?;
+                self.advance_past_batch(1)?;
             }
         }
+
+        Ok(outcomes)
     }
 
-    fn build_folders() -> Vec<PathBuf> {
-        vec![
-            PathBuf::from("./folder1"),
-            PathBuf::from("./folder2"),
-            PathBuf::from("./folder3"),
-        ]
+    /// Sorts the current file and the ones after it, up to `count` files, into a folder in one
+    /// step, such as when the next twenty photos all belong to the same event.
+    ///
+    /// Each file is put into the folder the way [sort_file](Backend::sort_file) would, and an
+    /// outcome is returned for each of them in order. Files that are skipped by the
+    /// [collision policy](Backend::collision_policy) or left where they are as
+    /// [duplicates](MoveOutcome::Duplicate) are skipped, so that the batch always advances past
+    /// all of its files. Everything is recorded as a single action, so that one undo reverses the
+    /// whole batch.
+    ///
+    /// # Errors
+    ///
+    /// If sorting any of the files fails, the ones already sorted are put back and the error is
+    /// returned, leaving the current file where it was.
+    pub fn sort_next(
+        &mut self,
+        count: usize,
+        to_folder: PathBuf,
+    ) -> Result<Vec<MoveOutcome>, Error> {
+        let selection: Vec<usize> = self.unsorted_indexes().take(count).collect();
+        self.sort_selection(&selection, to_folder)
+    }
+
+    /// Returns the indexes of the files left to sort that the predicate accepts, in their order,
+    /// such as to [sort](Backend::sort_selection) them all at once.
+    pub fn select(&self, mut predicate: impl FnMut(&Path) -> bool) -> Vec<usize> {
+        self.unsorted_indexes()
+            .filter(|index| predicate(&self.files[*index]))
+            .collect()
+    }
+
+    /// Returns the indexes of the files left to sort whose name matches a [glob](Glob).
+    ///
+    /// # Errors
+    ///
+    /// If the pattern isn't a valid glob, an [InvalidInput](ErrorKind::InvalidInput) error is
+    /// returned.
+    pub fn select_glob(&self, pattern: &str) -> Result<Vec<usize>, Error> {
+        let glob = Glob::new(pattern)?;
+
+        Ok(self.select(|file| {
+            file.file_name()
+                .is_some_and(|name| glob.matches(&name.to_string_lossy()))
+        }))
+    }
+
+    /// Sorts the selected files into a folder in one step, like [sort_next](Backend::sort_next)
+    /// does with the files right after the current one.
+    ///
+    /// The selection is a list of indexes into the [files](Backend::files), such as from
+    /// [select](Backend::select) or a range. The selected files are brought forward to the
+    /// current file first, keeping their order, so that the files that weren't selected are
+    /// left to sort after them.
+    ///
+    /// # Errors
+    ///
+    /// If any of the indexes isn't a file left to sort, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned. If sorting any of the files
+    /// fails, the ones already sorted are put back, the files keep their order and the error is
+    /// returned.
+    pub fn sort_selection(
+        &mut self,
+        selection: &[usize],
+        to_folder: PathBuf,
+    ) -> Result<Vec<MoveOutcome>, Error> {
+        let transfer_mode = self.transfer_mode;
+        self.apply_to_selection(selection, |backend, file| {
+            backend.perform_transfer(file, to_folder.clone(), transfer_mode)
+        })
+    }
+
+    /// Deletes the selected files in one step, which a single undo restores. See
+    /// [sort_selection](Backend::sort_selection) for how files are selected.
+    ///
+    /// # Errors
+    ///
+    /// If any of the indexes isn't a file left to sort, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned. If deleting any of the files
+    /// fails, the ones already deleted are restored and the error is returned.
+    pub fn delete_selection(&mut self, selection: &[usize]) -> Result<(), Error> {
+        self.apply_to_selection(selection, |backend, file| {
+            let trashed_file = backend.filesystem_helper.trash_file(file)?;
+            let action: Box<dyn Controllable> = Box::new(Delete::new(
+                file.to_path_buf(),
+                trashed_file,
+                Rc::clone(&backend.filesystem_helper),
+            ));
+            Ok(((), Some(action)))
+        })?;
+
+        Ok(())
+    }
+
+    /// Skips the selected files in one step. See [sort_selection](Backend::sort_selection) for
+    /// how files are selected.
+    ///
+    /// # Errors
+    ///
+    /// If any of the indexes isn't a file left to sort, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub fn skip_selection(&mut self, selection: &[usize]) -> Result<(), Error> {
+        self.apply_to_selection(selection, |_, _| Ok(((), None)))?;
+
+        Ok(())
+    }
+
+    /// Lists the files left to sort that the [rules](Backend::rules) would sort, without sorting
+    /// them. Each file goes into the destination of the first rule it matches.
+    ///
+    /// # Errors
+    ///
+    /// If the destination of a rule isn't one of the [folders](Backend::folders), a
+    /// [NotFound](ErrorKind::NotFound) error is returned. If a file can't be read to check it
+    /// against a rule, an error variant will be returned.
+    pub fn plan_rules(&self) -> Result<Vec<ProposedMove>, Error> {
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| !self.folders.contains(&rule.destination))
+        {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "{} is not a folder to sort into",
+                    rule.destination.display()
+                ),
+            ));
+        }
+
+        let mut proposed_moves = Vec::new();
+        for index in self.unsorted_indexes() {
+            let file = &self.files[index];
+            for rule in &self.rules {
+                if rule.matches(file)? {
+                    proposed_moves.push(ProposedMove {
+                        index,
+                        file: file.clone(),
+                        destination: rule.destination.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+
+        Ok(proposed_moves)
+    }
+
+    /// Moves the files that the [rules](Backend::rules) match into their folders in one step,
+    /// which a single undo reverses, and returns what was done along with the outcome of each
+    /// move. Files that no rule matches are left to sort by hand after the ones that were moved.
+    ///
+    /// # Errors
+    ///
+    /// See [plan_rules](Backend::plan_rules) and [sort_selection](Backend::sort_selection).
+    pub fn apply_rules(&mut self) -> Result<Vec<(ProposedMove, MoveOutcome)>, Error> {
+        let proposed_moves = self.plan_rules()?;
+        let selection: Vec<usize> = proposed_moves
+            .iter()
+            .map(|proposed| proposed.index)
+            .collect();
+        let destinations: HashMap<PathBuf, PathBuf> = proposed_moves
+            .iter()
+            .map(|proposed| (proposed.file.clone(), proposed.destination.clone()))
+            .collect();
+        let outcomes = self.apply_to_selection(&selection, |backend, file| {
+            backend.perform_transfer(file, destinations[file].clone(), TransferMode::Move)
+        })?;
+
+        Ok(proposed_moves.into_iter().zip(outcomes).collect())
+    }
+
+    /// The indexes of the files that are left to sort.
+    fn unsorted_indexes(&self) -> Range<usize> {
+        match self.end_of_files {
+            true => self.file_count()..self.file_count(),
+            false => self.current_file_index..self.file_count(),
+        }
+    }
+
+    /// Brings the selected files forward to the current file and performs an action on each of
+    /// them, which is recorded as a single [batch](Batch). Files that the action leaves where
+    /// they are get skipped, so that the batch advances past all of them.
+    fn apply_to_selection<T>(
+        &mut self,
+        selection: &[usize],
+        mut perform: impl FnMut(&mut Self, &Path) -> Result<(T, Option<Box<dyn Controllable>>), Error>,
+    ) -> Result<Vec<T>, Error> {
+        if self.files.is_empty() {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+        let unsorted = self.unsorted_indexes();
+        let mut selection = selection.to_vec();
+        selection.sort_unstable();
+        selection.dedup();
+        if selection.iter().any(|index| !unsorted.contains(index)) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "only files that are left to sort can be selected",
+            ));
+        }
+        if selection.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let previous_files = self.files.clone();
+        let (mut selected, mut others) = (Vec::new(), Vec::new());
+        for (index, file) in unsorted.clone().zip(self.files.split_off(unsorted.start)) {
+            match selection.binary_search(&index) {
+                Ok(_) => selected.push(file),
+                Err(_) => others.push(file),
+            }
+        }
+        self.files.extend(selected.iter().cloned());
+        self.files.append(&mut others);
+
+        let mut results = Vec::new();
+        let mut parts: Vec<Box<dyn Controllable>> = Vec::new();
+        for file in selected {
+            let result = perform(self, &file);
+            match result {
+                Ok((result, action)) => {
+                    parts.push(action.unwrap_or_else(|| Box::new(Skip::new(file))));
+                    results.push(result);
+                }
+                Err(error) => {
+                    // the failure matters more than whether rolling back went cleanly
+                    for part in parts.iter_mut().rev() {
+                        let _ = part.undo();
+                    }
+                    self.files = previous_files;
+                    return Err(error);
+                }
+            }
+        }
+        if self.files != previous_files {
+            if let Err(error) = self.record(Entry::Files(self.files.clone())) {
+                for part in parts.iter_mut().rev() {
+                    let _ = part.undo();
+                }
+                self.files = previous_files;
+                return Err(error);
+            }
+        }
+
+        let file_count = parts.len();
+        let action = match file_count {
+            1 => parts.remove(0),
+            _ => Box::new(Batch::new(parts)),
+        };
+        self.push_action(action)?;
+        self.advance_past_batch(file_count)?;
+
+        Ok(results)
+    }
+
+    fn transfer_file(
+        &mut self,
+        to_folder: PathBuf,
+        transfer_mode: TransferMode,
+    ) -> Result<MoveOutcome, Error> {
+        let from_file = match self.current_file_to_sort()? {
+            Some(from_file) => from_file,
+            None => return Ok(MoveOutcome::Skipped),
+        };
+
+        let (outcome, action) = self.perform_transfer(&from_file, to_folder, transfer_mode)?;
+        match action {
+            Some(action) => {
+                self.push_action(action)?;
+                self.increment()?;
+            }
+            None if outcome == MoveOutcome::Skipped => self.skip()?,
+            None => {}
+        }
+
+        Ok(outcome)
+    }
+
+    fn current_file_to_sort(&self) -> Result<Option<PathBuf>, Error> {
+        if self.files.is_empty() {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(self.get_current_file().cloned())
+    }
+
+    /// Puts a file into a folder and returns the action that did it, without recording the
+    /// action or advancing. No action is returned if the file was left where it is.
+    fn perform_transfer(
+        &mut self,
+        from_file: &Path,
+        to_folder: PathBuf,
+        transfer_mode: TransferMode,
+    ) -> Result<(MoveOutcome, Option<Box<dyn Controllable>>), Error> {
+        if self.detect_duplicates {
+            if let Some(duplicate) = self.duplicate_index.find_duplicate(from_file, &to_folder)? {
+                return Ok((MoveOutcome::Duplicate(duplicate), None));
+            }
+        }
+        let mut destination = Self::build_destination(to_folder, from_file)?;
+        let mut replaced_file = None;
+        if self.filesystem_helper.exists(&destination) {
+            let helper = self.filesystem_helper.as_ref();
+            match self.collision_policy {
+                CollisionPolicy::Fail => {}
+                CollisionPolicy::Rename => {
+                    destination = collision::numbered_destination(&destination, helper)
+                }
+                CollisionPolicy::RenameWithHash => {
+                    destination = collision::hashed_destination(&destination, from_file, helper)?
+                }
+                CollisionPolicy::Skip => return Ok((MoveOutcome::Skipped, None)),
+                CollisionPolicy::Overwrite => {
+                    replaced_file = Some(self.filesystem_helper.trash_file(&destination)?)
+                }
+            }
+        }
+
+        let transferred = match transfer_mode {
+            TransferMode::Move => self.filesystem_helper.move_file(from_file, &destination),
+            TransferMode::Copy => self.filesystem_helper.copy_file(from_file, &destination),
+            TransferMode::Link(kind) => {
+                self.filesystem_helper
+                    .link_file(from_file, &destination, kind)
+            }
+        };
+        if let Err(error) = transferred {
+            if let Some(replaced_file) = &replaced_file {
+                self.filesystem_helper
+                    .restore_file(replaced_file, &destination)?;
+            }
+            return Err(error);
+        }
+        if self.detect_duplicates {
+            if let Some(folder) = destination.parent() {
+                self.duplicate_index.add_file(folder, &destination);
+            }
+        }
+        let from_file = from_file.to_path_buf();
+        let helper = Rc::clone(&self.filesystem_helper);
+        let action: Box<dyn Controllable> = match transfer_mode {
+            TransferMode::Move => {
+                let mut action = Move::new(from_file, destination.clone(), helper);
+                action.replaced_file_location = replaced_file;
+                Box::new(action)
+            }
+            TransferMode::Copy => {
+                let mut action = CopyFile::new(from_file, destination.clone(), helper);
+                action.replaced_file_location = replaced_file;
+                Box::new(action)
+            }
+            TransferMode::Link(kind) => {
+                let mut action = Link::new(from_file, destination.clone(), kind, helper);
+                action.replaced_file_location = replaced_file;
+                Box::new(action)
+            }
+        };
+
+        Ok((MoveOutcome::Moved(destination), Some(action)))
+    }
+
+    fn build_destination(mut to_folder: PathBuf, from_file: &Path) -> Result<PathBuf, Error> {
+        let file_name = match from_file.file_name() {
+            Some(some_file) => some_file,
+            None => {
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        };
+        to_folder.push(file_name);
+
+        Ok(to_folder)
+    }
+
+    fn increment(&mut self) -> Result<(), Error> {
+        self.is_end_of_files(self.current_file_index, self.file_count())?;
+        self.current_file_index += 1;
+
+        Ok(())
+    }
+
+    /// Increments past the files of an action.
+    fn advance(&mut self, file_count: usize) -> Result<(), Error> {
+        for _ in 0..file_count {
+            self.increment()?;
+        }
+
+        Ok(())
+    }
+
+    /// Increments past the files of an action that returns a result for each of them, which
+    /// matters more than reaching the end of the files. The end shows as there being no
+    /// [current file](Backend::get_current_file) instead.
+    fn advance_past_batch(&mut self, file_count: usize) -> Result<(), Error> {
+        match self.advance(file_count) {
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Skips the current file.
+    ///
+    /// A `control_flow` action that increments the index that points to the current file forward.
+    pub fn skip(&mut self) -> Result<(), Error> {
+        let file = self.get_current_file().cloned().unwrap_or_default();
+        self.increment()?;
+        if let Err(error) = self.push_action(Box::new(Skip::new(file))) {
+            self.current_file_index -= 1;
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    fn is_end_of_files(&mut self, file_index: usize, file_count: usize) -> Result<(), Error> {
+        match file_index + 1 >= file_count {
+            true => {
+                self.end_of_files = true;
+                Err(Error::from(ErrorKind::UnexpectedEof))
+            }
+            false => Ok(()),
+        }
+    }
+
+    /// Returns descriptions of the actions that can be undone, oldest first.
+    pub fn undo_history(&self) -> Vec<ActionDescription> {
+        self.undo_stack.iter().map(|item| item.describe()).collect()
+    }
+
+    /// Returns descriptions of the actions that can be redone, the next one to be redone last.
+    pub fn redo_history(&self) -> Vec<ActionDescription> {
+        self.redo_stack.iter().map(|item| item.describe()).collect()
+    }
+
+    /// Undoes the previous action.
+    ///
+    /// Undoes the previous `control_flow` action and pushes a redo action onto the `redo_stack`.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors while undoing, an error variant will be
+    /// returned.
+    pub fn undo(&mut self) -> Result<(), Error> {
+        match self.undo_stack.pop() {
+            Some(mut item) => {
+                let result = item.undo();
+                self.step_back(item.file_count());
+                let description = item.describe();
+                self.redo_stack.push(item);
+                self.record(Entry::Undo)?;
+                if result.is_ok() {
+                    self.follow_action(description, true)?;
+                }
+
+                result
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Steps back over the files of an undone action.
+    fn step_back(&mut self, file_count: usize) {
+        for _ in 0..file_count {
+            if self.end_of_files {
+                self.end_of_files = false;
+            } else {
+                self.current_file_index -= 1;
+            }
+        }
+    }
+
+    /// Keeps the folders and files up to date after an action was undone or redone: a
+    /// [created](Backend::create_folder) folder is only listed while it exists, and a
+    /// [renamed](Backend::rename_current) file is listed under the name it has now.
+    fn follow_action(&mut self, description: ActionDescription, undone: bool) -> Result<(), Error> {
+        match description.kind {
+            ActionKind::CreateFolder => {
+                let folder = description.source;
+                match (folder.is_dir(), self.folders.contains(&folder)) {
+                    (true, false) => self.folders.push(folder),
+                    (false, true) => self.folders.retain(|listed| *listed != folder),
+                    _ => return Ok(()),
+                }
+
+                self.record(Entry::Folders(self.folders.clone()))
+            }
+            ActionKind::Rename => {
+                let (from_file, to_file) = match (undone, description.destination) {
+                    (true, Some(destination)) => (destination, description.source),
+                    (false, Some(destination)) => (description.source, destination),
+                    (_, None) => return Ok(()),
+                };
+                for file in self.files.iter_mut().filter(|file| **file == from_file) {
+                    *file = to_file.clone();
+                }
+
+                self.record(Entry::Files(self.files.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Redoes the action most recently undone.
+    ///
+    /// Redoes the last `control_flow` action on the `redo_stack` and pushes an undo action
+    /// onto the `undo_stack`. The `redo_stack` gets cleared when any action that isn't a
+    /// redo or an undo is performed.
+    ///
+    /// # Errors
+    ///
+    /// If there are any I/O errors redoing, an error variant will be
+    /// returned.
+    pub fn redo(&mut self) -> Result<(), Error> {
+        match self.redo_stack.pop() {
+            Some(mut item) => {
+                let result = item.redo();
+                let description = item.describe();
+                let entry = Entry::Redo(description.clone());
+                let file_count = item.file_count();
+                self.undo_stack.push(item);
+                let recorded = self.record(entry);
+                self.advance(file_count)?;
+                recorded?;
+                if result.is_ok() {
+                    self.follow_action(description, false)?;
+                }
+                result
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control_flow::Move;
+    use crate::filesystem::FilesystemIO;
+    use crate::trash::TRASH_FOLDER_NAME;
+    use crate::{
+        ActionKind, Backend, CollisionPolicy, Condition, FileFilter, FileFormat, FolderTemplate,
+        Glob, LinkKind, MoveOutcome, NameTemplate, Recursion, Rule, SortKey, SortOrder,
+        TransferMode, TrashMode,
+    };
+    use std::cell::RefCell;
+    use std::fs::{self, File};
+    use std::io::{Error, ErrorKind};
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+    use tempdir::TempDir;
+
+    struct FilesystemMock {
+        folders: Vec<PathBuf>,
+        files: Vec<PathBuf>,
+        purged_files: RefCell<Vec<PathBuf>>,
+        trash_mode: RefCell<TrashMode>,
+    }
+
+    impl FilesystemMock {
+        fn new() -> FilesystemMock {
+            FilesystemMock {
+                folders: Vec::new(),
+                files: Vec::new(),
+                purged_files: RefCell::new(Vec::new()),
+                trash_mode: RefCell::new(TrashMode::Staging),
+            }
+        }
+    }
+
+    impl FilesystemIO for FilesystemMock {
+        fn load_filesystem_elements(
+            &self,
+            _directory: &Path,
+        ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
+            Ok((self.folders.clone(), self.files.clone()))
+        }
+        fn load_files_recursively(
+            &self,
+            _directory: &Path,
+            _max_depth: usize,
+            excluded_folders: &[PathBuf],
+        ) -> Result<Vec<PathBuf>, Error> {
+            let mut files = self.files.clone();
+            files.retain(|file| {
+                !excluded_folders
+                    .iter()
+                    .any(|folder| file.starts_with(folder))
+            });
+            Ok(files)
+        }
+        fn delete_file(&self, _file: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn move_file(&self, _from_file: &Path, _to_file: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn rename_file(&self, _from_file: &Path, _to_file: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn exists(&self, path: &Path) -> bool {
+            self.files
+                .iter()
+                .chain(&self.folders)
+                .any(|file| file == path)
+        }
+        fn copy_file(&self, _from_file: &Path, _to_file: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn link_file(
+            &self,
+            _from_file: &Path,
+            _to_file: &Path,
+            _kind: LinkKind,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+        fn add_folder(&self, _folder: &str) -> Result<PathBuf, Error> {
+            match self.folders.len() == 1 {
+                true => Ok(self.folders[0].clone()),
+                false => Err(Error::from(ErrorKind::NotFound)),
+            }
+        }
+        fn create_folder(&self, folder: &Path) -> Result<(), Error> {
+            match self.exists(folder) {
+                true => Err(Error::from(ErrorKind::AlreadyExists)),
+                false => Ok(()),
+            }
+        }
+        fn remove_empty_folder(&self, _folder: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn trash_file(&self, file: &Path) -> Result<PathBuf, Error> {
+            Ok(PathBuf::from("./trash").join(file.file_name().unwrap()))
+        }
+        fn restore_file(&self, _trashed_file: &Path, _original_file: &Path) -> Result<(), Error> {
+            Ok(())
+        }
+        fn purge_file(&self, trashed_file: &Path) -> Result<(), Error> {
+            self.purged_files
+                .borrow_mut()
+                .push(trashed_file.to_path_buf());
+            Ok(())
+        }
+        fn set_trash_mode(&self, trash_mode: TrashMode) {
+            *self.trash_mode.borrow_mut() = trash_mode;
+        }
+    }
+
+    fn build_folders() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("./folder1"),
+            PathBuf::from("./folder2"),
+            PathBuf::from("./folder3"),
+        ]
+    }
+
+    fn build_files() -> Vec<PathBuf> {
+        vec![
+            PathBuf::from("./file1.png"),
+            PathBuf::from("./file2.png"),
+            PathBuf::from("./file3.png"),
+        ]
+    }
+
+    #[test]
+    fn ensure_files_and_folders_are_populated_when_loading_all() {
+        let mut test_backend = Backend::new();
+        let mut filesystem_mock = FilesystemMock::new();
+        let expected_folders = build_folders();
+        let expected_files = build_files();
+        filesystem_mock.folders = expected_folders.clone();
+        filesystem_mock.files = expected_files.clone();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+
+        test_backend
+            .load_folders_and_files("./testFolder".to_owned())
+            .unwrap();
+
+        let actual_folders = test_backend.folders;
+        let actual_files = test_backend.files;
+        assert_vectors(&actual_folders, &expected_folders);
+        assert_vectors(&actual_files, &expected_files);
+    }
+
+    #[test]
+    fn ensure_excluded_files_are_counted_when_loading_all() {
+        let mut test_backend = Backend::new();
+        let mut filesystem_mock = FilesystemMock::new();
+        filesystem_mock.files = build_files();
+        filesystem_mock.files.push(PathBuf::from("./Thumbs.db"));
+        filesystem_mock.files.push(PathBuf::from("./notes.txt"));
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+        test_backend.file_filter = FileFilter {
+            allowed_extensions: vec!["png".to_owned()],
+            ..FileFilter::default()
+        };
+
+        let excluded_count = test_backend
+            .load_folders_and_files("./testFolder".to_owned())
+            .unwrap();
+
+        assert_eq!(excluded_count, 2);
+        assert_vectors(&test_backend.files, &build_files());
+    }
+
+    #[test]
+    fn ensure_sorted_files_are_skipped_when_loading_recursively() {
+        let mut test_backend = Backend::new();
+        let mut filesystem_mock = FilesystemMock::new();
+        filesystem_mock.folders = build_folders();
+        filesystem_mock.files = build_files();
+        filesystem_mock
+            .files
+            .push(PathBuf::from("./folder1/sorted.png"));
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+        test_backend.recursion = Recursion {
+            max_depth: 2,
+            exclude_folders: true,
+        };
+
+        test_backend
+            .load_folders_and_files("./testFolder".to_owned())
+            .unwrap();
+
+        assert_vectors(&test_backend.files, &build_files());
+        assert_vectors(&test_backend.folders, &build_folders());
+    }
+
+    #[test]
+    fn ensure_only_unsorted_files_are_reordered_when_sorting() {
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(FilesystemMock::new());
+        test_backend.files = ["./img10.png", "./img2.png", "./img30.png", "./img4.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        test_backend.skip().unwrap();
+
+        test_backend
+            .sort_files(SortOrder::ascending(SortKey::NaturalName))
+            .unwrap();
+
+        let expected_files: Vec<PathBuf> =
+            ["./img10.png", "./img2.png", "./img4.png", "./img30.png"]
+                .iter()
+                .map(PathBuf::from)
+                .collect();
+        assert_vectors(&test_backend.files, &expected_files);
+        assert_eq!(test_backend.get_current_file(), Some(&expected_files[1]));
+        test_backend.undo().unwrap();
+        assert_eq!(test_backend.get_current_file(), Some(&expected_files[0]));
+    }
+
+    #[test]
+    fn ensure_mismatched_extensions_are_reported() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let renamed = dir.path().join("IMG_0001.jpg");
+        let correct = dir.path().join("IMG_0002.png");
+        fs::write(&renamed, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(&correct, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let mut test_backend = Backend::new();
+        test_backend.files = vec![renamed.clone(), correct];
+
+        assert_eq!(test_backend.file_format(0).unwrap(), FileFormat::Png);
+        assert_eq!(
+            test_backend.extension_mismatches(),
+            vec![(renamed, FileFormat::Png)]
+        );
+        assert!(test_backend.file_format(2).is_err());
+    }
+
+    #[test]
+    fn ensure_metadata_is_cached_until_the_file_changes() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("scan.tif");
+        fs::write(&file, crate::exif::tests::build_tiff("2021:07:04 18:30:00")).unwrap();
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        let mut test_backend = Backend::new();
+        test_backend.files = vec![file.clone()];
+
+        let first = test_backend.metadata(0).unwrap();
+        fs::write(&file, crate::exif::tests::build_tiff("1999:12:31 23:59:59")).unwrap();
+        let handle = fs::File::options().write(true).open(&file).unwrap();
+        handle.set_modified(modified).unwrap();
+        let cached = test_backend.metadata(0).unwrap();
+        handle
+            .set_modified(modified + std::time::Duration::from_secs(60))
+            .unwrap();
+        let changed = test_backend.metadata(0).unwrap();
+
+        assert_eq!(first.capture_date.as_deref(), Some("2021:07:04 18:30:00"));
+        assert_eq!(cached, first);
+        assert_eq!(changed.capture_date.as_deref(), Some("1999:12:31 23:59:59"));
+        assert_eq!(
+            test_backend.metadata(1).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn ensure_folders_are_populated_when_loading_external_folders() {
+        let mut test_backend = Backend::new();
+        let mut filesystem_mock = FilesystemMock::new();
+        let expected_folders = build_folders();
+        filesystem_mock.folders = expected_folders.clone();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+
+        test_backend
+            .load_external_folders("./testFolder".to_owned())
+            .unwrap();
+
+        let actual_folders = test_backend.folders;
+        assert_vectors(&actual_folders, &expected_folders);
+    }
+
+    #[test]
+    fn ensure_file_is_moved_when_move_file_is_called() {
+        let filesystem_mock = FilesystemMock::new();
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+        test_backend.files = expected_files.clone();
+        assert_eq!(test_backend.undo_stack.len(), 0);
+
+        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
+
+        assert_eq!(test_backend.undo_stack.len(), 1);
+        assert_eq!(test_backend.current_file_index, 1);
+    }
+
+    #[test]
+    fn ensure_error_is_thrown_when_no_files_loaded_when_moving() {
+        let mut test_backend = Backend::new();
+
+        assert!(test_backend.move_file(PathBuf::from("./toFolder")).is_err());
+    }
+
+    #[test]
+    fn ensure_file_is_added_when_adding_a_folder() {
+        let mut test_backend = Backend::new();
+        let mut filesystem_mock = FilesystemMock::new();
+        let expected_folders = vec![PathBuf::from("./folder1")];
+        filesystem_mock.folders = expected_folders.clone();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+
+        test_backend.add_folder("./testFolder".to_owned()).unwrap();
+
+        let actual_folders = test_backend.folders;
+        assert_vectors(&actual_folders, &expected_folders);
+    }
+
+    #[test]
+    fn ensure_folders_are_cleared_when_clearing_folders() {
+        let original_folders = build_folders();
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.folders = original_folders.clone();
+        test_backend.files = expected_files.clone();
+
+        test_backend.clear_folders().expect("clearing failed");
+
+        let actual_folders = test_backend.folders;
+        let actual_files = test_backend.files;
+        assert_vectors(&actual_folders, &Vec::new());
+        assert_vectors(&actual_files, &expected_files);
+    }
+
+    #[test]
+    fn ensure_file_is_removed_when_deleting_file() {
+        let expected_folders = build_folders();
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(FilesystemMock::new());
+        test_backend.folders = expected_folders.clone();
+        test_backend.files = expected_files.clone();
+        assert_eq!(test_backend.undo_stack.len(), 0);
+
+        test_backend.delete_file().expect("delete failed!");
+
+        let actual_folders = &test_backend.folders;
+        let actual_files = &test_backend.files;
+        assert_vectors(actual_folders, &expected_folders);
+        assert_vectors(actual_files, &expected_files);
+        assert_eq!(test_backend.undo_stack.len(), 1);
+        assert_eq!(test_backend.current_file_index, 1);
+    }
+
+    #[test]
+    fn ensure_deleted_file_is_restored_when_undoing_delete() {
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(FilesystemMock::new());
+        test_backend.files = build_files();
+        test_backend.delete_file().expect("delete failed!");
+
+        test_backend.undo().expect("undo failed");
+
+        assert_eq!(test_backend.current_file_index, 0);
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.redo_stack.len(), 1);
+    }
+
+    #[test]
+    fn ensure_trash_is_purged_and_stacks_cleared_when_purging_trash() {
+        let filesystem_mock = Rc::new(FilesystemMock::new());
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = filesystem_mock.clone();
+        test_backend.files = build_files();
+        test_backend.delete_file().expect("delete failed!");
+        test_backend.skip().expect("skip failed!");
+
+        test_backend.purge_trash().expect("purge failed!");
+
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(
+            *filesystem_mock.purged_files.borrow(),
+            vec![PathBuf::from("./trash/file1.png")]
+        );
+    }
+
+    #[test]
+    fn ensure_trash_mode_is_set_on_the_filesystem_helper() {
+        let filesystem_mock = Rc::new(FilesystemMock::new());
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = filesystem_mock.clone();
+        let trash_mode = TrashMode::FreeDesktop {
+            data_home: PathBuf::from("./data_home"),
+        };
+
+        test_backend.set_trash_mode(trash_mode.clone()).unwrap();
+        test_backend.files = build_files();
+        test_backend.delete_file().unwrap();
+
+        assert_eq!(*filesystem_mock.trash_mode.borrow(), trash_mode);
+        assert_eq!(
+            test_backend.undo_stack[0].describe().destination,
+            Some(PathBuf::from("./trash/file1.png"))
+        );
+    }
+
+    #[test]
+    fn ensure_undone_delete_is_not_purged_when_loading() {
+        let filesystem_mock = Rc::new(FilesystemMock::new());
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = filesystem_mock.clone();
+        test_backend.files = build_files();
+        test_backend.delete_file().expect("delete failed!");
+        test_backend.delete_file().expect("delete failed!");
+        test_backend.undo().expect("undo failed");
+
+        test_backend
+            .load_folders_and_files("./testFolder".to_owned())
+            .unwrap();
+
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(
+            *filesystem_mock.purged_files.borrow(),
+            vec![PathBuf::from("./trash/file1.png")]
+        );
+    }
+
+    #[test]
+    fn ensure_redo_stack_is_cleared_when_new_action_is_performed() {
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(FilesystemMock::new());
+        test_backend.files = build_files();
+        test_backend.skip().expect("skip failed!");
+        test_backend.undo().expect("undo failed");
+        assert_eq!(test_backend.redo_stack.len(), 1);
+
+        test_backend.delete_file().expect("delete failed!");
+
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(test_backend.undo_stack.len(), 1);
+    }
+
+    #[test]
+    fn ensure_pointer_is_moved_forward_when_file_is_skipped() {
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.files = expected_files.clone();
+        let expected_index = 1;
+        assert_eq!(
+            test_backend.get_current_file().unwrap(),
+            &expected_files[expected_index - 1]
+        );
+        assert_eq!(test_backend.undo_stack.len(), 0);
+
+        test_backend.skip().expect("Skipping failed");
+
+        assert_eq!(test_backend.current_file_index, expected_index);
+        assert_eq!(
+            test_backend.get_current_file().unwrap(),
+            &expected_files[expected_index]
+        );
+        assert_eq!(test_backend.undo_stack.len(), 1);
+    }
+
+    #[test]
+    fn ensure_pointer_is_moved_forward_and_unchanged_undo_stack_when_incrementing() {
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.files = expected_files.clone();
+        let expected_index = 1;
+        assert_eq!(
+            test_backend.get_current_file().unwrap(),
+            &expected_files[expected_index - 1]
+        );
+        assert_eq!(test_backend.undo_stack.len(), 0);
+
+        test_backend.increment().expect("Skipping failed");
+
+        assert_eq!(test_backend.current_file_index, expected_index);
+        assert_eq!(
+            test_backend.get_current_file().unwrap(),
+            &expected_files[expected_index]
+        );
+        assert_eq!(test_backend.undo_stack.len(), 0);
+    }
+
+    #[test]
+    fn ensure_error_is_thrown_when_index_out_of_bounds_when_skipping() {
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.files = expected_files.clone();
+        test_backend.current_file_index = 2;
+        let expected_index = 3;
+        assert_eq!(
+            test_backend.get_current_file().unwrap(),
+            &expected_files[expected_index - 1]
+        );
+
+        assert!(test_backend.increment().is_err());
+    }
+
+    #[test]
+    fn ensure_undo_stack_is_popped_and_redo_stack_is_pushed_when_undoing() {
+        let mut test_backend = Backend::new();
+        let filesystem_mock = FilesystemMock::new();
+        let undo_element = Move::new(
+            PathBuf::from("a"),
+            PathBuf::from("b"),
+            Rc::new(filesystem_mock),
+        );
+        test_backend.undo_stack.push(Box::new(undo_element));
+        test_backend.current_file_index = 2;
+
+        test_backend.undo().expect("undo failed");
+
+        assert_eq!(test_backend.redo_stack.len(), 1);
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.current_file_index, 1);
+    }
+
+    #[test]
+    fn ensure_nothing_happens_when_undo_stack_is_empty() {
+        let mut test_backend = Backend::new();
+        test_backend.current_file_index = 0;
+
+        test_backend.undo().expect("undo failed");
+
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.current_file_index, 0);
+    }
+
+    #[test]
+    fn ensure_final_image_is_undone_when_end_of_files_hit_and_undo() {
+        let filesystem_mock = FilesystemMock::new();
+        let expected_files = build_files();
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+        test_backend.files = expected_files.clone();
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        dbg!(&test_backend.files);
+        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
+        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
+        let _ = test_backend.move_file(PathBuf::from("./toFolder")); // ignore result since EoF
+
+        let _ = test_backend.undo(); // ignore result since it tries on real filesystem
+
+        assert_eq!(test_backend.current_file_index, 2);
+    }
+
+    #[test]
+    fn ensure_redo_stack_is_popped_and_undo_stack_is_pushed_when_redoing() {
+        let filesystem_mock = FilesystemMock::new();
+        let expected_files = build_files();
+        let redo_element = Move::new(
+            PathBuf::from("a"),
+            PathBuf::from("b"),
+            Rc::new(filesystem_mock),
+        );
+        let mut test_backend = Backend::new();
+        test_backend.redo_stack.push(Box::new(redo_element));
+        test_backend.current_file_index = 0;
+        test_backend.files = expected_files;
+
+        test_backend.redo().expect("redo failed");
+
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(test_backend.undo_stack.len(), 1);
+        assert_eq!(test_backend.current_file_index, 1);
+    }
+
+    #[test]
+    fn ensure_nothing_happens_when_redo_stack_is_empty() {
+        let mut test_backend = Backend::new();
+        test_backend.current_file_index = 0;
+
+        test_backend.redo().expect("redo failed");
+
+        assert_eq!(test_backend.redo_stack.len(), 0);
+        assert_eq!(test_backend.undo_stack.len(), 0);
+        assert_eq!(test_backend.current_file_index, 0);
+    }
+
+    #[test]
+    fn ensure_none_is_returned_when_current_file_index_is_out_of_bounds() {
+        let mut test_backend = Backend::new();
+        test_backend.current_file_index = 10;
+
+        assert!(test_backend.get_current_file().is_none());
+    }
+
+    #[test]
+    fn ensure_session_is_rebuilt_when_resuming() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
+        let source = dir.path().join("source");
+        let folder = source.join("folder1");
+        fs::create_dir_all(&folder).unwrap();
+        for file in ["file1.png", "file2.png", "file3.png", "file4.png"] {
+            File::create(source.join(file)).unwrap();
+        }
+        let mut test_backend = Backend::new();
+        test_backend
+            .load_folders_and_files(source.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.start_session(journal_path.clone()).unwrap();
+        test_backend.move_file(folder.clone()).unwrap();
+        test_backend.delete_file().unwrap();
+        test_backend.skip().unwrap();
+        test_backend.undo().unwrap();
+        test_backend.undo().unwrap();
+        test_backend.redo().unwrap();
+
+        let mut resumed_backend = Backend::new();
+        resumed_backend.resume_session(journal_path).unwrap();
+
+        assert_eq!(resumed_backend.pwd, test_backend.pwd);
+        assert_eq!(resumed_backend.files, test_backend.files);
+        assert_eq!(resumed_backend.folders, test_backend.folders);
+        assert_eq!(resumed_backend.current_file_index, 2);
+        assert_eq!(resumed_backend.undo_stack.len(), 2);
+        assert_eq!(resumed_backend.redo_stack.len(), 1);
+
+        resumed_backend.undo().unwrap();
+        resumed_backend.undo().unwrap();
+        assert!(source.join("file2.png").exists());
+        assert!(source.join("file1.png").exists());
+        assert!(!folder.join("file1.png").exists());
+        assert_eq!(resumed_backend.current_file_index, 0);
+    }
+
+    #[test]
+    fn ensure_batch_is_rebuilt_when_resuming() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
+        let source = dir.path().join("source");
+        let folder = source.join("folder1");
+        fs::create_dir_all(&folder).unwrap();
+        for file in ["file1.png", "file2.png", "file3.png"] {
+            File::create(source.join(file)).unwrap();
+        }
+        let mut test_backend = Backend::new();
+        test_backend
+            .load_folders_and_files(source.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.start_session(journal_path.clone()).unwrap();
+        test_backend.sort_next(2, folder.clone()).unwrap();
+
+        let mut resumed_backend = Backend::new();
+        resumed_backend.resume_session(journal_path).unwrap();
+        assert_eq!(resumed_backend.current_file_index, 2);
+
+        resumed_backend.undo().unwrap();
+        assert_eq!(resumed_backend.current_file_index, 0);
+        assert!(source.join("file1.png").exists());
+        assert!(source.join("file2.png").exists());
+    }
+
+    fn build_sources(dir: &Path) -> (PathBuf, PathBuf) {
+        let camera = dir.join("camera");
+        let phone = dir.join("phone");
+        fs::create_dir_all(&camera).unwrap();
+        fs::create_dir_all(&phone).unwrap();
+        for file in [
+            camera.join("file1.png"),
+            camera.join("file2.png"),
+            phone.join("file3.png"),
+            phone.join("file4.png"),
+        ] {
+            File::create(file).unwrap();
+        }
+
+        (camera, phone)
+    }
+
+    #[test]
+    fn ensure_sources_are_added_and_removed_without_losing_position() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (camera, phone) = build_sources(dir.path());
+        let mut test_backend = Backend::new();
+        test_backend
+            .load_folders_and_files(camera.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.skip().unwrap();
+
+        test_backend
+            .add_source(phone.to_string_lossy().into_owned())
+            .unwrap();
+        assert_eq!(test_backend.file_count(), 4);
+        assert_eq!(test_backend.current_file_index, 1);
+        assert_eq!(
+            test_backend.source_of(&test_backend.files[2]),
+            Some(phone.as_path())
+        );
+
+        test_backend
+            .remove_source(camera.to_string_lossy().into_owned())
+            .unwrap();
+        assert_vectors(
+            &test_backend.files,
+            &[
+                camera.join("file1.png"),
+                phone.join("file3.png"),
+                phone.join("file4.png"),
+            ],
+        );
+        assert_eq!(
+            test_backend.get_current_file(),
+            Some(&phone.join("file3.png"))
+        );
+        assert_eq!(test_backend.sources, vec![phone.clone()]);
+
+        test_backend.undo().unwrap();
+        assert_eq!(
+            test_backend.get_current_file(),
+            Some(&camera.join("file1.png"))
+        );
+    }
+
+    #[test]
+    fn ensure_unknown_or_repeated_sources_are_rejected() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (camera, phone) = build_sources(dir.path());
+        let mut test_backend = Backend::new();
+        test_backend
+            .load_folders_and_files(camera.to_string_lossy().into_owned())
+            .unwrap();
+
+        let repeated = test_backend.add_source(camera.to_string_lossy().into_owned());
+        let unknown = test_backend.remove_source(phone.to_string_lossy().into_owned());
+
+        assert_eq!(repeated.unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(unknown.unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn ensure_sources_are_rebuilt_when_resuming() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
+        let (camera, phone) = build_sources(dir.path());
+        let mut test_backend = Backend::new();
+        test_backend
+            .load_folders_and_files(camera.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.start_session(journal_path.clone()).unwrap();
+        test_backend.skip().unwrap();
+        test_backend
+            .add_source(phone.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend
+            .remove_source(camera.to_string_lossy().into_owned())
+            .unwrap();
+
+        let mut resumed_backend = Backend::new();
+        resumed_backend.resume_session(journal_path).unwrap();
+
+        assert_eq!(resumed_backend.sources, test_backend.sources);
+        assert_eq!(resumed_backend.files, test_backend.files);
+        assert_eq!(resumed_backend.current_file_index, 1);
+    }
+
+    /// Loads a directory with a file whose name is already taken in the folder to sort into.
+    fn build_collision(dir: &Path, policy: CollisionPolicy) -> (Backend, PathBuf) {
+        let folder = dir.join("folder1");
+        fs::create_dir(&folder).unwrap();
+        fs::write(dir.join("file1.png"), "new").unwrap();
+        fs::write(dir.join("file2.png"), "other").unwrap();
+        fs::write(folder.join("file1.png"), "old").unwrap();
+        let mut test_backend = Backend::new();
+        test_backend.collision_policy = policy;
+        test_backend
+            .load_folders_and_files(dir.to_string_lossy().into_owned())
+            .unwrap();
+
+        (test_backend, folder)
+    }
+
+    #[test]
+    fn ensure_taken_names_fail_by_default() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Fail);
+
+        let actual_error = test_backend.move_file(folder).unwrap_err();
+
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(test_backend.current_file_index, 0);
+    }
+
+    #[test]
+    fn ensure_taken_names_are_asked_of_the_filesystem_helper() {
+        let mut filesystem_mock = FilesystemMock::new();
+        filesystem_mock.files = vec![
+            PathBuf::from("./folder1/file1.png"),
+            PathBuf::from("./folder1/file1 (1).png"),
+        ];
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
+        test_backend.collision_policy = CollisionPolicy::Rename;
+        test_backend.files = build_files();
+
+        let outcome = test_backend.move_file(PathBuf::from("./folder1")).unwrap();
+
+        assert_eq!(
+            outcome,
+            MoveOutcome::Moved(PathBuf::from("./folder1/file1 (2).png"))
+        );
+    }
+
+    #[test]
+    fn ensure_moved_file_is_renamed_and_undone_when_name_is_taken() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Rename);
+
+        let outcome = test_backend.move_file(folder.clone()).unwrap();
+        assert_eq!(outcome, MoveOutcome::Moved(folder.join("file1 (1).png")));
+        assert_eq!(
+            fs::read_to_string(folder.join("file1 (1).png")).unwrap(),
+            "new"
+        );
+
+        test_backend.undo().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("file1.png")).unwrap(),
+            "new"
+        );
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "old");
+        assert!(!folder.join("file1 (1).png").exists());
+    }
+
+    #[test]
+    fn ensure_file_is_skipped_when_name_is_taken() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Skip);
+
+        let outcome = test_backend.move_file(folder.clone()).unwrap();
+
+        assert_eq!(outcome, MoveOutcome::Skipped);
+        assert_eq!(test_backend.current_file_index, 1);
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Skip);
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "old");
+    }
+
+    #[test]
+    fn ensure_overwritten_file_is_trashed_and_restored_by_undo() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Overwrite);
+
+        test_backend.move_file(folder.clone()).unwrap();
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "new");
+        assert!(test_backend.undo_history()[0].replaced.is_some());
+
+        test_backend.undo().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("file1.png")).unwrap(),
+            "new"
+        );
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "old");
+
+        test_backend.redo().unwrap();
+        test_backend.purge_trash().unwrap();
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "new");
+        assert!(!folder.join(TRASH_FOLDER_NAME).exists());
     }
 
-    fn build_files() -> Vec<PathBuf> {
-        vec![
-            PathBuf::from("./file1.png"),
-            PathBuf::from("./file2.png"),
-            PathBuf::from("./file3.png"),
-        ]
+    #[test]
+    fn ensure_copy_mode_copies_and_undo_removes_the_copy() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Rename);
+        test_backend.transfer_mode = TransferMode::Copy;
+
+        let outcome = test_backend.sort_file(folder.clone()).unwrap();
+        assert_eq!(outcome, MoveOutcome::Moved(folder.join("file1 (1).png")));
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Copy);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("file1.png")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(folder.join("file1 (1).png")).unwrap(),
+            "new"
+        );
+
+        test_backend.undo().unwrap();
+        assert!(!folder.join("file1 (1).png").exists());
+        assert!(dir.path().join("file1.png").exists());
+        assert_eq!(test_backend.current_file_index, 0);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn ensure_files_and_folders_are_populated_when_loading_all() {
-        let mut test_backend = Backend::new();
-        let mut filesystem_mock = FilesystemMock::new();
-        let expected_folders = build_folders();
-        let expected_files = build_files();
-        filesystem_mock.folders = expected_folders.clone();
-        filesystem_mock.files = expected_files.clone();
-        test_backend.filesystem_helper = Box::new(filesystem_mock);
+    fn ensure_file_is_linked_into_several_folders() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Fail);
+        let other_folder = dir.path().join("folder2");
+        fs::create_dir(&other_folder).unwrap();
+        let file = dir.path().join("file1.png");
 
         test_backend
-            .load_folders_and_files("./testFolder".to_owned())
+            .link_file(other_folder.clone(), LinkKind::Hard)
+            .unwrap();
+        test_backend.undo().unwrap();
+        test_backend
+            .link_file(other_folder.clone(), LinkKind::Symbolic)
             .unwrap();
+        test_backend.undo().unwrap();
+        test_backend.transfer_mode = TransferMode::Link(LinkKind::RelativeSymbolic);
+        test_backend.sort_file(other_folder.clone()).unwrap();
 
-        let actual_folders = test_backend.folders;
-        let actual_files = test_backend.files;
-        assert_vectors(&actual_folders, &expected_folders);
-        assert_vectors(&actual_files, &expected_files);
+        assert_eq!(
+            test_backend.undo_history()[0].kind,
+            ActionKind::Link(LinkKind::RelativeSymbolic)
+        );
+        assert_eq!(
+            fs::read_link(other_folder.join("file1.png")).unwrap(),
+            PathBuf::from("../file1.png")
+        );
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert_eq!(fs::read_to_string(folder.join("file1.png")).unwrap(), "old");
     }
 
     #[test]
-    fn ensure_folders_are_populated_when_loading_external_folders() {
-        let mut test_backend = Backend::new();
-        let mut filesystem_mock = FilesystemMock::new();
-        let expected_folders = build_folders();
-        filesystem_mock.folders = expected_folders.clone();
-        test_backend.filesystem_helper = Box::new(filesystem_mock);
+    fn ensure_file_is_sent_to_several_folders_and_undone_at_once() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Rename);
+        let family = dir.path().join("Family");
+        let year = dir.path().join("2024");
+        fs::create_dir(&family).unwrap();
+        fs::create_dir(&year).unwrap();
+
+        let outcomes = test_backend
+            .sort_into(&[
+                (family.clone(), TransferMode::Copy),
+                (year.clone(), TransferMode::Move),
+                (folder.clone(), TransferMode::Copy),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            outcomes,
+            vec![
+                MoveOutcome::Moved(family.join("file1.png")),
+                MoveOutcome::Moved(year.join("file1.png")),
+                MoveOutcome::Moved(folder.join("file1 (1).png")),
+            ]
+        );
+        assert!(!dir.path().join("file1.png").exists());
+        assert_eq!(fs::read_to_string(family.join("file1.png")).unwrap(), "new");
+        assert_eq!(test_backend.current_file_index, 1);
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Compound);
+        assert_eq!(test_backend.undo_history()[0].parts.len(), 3);
+
+        test_backend.undo().unwrap();
+        assert_eq!(test_backend.current_file_index, 0);
+        assert!(dir.path().join("file1.png").exists());
+        assert!(!family.join("file1.png").exists());
+        assert!(!year.join("file1.png").exists());
+        assert!(!folder.join("file1 (1).png").exists());
+
+        test_backend.skip().unwrap();
+        let outcomes = test_backend
+            .sort_into(&[
+                (family.clone(), TransferMode::Copy),
+                (year.clone(), TransferMode::Move),
+            ])
+            .unwrap();
+        assert_eq!(
+            outcomes,
+            vec![
+                MoveOutcome::Moved(family.join("file2.png")),
+                MoveOutcome::Moved(year.join("file2.png")),
+            ]
+        );
+        assert_eq!(test_backend.get_current_file(), None);
+    }
 
+    #[test]
+    fn ensure_dated_folders_are_created_and_removed_by_undo() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let scan = dir.path().join("scan.tif");
+        let notes = dir.path().join("notes.txt");
+        fs::write(&scan, crate::exif::tests::build_tiff("2021:07:04 18:30:00")).unwrap();
+        let handle = File::create(&notes).unwrap();
+        handle
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(951_782_400))
+            .unwrap();
+        // a file left to sort, so that the end of the files isn't reached
+        File::create(dir.path().join("unsorted.txt")).unwrap();
+        let mut test_backend = Backend::new();
+        test_backend.sort_order = Some(SortOrder::ascending(SortKey::NaturalName));
         test_backend
-            .load_external_folders("./testFolder".to_owned())
+            .load_folders_and_files(dir.path().to_string_lossy().into_owned())
             .unwrap();
+        let template = FolderTemplate::new("{root}/{exif.year}/{exif.month}").unwrap();
 
-        let actual_folders = test_backend.folders;
-        assert_vectors(&actual_folders, &expected_folders);
+        test_backend.move_file_by_template(&template).unwrap();
+        let outcome = test_backend.move_file_by_template(&template).unwrap();
+        assert_eq!(
+            outcome,
+            MoveOutcome::Moved(dir.path().join("2021/07/scan.tif"))
+        );
+        assert!(dir.path().join("2000/02/notes.txt").exists());
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Compound);
+
+        File::create(dir.path().join("2000/02/other.txt")).unwrap();
+        test_backend.undo().unwrap();
+        test_backend.undo().unwrap();
+        assert!(scan.exists() && notes.exists());
+        assert!(!dir.path().join("2021").exists());
+        assert!(dir.path().join("2000/02/other.txt").exists());
+
+        test_backend.redo().unwrap();
+        test_backend.redo().unwrap();
+        assert!(dir.path().join("2021/07/scan.tif").exists());
+        assert!(dir.path().join("2000/02/notes.txt").exists());
     }
 
     #[test]
-    fn ensure_file_is_moved_when_move_file_is_called() {
-        let filesystem_mock = FilesystemMock::new();
-        let expected_files = build_files();
+    fn ensure_created_folder_is_listed_and_removed_by_undo_while_empty() {
+        let dir = TempDir::new("unit_test").unwrap();
+        File::create(dir.path().join("file1.png")).unwrap();
+        File::create(dir.path().join("file2.png")).unwrap();
         let mut test_backend = Backend::new();
-        test_backend.filesystem_helper = Box::new(filesystem_mock);
-        test_backend.files = expected_files.clone();
-        assert_eq!(test_backend.undo_stack.len(), 0);
+        test_backend
+            .load_folders_and_files(dir.path().to_string_lossy().into_owned())
+            .unwrap();
 
-        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
+        let folder = test_backend
+            .create_folder(dir.path().to_path_buf(), "Holidays")
+            .unwrap();
+        assert!(folder.is_dir());
+        assert_eq!(test_backend.folders, vec![folder.clone()]);
+        assert_eq!(
+            test_backend.undo_history()[0].kind,
+            ActionKind::CreateFolder
+        );
+        assert_eq!(test_backend.current_file_index, 0);
 
-        assert_eq!(test_backend.undo_stack.len(), 1);
-        assert_eq!(test_backend.current_file_index, 1);
+        test_backend.undo().unwrap();
+        assert!(!folder.exists());
+        assert!(test_backend.folders.is_empty());
+        test_backend.redo().unwrap();
+        assert!(folder.is_dir());
+        assert_eq!(test_backend.folders, vec![folder.clone()]);
+
+        test_backend.move_file(folder.clone()).unwrap();
+        File::create(folder.join("other.png")).unwrap();
+        test_backend.undo().unwrap();
+        test_backend.undo().unwrap();
+        assert!(folder.join("other.png").exists());
+        assert_eq!(test_backend.folders, vec![folder]);
+        assert_eq!(test_backend.current_file_index, 0);
     }
 
     #[test]
-    fn ensure_error_is_thrown_when_no_files_loaded_when_moving() {
+    fn ensure_invalid_or_taken_folder_names_are_rejected() {
+        let dir = TempDir::new("unit_test").unwrap();
+        fs::create_dir(dir.path().join("Holidays")).unwrap();
         let mut test_backend = Backend::new();
-
-        assert!(test_backend.move_file(PathBuf::from("./toFolder")).is_err());
+        let parent = dir.path().to_path_buf();
+
+        let taken = test_backend.create_folder(parent.clone(), "Holidays");
+        let illegal = test_backend.create_folder(parent.clone(), "a:b");
+        let reserved = test_backend.create_folder(parent.clone(), "aux");
+        let nested = test_backend.create_folder(parent, "a/b");
+
+        assert_eq!(taken.unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(illegal.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(reserved.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(nested.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(test_backend.folders.is_empty());
+        assert!(test_backend.undo_history().is_empty());
     }
 
     #[test]
-    fn ensure_file_is_added_when_adding_a_folder() {
-        let mut test_backend = Backend::new();
+    fn ensure_folder_taken_according_to_the_filesystem_helper_is_rejected() {
         let mut filesystem_mock = FilesystemMock::new();
-        let expected_folders = vec![PathBuf::from("./folder1")];
-        filesystem_mock.folders = expected_folders.clone();
-        test_backend.filesystem_helper = Box::new(filesystem_mock);
+        filesystem_mock.folders = build_folders();
+        let mut test_backend = Backend::new();
+        test_backend.filesystem_helper = Rc::new(filesystem_mock);
 
-        test_backend.add_folder("./testFolder".to_owned()).unwrap();
+        let taken = test_backend.create_folder(PathBuf::from("."), "folder1");
+        let created = test_backend.create_folder(PathBuf::from("."), "folder4");
 
-        let actual_folders = test_backend.folders;
-        assert_vectors(&actual_folders, &expected_folders);
+        let error = taken.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(error.to_string(), "./folder1 already exists");
+        assert_eq!(created.unwrap(), PathBuf::from("./folder4"));
+        assert_eq!(test_backend.folders, vec![PathBuf::from("./folder4")]);
+        assert_eq!(test_backend.undo_history().len(), 1);
     }
 
     #[test]
-    fn ensure_folders_are_cleared_when_clearing_folders() {
-        let original_folders = build_folders();
-        let expected_files = build_files();
+    fn ensure_renamed_file_stays_current_and_gets_its_name_back_by_undo() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
+        let source = dir.path().join("source");
+        fs::create_dir(&source).unwrap();
+        let photo = source.join("IMG_1234.jpg");
+        File::create(&photo).unwrap();
+        File::create(source.join("beach.jpg")).unwrap();
         let mut test_backend = Backend::new();
-        test_backend.folders = original_folders.clone();
-        test_backend.files = expected_files.clone();
-
-        test_backend.clear_folders();
-
-        let actual_folders = test_backend.folders;
-        let actual_files = test_backend.files;
-        assert_vectors(&actual_folders, &Vec::new());
-        assert_vectors(&actual_files, &expected_files);
+        test_backend.sort_order = Some(SortOrder::descending(SortKey::NaturalName));
+        test_backend
+            .load_folders_and_files(source.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.start_session(journal_path.clone()).unwrap();
+
+        let renamed = test_backend.rename_current("Sunset.jpg").unwrap();
+        assert_eq!(renamed, source.join("Sunset.jpg"));
+        assert!(renamed.exists() && !photo.exists());
+        assert_eq!(test_backend.get_current_file(), Some(&renamed));
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Rename);
+
+        let taken = test_backend.rename_current("beach.jpg").unwrap_err();
+        let invalid = test_backend.rename_current("a/b.jpg").unwrap_err();
+        assert_eq!(taken.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(invalid.kind(), ErrorKind::InvalidInput);
+        assert_eq!(test_backend.undo_history().len(), 1);
+
+        let mut resumed_backend = Backend::new();
+        resumed_backend.resume_session(journal_path).unwrap();
+        assert_eq!(resumed_backend.files, test_backend.files);
+        assert_eq!(resumed_backend.current_file_index, 0);
+
+        test_backend.undo().unwrap();
+        assert!(photo.exists() && !renamed.exists());
+        assert_eq!(test_backend.get_current_file(), Some(&photo));
+        test_backend.redo().unwrap();
+        assert!(renamed.exists());
+        assert_eq!(test_backend.get_current_file(), Some(&renamed));
     }
 
     #[test]
-    fn ensure_file_is_removed_when_deleting_file() {
-        let expected_folders = build_folders();
-        let expected_files = build_files();
+    fn ensure_files_are_renamed_by_template_with_a_free_counter() {
+        let dir = TempDir::new("unit_test").unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            File::create(dir.path().join(name))
+                .unwrap()
+                .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(951_782_400))
+                .unwrap();
+        }
+        File::create(dir.path().join("2000-02-29_1.jpg")).unwrap();
         let mut test_backend = Backend::new();
-        test_backend.filesystem_helper = Box::new(FilesystemMock::new());
-        test_backend.folders = expected_folders.clone();
-        test_backend.files = expected_files.clone();
-        assert_eq!(test_backend.undo_stack.len(), 0);
+        test_backend.sort_order = Some(SortOrder::ascending(SortKey::NaturalName));
+        test_backend
+            .load_folders_and_files(dir.path().to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.skip().unwrap();
+        let template = NameTemplate::new("{date}_{counter}{ext}").unwrap();
 
-        test_backend.delete_file().expect("delete failed!");
+        let first = test_backend.rename_current_by_template(&template).unwrap();
+        test_backend.skip().unwrap();
+        let second = test_backend.rename_current_by_template(&template).unwrap();
+        let again = test_backend.rename_current_by_template(&template).unwrap();
 
-        let actual_folders = &test_backend.folders;
-        let actual_files = &test_backend.files;
-        assert_vectors(&actual_folders, &expected_folders);
-        assert_vectors(&actual_files, &expected_files);
-        assert_eq!(test_backend.undo_stack.len(), 1);
+        assert_eq!(first, dir.path().join("2000-02-29_2.jpg"));
+        assert_eq!(second, dir.path().join("2000-02-29_3.jpg"));
+        assert_eq!(again, second);
+        assert_eq!(
+            test_backend.files[1..],
+            [first, second.clone(), dir.path().join("c.jpg")]
+        );
+        let fixed = NameTemplate::new("{date}{ext}").unwrap();
+        test_backend.skip().unwrap();
+        test_backend.rename_current_by_template(&fixed).unwrap();
+        assert_eq!(
+            test_backend.get_current_file(),
+            Some(&dir.path().join("2000-02-29.jpg"))
+        );
     }
 
     #[test]
-    fn ensure_pointer_is_moved_forward_when_file_is_skipped() {
-        let expected_files = build_files();
-        let mut test_backend = Backend::new();
-        test_backend.files = expected_files.clone();
-        let expected_index = 1;
-        assert_eq!(
-            test_backend.get_current_file().unwrap(),
-            &expected_files[expected_index - 1]
-        );
-        assert_eq!(test_backend.undo_stack.len(), 0);
+    fn ensure_failed_destination_rolls_back_the_others() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Fail);
+        let family = dir.path().join("Family");
+        fs::create_dir(&family).unwrap();
+
+        let actual_error = test_backend
+            .sort_into(&[
+                (family.clone(), TransferMode::Move),
+                (folder.clone(), TransferMode::Copy),
+            ])
+            .unwrap_err();
+
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert!(dir.path().join("file1.png").exists());
+        assert!(!family.join("file1.png").exists());
+        assert_eq!(test_backend.current_file_index, 0);
+        assert!(test_backend.undo_history().is_empty());
+        let actual_error = test_backend
+            .sort_into(&[
+                (family.clone(), TransferMode::Move),
+                (folder, TransferMode::Move),
+            ])
+            .unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::InvalidInput);
+    }
 
-        test_backend.skip().expect("Skipping failed");
+    #[test]
+    fn ensure_next_files_are_sorted_and_undone_as_one_batch() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Skip);
+        fs::write(dir.path().join("file3.png"), "third").unwrap();
+        test_backend
+            .load_folders_and_files(dir.path().to_string_lossy().into_owned())
+            .unwrap();
 
-        assert_eq!(test_backend.current_file_index, expected_index);
+        let outcomes = test_backend.sort_next(2, folder.clone()).unwrap();
         assert_eq!(
-            test_backend.get_current_file().unwrap(),
-            &expected_files[expected_index]
+            outcomes,
+            vec![
+                MoveOutcome::Skipped,
+                MoveOutcome::Moved(folder.join("file2.png"))
+            ]
         );
-        assert_eq!(test_backend.undo_stack.len(), 1);
+        assert_eq!(test_backend.current_file_index, 2);
+        assert_eq!(test_backend.undo_history().len(), 1);
+        assert_eq!(test_backend.undo_history()[0].kind, ActionKind::Batch);
+
+        test_backend.undo().unwrap();
+        assert_eq!(test_backend.current_file_index, 0);
+        assert!(dir.path().join("file2.png").exists());
+        assert!(!folder.join("file2.png").exists());
+
+        test_backend.redo().unwrap();
+        assert_eq!(test_backend.current_file_index, 2);
+        assert!(folder.join("file2.png").exists());
+        let outcomes = test_backend.sort_next(5, folder.clone()).unwrap();
+        assert_eq!(outcomes, vec![MoveOutcome::Moved(folder.join("file3.png"))]);
+        assert_eq!(test_backend.get_current_file(), None);
+        test_backend.undo().unwrap();
+        assert_eq!(test_backend.current_file_index, 2);
+        assert!(dir.path().join("file3.png").exists());
     }
 
     #[test]
-    fn ensure_pointer_is_moved_forward_and_unchanged_undo_stack_when_incrementing() {
-        let expected_files = build_files();
+    fn ensure_failed_file_rolls_back_the_batch() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Fail);
+        fs::rename(folder.join("file1.png"), folder.join("file2.png")).unwrap();
+
+        let actual_error = test_backend.sort_next(2, folder.clone()).unwrap_err();
+
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert!(dir.path().join("file1.png").exists());
+        assert!(!folder.join("file1.png").exists());
+        assert_eq!(test_backend.current_file_index, 0);
+        assert!(test_backend.undo_history().is_empty());
+    }
+
+    fn build_selection(dir: &Path) -> (Backend, PathBuf) {
+        let folder = dir.join("folder1");
+        fs::create_dir(&folder).unwrap();
+        for file in ["1a.png", "1b.jpg", "2a.png", "2b.jpg"] {
+            fs::write(dir.join(file), file).unwrap();
+        }
         let mut test_backend = Backend::new();
-        test_backend.files = expected_files.clone();
-        let expected_index = 1;
-        assert_eq!(
-            test_backend.get_current_file().unwrap(),
-            &expected_files[expected_index - 1]
-        );
-        assert_eq!(test_backend.undo_stack.len(), 0);
+        test_backend.sort_order = Some(SortOrder::ascending(SortKey::NaturalName));
+        test_backend
+            .load_folders_and_files(dir.to_string_lossy().into_owned())
+            .unwrap();
 
-        test_backend.increment().expect("Skipping failed");
+        (test_backend, folder)
+    }
 
-        assert_eq!(test_backend.current_file_index, expected_index);
+    #[test]
+    fn ensure_selected_files_are_sorted_together_and_the_rest_are_left() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_selection(dir.path());
+
+        let selection = test_backend.select_glob("*.jpg").unwrap();
+        assert_eq!(selection, vec![1, 3]);
+        test_backend
+            .sort_selection(&selection, folder.clone())
+            .unwrap();
+
+        assert!(folder.join("1b.jpg").exists());
+        assert!(folder.join("2b.jpg").exists());
+        assert_eq!(test_backend.current_file_index, 2);
         assert_eq!(
-            test_backend.get_current_file().unwrap(),
-            &expected_files[expected_index]
+            test_backend.get_current_file(),
+            Some(&dir.path().join("1a.png"))
         );
-        assert_eq!(test_backend.undo_stack.len(), 0);
+        test_backend.undo().unwrap();
+        assert_eq!(test_backend.current_file_index, 0);
+        assert!(dir.path().join("1b.jpg").exists());
+        assert!(dir.path().join("2b.jpg").exists());
+
+        test_backend.delete_selection(&[1, 2]).unwrap();
+        assert!(!dir.path().join("2b.jpg").exists());
+        test_backend.undo().unwrap();
+        assert!(dir.path().join("2b.jpg").exists());
+        test_backend.skip_selection(&[3]).unwrap();
+        assert_eq!(test_backend.current_file_index, 1);
+        let actual_error = test_backend.skip_selection(&[0]).unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::InvalidInput);
+        assert!(test_backend.select_glob("[").is_err());
     }
 
     #[test]
-    fn ensure_error_is_thrown_when_index_out_of_bounds_when_skipping() {
-        let expected_files = build_files();
-        let mut test_backend = Backend::new();
-        test_backend.files = expected_files.clone();
-        test_backend.current_file_index = 2;
-        let expected_index = 3;
+    fn ensure_files_keep_their_order_when_a_selection_fails() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_selection(dir.path());
+        let files = test_backend.files.clone();
+        fs::write(folder.join("2a.png"), "taken").unwrap();
+
+        let selection = test_backend.select(|file| file.extension().unwrap() == "png");
+        let actual_error = test_backend
+            .sort_selection(&selection, folder.clone())
+            .unwrap_err();
+
+        assert_eq!(actual_error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(test_backend.files, files);
+        assert!(dir.path().join("1a.png").exists());
+        assert!(test_backend.undo_history().is_empty());
+    }
+
+    #[test]
+    fn ensure_rules_are_planned_and_applied_as_one_batch() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_selection(dir.path());
+        let other = dir.path().join("folder2");
+        fs::create_dir(&other).unwrap();
+        test_backend.rules = vec![Rule {
+            conditions: vec![Condition::Name(Glob::new("2*").unwrap())],
+            destination: other.clone(),
+        }];
+
+        let actual_error = test_backend.plan_rules().unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::NotFound);
+        test_backend
+            .add_folder(other.to_string_lossy().into_owned())
+            .unwrap();
+        test_backend.rules.push(Rule {
+            conditions: vec![Condition::Extension(vec!["png".to_owned()])],
+            destination: folder.clone(),
+        });
+        let proposed_moves = test_backend.plan_rules().unwrap();
+        let destinations: Vec<(usize, &Path)> = proposed_moves
+            .iter()
+            .map(|proposed| (proposed.index, proposed.destination.as_path()))
+            .collect();
         assert_eq!(
-            test_backend.get_current_file().unwrap(),
-            &expected_files[expected_index - 1]
+            destinations,
+            vec![
+                (0, folder.as_path()),
+                (2, other.as_path()),
+                (3, other.as_path())
+            ]
         );
+        assert!(dir.path().join("1a.png").exists());
 
-        assert!(test_backend.increment().is_err());
+        let applied = test_backend.apply_rules().unwrap();
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied[1].1, MoveOutcome::Moved(other.join("2a.png")));
+        assert!(other.join("2b.jpg").exists());
+        assert_eq!(
+            test_backend.get_current_file(),
+            Some(&dir.path().join("1b.jpg"))
+        );
+        test_backend.undo().unwrap();
+        assert!(dir.path().join("1a.png").exists());
+        assert!(dir.path().join("2b.jpg").exists());
+        assert_eq!(test_backend.current_file_index, 0);
     }
 
     #[test]
-    fn ensure_undo_stack_is_popped_and_redo_stack_is_pushed_when_undoing() {
-        let mut test_backend = Backend::new();
-        let filesystem_mock = FilesystemMock::new();
-        let mut undo_element = Move::new(PathBuf::from("a"), PathBuf::from("b"));
-        undo_element.filesystem_helper = Box::new(filesystem_mock);
-        test_backend.undo_stack.push(Box::new(undo_element));
-        test_backend.current_file_index = 2;
+    fn ensure_rules_are_loaded_only_for_folders_to_sort_into() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_selection(dir.path());
+        let archive = TempDir::new("unit_test").unwrap();
+        let rules_file = archive.path().join("team.rules");
+        let rules = format!(
+            "ext:png -> folder1\nname:2* -> {}\n",
+            archive.path().display()
+        );
+        fs::write(&rules_file, rules).unwrap();
 
-        test_backend.undo().expect("undo failed");
+        let actual_error = test_backend
+            .load_rules(rules_file.to_string_lossy().into_owned())
+            .unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            actual_error.to_string(),
+            format!(
+                "line 2, column 12: {} is not a folder to sort into",
+                archive.path().display()
+            )
+        );
+        assert!(test_backend.rules.is_empty());
+        assert_eq!(test_backend.folders, vec![folder.clone()]);
+        test_backend
+            .add_folder(archive.path().to_string_lossy().into_owned())
+            .unwrap();
+        test_backend
+            .load_rules(rules_file.to_string_lossy().into_owned())
+            .unwrap();
 
-        assert_eq!(test_backend.redo_stack.len(), 1);
-        assert_eq!(test_backend.undo_stack.len(), 0);
-        assert_eq!(test_backend.current_file_index, 1);
+        assert_eq!(test_backend.rules.len(), 2);
+        assert_eq!(test_backend.rules[0].destination, folder);
+        assert_eq!(
+            test_backend.folders,
+            vec![folder, archive.path().to_path_buf()]
+        );
+        assert_eq!(test_backend.plan_rules().unwrap().len(), 3);
+
+        fs::write(&rules_file, "ext:png -> folder1\next:jpg -> missing\n").unwrap();
+        let actual_error = test_backend
+            .load_rules(rules_file.to_string_lossy().into_owned())
+            .unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::NotFound);
+        assert!(actual_error.to_string().ends_with("missing doesn't exist"));
+        assert!(actual_error.to_string().starts_with("line 2, column 12: "));
+        assert_eq!(test_backend.rules.len(), 2);
+        fs::write(&rules_file, "ext:png -> folder1\next:png -> folder1\n").unwrap();
+        let actual_error = test_backend
+            .load_rules(rules_file.to_string_lossy().into_owned())
+            .unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::InvalidData);
     }
 
     #[test]
-    fn ensure_nothing_happens_when_undo_stack_is_empty() {
-        let mut test_backend = Backend::new();
-        test_backend.current_file_index = 0;
-
-        test_backend.undo().expect("undo failed");
-
-        assert_eq!(test_backend.redo_stack.len(), 0);
-        assert_eq!(test_backend.undo_stack.len(), 0);
+    fn ensure_duplicate_is_reported_instead_of_moved() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let (mut test_backend, folder) = build_collision(dir.path(), CollisionPolicy::Fail);
+        fs::write(folder.join("copy.png"), "new").unwrap();
+        test_backend.detect_duplicates = true;
+
+        let outcome = test_backend.move_file(folder.clone()).unwrap();
+        assert_eq!(outcome, MoveOutcome::Duplicate(folder.join("copy.png")));
         assert_eq!(test_backend.current_file_index, 0);
+        assert!(dir.path().join("file1.png").exists());
+
+        test_backend.delete_file().unwrap();
+        let actual_error = test_backend.move_file(folder.clone()).unwrap_err();
+        assert_eq!(actual_error.kind(), ErrorKind::UnexpectedEof); // file2.png was the last file
+        assert!(folder.join("file2.png").exists());
     }
 
+    #[cfg(feature = "perceptual")]
     #[test]
-    fn ensure_final_image_is_undone_when_end_of_files_hit_and_undo() {
-        let filesystem_mock = FilesystemMock::new();
-        let expected_files = build_files();
+    fn ensure_remaining_files_that_look_alike_are_grouped() {
+        let dir = TempDir::new("unit_test").unwrap();
         let mut test_backend = Backend::new();
-        test_backend.filesystem_helper = Box::new(filesystem_mock);
-        test_backend.files = expected_files.clone();
-        assert_eq!(test_backend.undo_stack.len(), 0);
-        dbg!(&test_backend.files);
-        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
-        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
-        let _ = test_backend.move_file(PathBuf::from("./toFolder")); // ignore result since EoF
-
-        let _ = test_backend.undo(); // ignore result since it tries on real filesystem
+        for (name, brightness) in [("a.png", 0), ("b.png", 255), ("c.png", 0), ("d.png", 0)] {
+            let path = dir.path().join(name);
+            image::GrayImage::from_fn(16, 16, |x, _| match x < 8 {
+                true => image::Luma([brightness]),
+                false => image::Luma([255 - brightness]),
+            })
+            .save(&path)
+            .unwrap();
+            test_backend.files.push(path);
+        }
+        test_backend.files.push(dir.path().join("missing.png"));
 
-        assert_eq!(test_backend.current_file_index, 2);
+        assert_eq!(test_backend.near_duplicate_groups(0), vec![vec![0, 2, 3]]);
+        test_backend.current_file_index = 1;
+        assert_eq!(test_backend.near_duplicate_groups(0), vec![vec![2, 3]]);
     }
 
     #[test]
-    fn ensure_redo_stack_is_popped_and_undo_stack_is_pushed_when_redoing() {
-        let filesystem_mock = FilesystemMock::new();
-        let expected_files = build_files();
-        let mut redo_element = Move::new(PathBuf::from("a"), PathBuf::from("b"));
-        redo_element.filesystem_helper = Box::new(filesystem_mock);
+    fn ensure_resumed_session_keeps_being_recorded() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
         let mut test_backend = Backend::new();
-        test_backend.redo_stack.push(Box::new(redo_element));
-        test_backend.current_file_index = 0;
-        test_backend.files = expected_files;
+        test_backend.files = build_files();
+        test_backend.start_session(journal_path.clone()).unwrap();
+        test_backend.skip().unwrap();
+        let mut resumed_backend = Backend::new();
+        resumed_backend
+            .resume_session(journal_path.clone())
+            .unwrap();
 
-        test_backend.redo().expect("redo failed");
+        resumed_backend.skip().unwrap();
+        let mut second_resumed_backend = Backend::new();
+        second_resumed_backend.resume_session(journal_path).unwrap();
 
-        assert_eq!(test_backend.redo_stack.len(), 0);
-        assert_eq!(test_backend.undo_stack.len(), 1);
-        assert_eq!(test_backend.current_file_index, 1);
+        assert_eq!(second_resumed_backend.current_file_index, 2);
+        assert_eq!(second_resumed_backend.undo_stack.len(), 2);
     }
 
     #[test]
-    fn ensure_nothing_happens_when_redo_stack_is_empty() {
+    fn ensure_session_started_at_the_end_of_the_files_resumes_there() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let journal_path = dir.path().join("session.journal");
         let mut test_backend = Backend::new();
-        test_backend.current_file_index = 0;
+        test_backend.files = build_files();
+        test_backend.skip().unwrap();
+        test_backend.skip().unwrap();
+        test_backend.skip().unwrap_err(); // end of files
+        test_backend.start_session(journal_path.clone()).unwrap();
 
-        test_backend.redo().expect("redo failed");
+        let mut resumed_backend = Backend::new();
+        resumed_backend.resume_session(journal_path).unwrap();
 
-        assert_eq!(test_backend.redo_stack.len(), 0);
-        assert_eq!(test_backend.undo_stack.len(), 0);
-        assert_eq!(test_backend.current_file_index, 0);
+        assert_eq!(resumed_backend.current_file_index, 2);
+        assert_eq!(resumed_backend.get_current_file(), None);
     }
 
     #[test]
-    fn ensure_none_is_returned_when_current_file_index_is_out_of_bounds() {
+    fn ensure_history_describes_performed_and_undone_actions() {
         let mut test_backend = Backend::new();
-        test_backend.current_file_index = 10;
+        test_backend.filesystem_helper = Rc::new(FilesystemMock::new());
+        test_backend.files = build_files();
+        test_backend.move_file(PathBuf::from("./toFolder")).unwrap();
+        test_backend.skip().unwrap();
+        test_backend.delete_file().unwrap_err(); // end of files
+        test_backend.undo().unwrap();
 
-        assert_eq!(test_backend.get_current_file().is_none(), true);
+        let undo_history = test_backend.undo_history();
+        let redo_history = test_backend.redo_history();
+
+        assert_eq!(undo_history.len(), 2);
+        assert_eq!(undo_history[0].kind, ActionKind::Move);
+        assert_eq!(undo_history[0].source, PathBuf::from("./file1.png"));
+        assert_eq!(
+            undo_history[0].destination,
+            Some(PathBuf::from("./toFolder/file1.png"))
+        );
+        assert_eq!(undo_history[1].kind, ActionKind::Skip);
+        assert_eq!(undo_history[1].source, PathBuf::from("./file2.png"));
+        assert_eq!(redo_history.len(), 1);
+        assert_eq!(redo_history[0].kind, ActionKind::Delete);
+        assert_eq!(
+            redo_history[0].destination,
+            Some(PathBuf::from("./trash/file3.png"))
+        );
     }
 
-    fn assert_vectors(actual_vector: &Vec<PathBuf>, expected_vector: &Vec<PathBuf>) {
+    fn assert_vectors(actual_vector: &[PathBuf], expected_vector: &[PathBuf]) {
         assert_eq!(actual_vector.len(), expected_vector.len());
         for expected in expected_vector {
             assert!(actual_vector.contains(expected));
         }
     }
 }
-
@@ -0,0 +1,546 @@
+//! Metadata describing how, when and where a photo was taken, read from the file without
+//! decoding the image.
+//!
+//! Most of it comes from EXIF data, which is stored in a different place in every format: an
+//! APP1 segment of JPEG files, the header of TIFF files, an `eXIf` chunk of PNG files, an `EXIF`
+//! chunk of WebP files and an `Exif` item of HEIF files such as HEIC and AVIF.
+
+use crate::dimensions::image_dimensions;
+use crate::exif::read_tiff;
+use crate::format::FileFormat;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Error, Read, Seek, SeekFrom};
+use std::iter;
+use std::path::Path;
+
+/// How much of a file is searched for its metadata, and how large an EXIF structure may be.
+const MAX_HEADER_LENGTH: u64 = 1024 * 1024;
+
+/// The number of bytes read from the start of a file to detect its format.
+const FORMAT_HEADER_LENGTH: u64 = 64;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// What a file tells about itself. Every field is optional since few files have all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// When the photo was taken as `YYYY:MM:DD HH:MM:SS`, which sorts chronologically.
+    pub capture_date: Option<String>,
+    /// The maker of the camera that took the photo.
+    pub camera_make: Option<String>,
+    /// The model of the camera that took the photo, as the camera names itself.
+    pub camera_model: Option<String>,
+    /// The lens the photo was taken with.
+    pub lens_model: Option<String>,
+    /// The exposure time in seconds, as a numerator and denominator such as `(1, 250)`.
+    pub exposure_time: Option<(u32, u32)>,
+    /// The aperture as an f-number.
+    pub f_number: Option<f64>,
+    /// The ISO speed.
+    pub iso: Option<u32>,
+    /// The focal length in millimeters.
+    pub focal_length: Option<f64>,
+    /// How the image has to be turned to be shown upright, as the EXIF orientation from 1 to 8.
+    pub orientation: Option<u16>,
+    /// Where the photo was taken.
+    pub gps: Option<GpsPosition>,
+    /// The width and height of the image in pixels, as stored and before any orientation.
+    pub dimensions: Option<(u32, u32)>,
+}
+
+/// A position on earth, as recorded by a camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    /// Degrees north of the equator, negative for south.
+    pub latitude: f64,
+    /// Degrees east of the prime meridian, negative for west.
+    pub longitude: f64,
+    /// Meters above sea level, negative for below.
+    pub altitude: Option<f64>,
+}
+
+impl Metadata {
+    /// Reads the metadata of a JPEG, TIFF, PNG, WebP, HEIC or AVIF file.
+    ///
+    /// PNG files without EXIF data may still have a capture date from their `Creation Time`
+    /// text. Other formats only have [dimensions](Metadata::dimensions) if their size can be
+    /// read from their header, and files that aren't images have no metadata at all.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read, an error variant will be returned.
+    pub fn of_file(file: &Path) -> Result<Metadata, Error> {
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut header = Vec::new();
+        reader
+            .by_ref()
+            .take(FORMAT_HEADER_LENGTH)
+            .read_to_end(&mut header)?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut metadata = Metadata {
+            dimensions: image_dimensions(file)?,
+            ..Metadata::default()
+        };
+        match FileFormat::detect(&header) {
+            FileFormat::Jpeg => read_jpeg(&mut reader, &mut metadata)?,
+            FileFormat::Tiff => {
+                let mut tiff = Vec::new();
+                reader.take(MAX_HEADER_LENGTH).read_to_end(&mut tiff)?;
+                read_tiff(&tiff, &mut metadata);
+            }
+            FileFormat::Png => read_png(&mut reader, &mut metadata)?,
+            FileFormat::WebP => read_webp(&mut reader, &mut metadata)?,
+            FileFormat::Heic | FileFormat::Avif => read_heif(&mut reader, &mut metadata)?,
+            _ => {}
+        }
+
+        Ok(metadata)
+    }
+}
+
+/// Walks the JPEG segments until the EXIF one or the image data.
+fn read_jpeg(reader: &mut impl Read, metadata: &mut Metadata) -> Result<(), Error> {
+    let mut reader = reader.take(MAX_HEADER_LENGTH);
+    let mut marker = [0; 2];
+    reader.read_exact(&mut marker)?;
+    loop {
+        if reader.read_exact(&mut marker).is_err()
+            || marker[0] != 0xff
+            || marker[1] == 0xda
+            || marker[1] == 0xd9
+        {
+            return Ok(());
+        }
+        let mut length = [0; 2];
+        if reader.read_exact(&mut length).is_err() {
+            return Ok(());
+        }
+        let length = usize::from(u16::from_be_bytes(length)).saturating_sub(2);
+        let mut segment = vec![0; length];
+        if reader.read_exact(&mut segment).is_err() {
+            return Ok(());
+        }
+        if marker[1] == 0xe1 && segment.starts_with(b"Exif\0\0") {
+            read_tiff(&segment[6..], metadata);
+            return Ok(());
+        }
+    }
+}
+
+/// Walks the PNG chunks for EXIF data and a creation time, until the end of the image.
+fn read_png(reader: &mut (impl Read + Seek), metadata: &mut Metadata) -> Result<(), Error> {
+    reader.seek(SeekFrom::Start(8))?;
+    let mut creation_time = None;
+    let mut header = [0; 8];
+    while reader.read_exact(&mut header).is_ok() {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let kind = &header[4..];
+        match kind {
+            b"IEND" => break,
+            b"eXIf" | b"tEXt" | b"iTXt" if u64::from(length) <= MAX_HEADER_LENGTH => {
+                let mut data = vec![0; length as usize];
+                if reader.read_exact(&mut data).is_err() {
+                    break;
+                }
+                match kind {
+                    b"eXIf" => read_tiff(&data, metadata),
+                    _ => creation_time = creation_time.or_else(|| png_creation_time(kind, &data)),
+                }
+                // skips the checksum
+                reader.seek(SeekFrom::Current(4))?;
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(i64::from(length) + 4))?;
+            }
+        }
+    }
+    if metadata.capture_date.is_none() {
+        metadata.capture_date = creation_time;
+    }
+
+    Ok(())
+}
+
+/// Returns the creation time from a `tEXt` or uncompressed `iTXt` chunk with that keyword.
+fn png_creation_time(kind: &[u8], data: &[u8]) -> Option<String> {
+    let mut parts = data.splitn(2, |byte| *byte == 0);
+    if parts.next()? != b"Creation Time" {
+        return None;
+    }
+    let text = parts.next()?;
+    let text = match kind {
+        // a compression flag and method, then a language and translated keyword before the text
+        b"iTXt" if text.first() == Some(&0) => {
+            text.get(2..)?.splitn(3, |byte| *byte == 0).nth(2)?
+        }
+        b"iTXt" => return None,
+        _ => text,
+    };
+
+    parse_creation_time(&String::from_utf8_lossy(text))
+}
+
+/// Parses a date written like ISO 8601, like EXIF or like RFC 1123, the formats PNG encoders
+/// write the creation time in, into the EXIF format. Time zones are ignored.
+fn parse_creation_time(text: &str) -> Option<String> {
+    let text = text.trim();
+    let is_number =
+        |text: &str, length| text.len() == length && text.bytes().all(|byte| byte.is_ascii_digit());
+
+    // `2021-07-04T18:30:00` or `2021:07:04 18:30:00`, possibly with fractions or a time zone
+    let date = text.get(..10).unwrap_or_default();
+    let time = text.get(11..19).unwrap_or_default();
+    let date_parts: Vec<&str> = date.split(['-', ':']).collect();
+    let time_parts: Vec<&str> = time.split(':').collect();
+    if let ([year, month, day], [hours, minutes, seconds]) =
+        (date_parts.as_slice(), time_parts.as_slice())
+    {
+        if is_number(year, 4)
+            && [month, day, hours, minutes, seconds]
+                .iter()
+                .all(|part| is_number(part, 2))
+        {
+            return Some(format!("{}:{}:{} {}", year, month, day, time));
+        }
+    }
+
+    // `Sun, 04 Jul 2021 18:30:00 GMT`, with an optional day of the week
+    let mut words = text
+        .split_whitespace()
+        .skip_while(|word| word.ends_with(','));
+    let (day, month, year, time) = (words.next()?, words.next()?, words.next()?, words.next()?);
+    let month = MONTHS
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(month))?
+        + 1;
+    let day = format!("{:0>2}", day);
+    let time_parts: Vec<&str> = time.split(':').collect();
+    let valid = is_number(&day, 2)
+        && is_number(year, 4)
+        && time_parts.len() == 3
+        && time_parts.iter().all(|part| is_number(part, 2));
+
+    valid.then(|| format!("{}:{:02}:{} {}", year, month, day, time))
+}
+
+/// Walks the WebP chunks for the EXIF one.
+fn read_webp(reader: &mut (impl Read + Seek), metadata: &mut Metadata) -> Result<(), Error> {
+    reader.seek(SeekFrom::Start(12))?;
+    let mut header = [0; 8];
+    while reader.read_exact(&mut header).is_ok() {
+        let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if &header[..4] == b"EXIF" {
+            let mut exif = Vec::new();
+            reader
+                .take(u64::from(length).min(MAX_HEADER_LENGTH))
+                .read_to_end(&mut exif)?;
+            // some encoders keep the prefix of the JPEG segment
+            let tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(&exif);
+            read_tiff(tiff, metadata);
+            break;
+        }
+        // chunks are padded to an even length
+        reader.seek(SeekFrom::Current(i64::from(length) + i64::from(length % 2)))?;
+    }
+
+    Ok(())
+}
+
+/// Reads the `Exif` item and the image size of a HEIF file.
+///
+/// The `meta` box lists the items of the file in `iinf`, where they are in `iloc` and their
+/// properties, such as their size in `ispe`, in `iprp`.
+fn read_heif(reader: &mut (impl Read + Seek), metadata: &mut Metadata) -> Result<(), Error> {
+    let mut data = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_HEADER_LENGTH)
+        .read_to_end(&mut data)?;
+    // `meta` is a full box, with a version and flags before its children
+    let meta = match boxes(&data).find(|(kind, _)| *kind == b"meta") {
+        Some((_, meta)) => meta.get(4..).unwrap_or_default(),
+        None => return Ok(()),
+    };
+    let (mut exif_item, mut locations) = (None, None);
+    for (kind, body) in boxes(meta) {
+        match kind {
+            b"iinf" => exif_item = find_exif_item(body),
+            b"iloc" => locations = Some(body),
+            b"iprp" if metadata.dimensions.is_none() => {
+                // thumbnails have their own size, so the largest is the image's
+                metadata.dimensions = boxes(body)
+                    .filter(|(kind, _)| *kind == b"ipco")
+                    .flat_map(|(_, properties)| boxes(properties))
+                    .filter(|(kind, _)| *kind == b"ispe")
+                    .filter_map(|(_, ispe)| Some((be(ispe, 4, 4)? as u32, be(ispe, 8, 4)? as u32)))
+                    .max_by_key(|(width, height)| u64::from(*width) * u64::from(*height));
+            }
+            _ => {}
+        }
+    }
+
+    let (offset, length) = match exif_item
+        .zip(locations)
+        .and_then(|(item, locations)| item_extent(locations, item))
+    {
+        Some(extent) => extent,
+        None => return Ok(()),
+    };
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut exif = Vec::new();
+    reader
+        .take(length.min(MAX_HEADER_LENGTH))
+        .read_to_end(&mut exif)?;
+    // the item starts with the offset of the TIFF structure after it
+    let tiff = be(&exif, 0, 4)
+        .and_then(|start| exif.get(4 + usize::try_from(start).ok()?..))
+        .unwrap_or_default();
+    read_tiff(tiff, metadata);
+
+    Ok(())
+}
+
+/// Iterates over the ISO media boxes in the data as their type and content.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    iter::from_fn(move || {
+        let current = data;
+        let (header, size) = match be(current, 0, 4)? {
+            1 => (16, be(current, 8, 8)?),
+            0 => (8, current.len() as u64),
+            size => (8, size),
+        };
+        let end = usize::try_from(size)
+            .unwrap_or(usize::MAX)
+            .min(current.len());
+        let content = current.get(header..end)?;
+        data = &current[end..];
+
+        Some((&current[4..8], content))
+    })
+}
+
+/// Returns the ID of the `Exif` item from the `iinf` box.
+fn find_exif_item(iinf: &[u8]) -> Option<u64> {
+    let entries = match iinf.first()? {
+        0 => iinf.get(6..)?,
+        _ => iinf.get(8..)?,
+    };
+
+    boxes(entries)
+        .filter(|(kind, _)| *kind == b"infe")
+        .find_map(|(_, infe)| {
+            let (item, kind) = match infe.first()? {
+                2 => (be(infe, 4, 2)?, infe.get(8..12)?),
+                3 => (be(infe, 4, 4)?, infe.get(10..14)?),
+                _ => return None,
+            };
+            (kind == b"Exif").then_some(item)
+        })
+}
+
+/// Returns the file offset and length of the item's first extent from the `iloc` box.
+fn item_extent(iloc: &[u8], item: u64) -> Option<(u64, u64)> {
+    let version = *iloc.first()?;
+    let sizes = *iloc.get(4)?;
+    let (offset_size, length_size) = (usize::from(sizes >> 4), usize::from(sizes & 0xf));
+    let sizes = *iloc.get(5)?;
+    let base_offset_size = usize::from(sizes >> 4);
+    let index_size = match version {
+        0 => 0,
+        _ => usize::from(sizes & 0xf),
+    };
+    let (item_size, mut position) = match version {
+        0 | 1 => (2, 8),
+        _ => (4, 10),
+    };
+    let item_count = be(iloc, 6, item_size)?;
+
+    for _ in 0..item_count {
+        let id = be(iloc, position, item_size)?;
+        position += item_size;
+        // only items stored at an offset in the file itself are read
+        let in_file = match version {
+            0 => true,
+            _ => {
+                position += 2;
+                be(iloc, position - 2, 2)? & 0xf == 0
+            }
+        };
+        // skips the data reference index
+        position += 2;
+        let base_offset = be(iloc, position, base_offset_size)?;
+        position += base_offset_size;
+        let extent_count = usize::try_from(be(iloc, position, 2)?).ok()?;
+        position += 2;
+        if id == item {
+            let extent = position + index_size;
+            let offset = be(iloc, extent, offset_size)?;
+            let length = match be(iloc, extent + offset_size, length_size)? {
+                // the extent runs to the end of the file
+                0 => MAX_HEADER_LENGTH,
+                length => length,
+            };
+            return (in_file && extent_count > 0)
+                .then(|| Some((base_offset.checked_add(offset)?, length)))
+                .flatten();
+        }
+        position += extent_count * (index_size + offset_size + length_size);
+    }
+
+    None
+}
+
+/// Reads a big endian number of up to eight bytes.
+fn be(data: &[u8], offset: usize, size: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(size)?)?;
+
+    Some(
+        bytes
+            .iter()
+            .fold(0, |number, byte| number << 8 | u64::from(*byte)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exif::tests::build_tiff;
+    use crate::metadata::{parse_creation_time, Metadata};
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Builds an ISO media box of the type around the content.
+    fn build_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut iso_box = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        iso_box.extend_from_slice(kind);
+        iso_box.extend_from_slice(content);
+        iso_box
+    }
+
+    #[test]
+    fn ensure_capture_date_is_read_from_jpeg() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("IMG_0001.jpg");
+        let tiff = build_tiff("2021:07:04 18:30:00");
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xe1];
+        jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0, 17, 8, 0x0b, 0xb8, 0x0f, 0xa0, 0xff, 0xda]);
+        fs::write(&file, jpeg).unwrap();
+
+        let actual = Metadata::of_file(&file).unwrap();
+
+        assert_eq!(actual.capture_date.as_deref(), Some("2021:07:04 18:30:00"));
+        assert_eq!(actual.dimensions, Some((4000, 3000)));
+    }
+
+    #[test]
+    fn ensure_capture_date_is_read_from_tiff_and_webp() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let tiff = dir.path().join("scan.tif");
+        let webp = dir.path().join("photo.webp");
+        fs::write(&tiff, build_tiff("1999:12:31 23:59:59")).unwrap();
+        let exif = build_tiff("2021:07:04 18:30:00");
+        let mut riff = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+        riff.extend_from_slice(&[8, 0, 0, 0, 0x3f, 0x01, 0, 0xef, 0, 0]);
+        riff.extend_from_slice(b"EXIF");
+        riff.extend_from_slice(&(exif.len() as u32 + 6).to_le_bytes());
+        riff.extend_from_slice(b"Exif\0\0");
+        riff.extend_from_slice(&exif);
+        fs::write(&webp, riff).unwrap();
+
+        let tiff = Metadata::of_file(&tiff).unwrap();
+        let webp = Metadata::of_file(&webp).unwrap();
+
+        assert_eq!(tiff.capture_date.as_deref(), Some("1999:12:31 23:59:59"));
+        assert_eq!(webp.capture_date.as_deref(), Some("2021:07:04 18:30:00"));
+        assert_eq!(webp.dimensions, Some((320, 240)));
+    }
+
+    #[test]
+    fn ensure_png_creation_time_is_used_without_exif() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("screenshot.png");
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 2, 128, 0, 0, 1, 224, 8, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let text = b"Creation Time\0Sun, 4 Jul 2021 18:30:00 GMT";
+        png.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"tEXt");
+        png.extend_from_slice(text);
+        png.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+        png.extend_from_slice(b"IEND");
+        fs::write(&file, png).unwrap();
+
+        let actual = Metadata::of_file(&file).unwrap();
+
+        assert_eq!(actual.capture_date.as_deref(), Some("2021:07:04 18:30:00"));
+        assert_eq!(actual.dimensions, Some((640, 480)));
+    }
+
+    #[test]
+    fn ensure_exif_item_and_size_are_read_from_heif() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("IMG_0001.heic");
+        let mut infe = vec![2, 0, 0, 0, 0, 7, 0, 0];
+        infe.extend_from_slice(b"Exif");
+        let mut iinf = vec![0, 0, 0, 0, 0, 1];
+        iinf.extend_from_slice(&build_box(b"infe", &infe));
+        let mut ispe = vec![0, 0, 0, 0];
+        ispe.extend_from_slice(&[0, 0, 0x0f, 0xc0, 0, 0, 0x0b, 0xd0]);
+        let iprp = build_box(b"ipco", &build_box(b"ispe", &ispe));
+        let meta_length = 4 + 8 + iinf.len() + 8 + 22 + 8 + iprp.len();
+        let exif_offset = (24 + 8 + meta_length) as u32;
+        let mut exif = vec![0, 0, 0, 6];
+        exif.extend_from_slice(b"Exif\0\0");
+        exif.extend_from_slice(&build_tiff("2021:07:04 18:30:00"));
+        // a single item with a single extent, with 4 byte offsets and lengths
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0, 0, 1, 0, 7, 0, 0, 0, 1];
+        iloc.extend_from_slice(&exif_offset.to_be_bytes());
+        iloc.extend_from_slice(&(exif.len() as u32).to_be_bytes());
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend_from_slice(&build_box(b"iinf", &iinf));
+        meta.extend_from_slice(&build_box(b"iloc", &iloc));
+        meta.extend_from_slice(&build_box(b"iprp", &iprp));
+        let mut heic = build_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        heic.extend_from_slice(&build_box(b"meta", &meta));
+        heic.extend_from_slice(&exif);
+        fs::write(&file, heic).unwrap();
+
+        let actual = Metadata::of_file(&file).unwrap();
+
+        assert_eq!(actual.capture_date.as_deref(), Some("2021:07:04 18:30:00"));
+        assert_eq!(actual.dimensions, Some((4032, 3024)));
+    }
+
+    #[test]
+    fn ensure_other_files_have_no_metadata() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let text = dir.path().join("notes.txt");
+        let truncated = dir.path().join("truncated.jpg");
+        fs::write(&text, "not an image").unwrap();
+        fs::write(&truncated, [0xff, 0xd8, 0xff, 0xe1, 0x10]).unwrap();
+
+        assert_eq!(Metadata::of_file(&text).unwrap(), Metadata::default());
+        assert_eq!(Metadata::of_file(&truncated).unwrap(), Metadata::default());
+        assert!(Metadata::of_file(&dir.path().join("missing.jpg")).is_err());
+    }
+
+    #[test]
+    fn ensure_creation_times_are_parsed() {
+        let expected = Some("2021:07:04 18:30:00".to_owned());
+
+        assert_eq!(parse_creation_time("2021-07-04T18:30:00+02:00"), expected);
+        assert_eq!(parse_creation_time("2021:07:04 18:30:00"), expected);
+        assert_eq!(
+            parse_creation_time("Sun, 04 Jul 2021 18:30:00 +0000"),
+            expected
+        );
+        assert_eq!(parse_creation_time("4 jul 2021 18:30:00"), expected);
+        assert_eq!(parse_creation_time("yesterday"), None);
+        assert_eq!(parse_creation_time("2021-07-04"), None);
+    }
+}
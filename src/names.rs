@@ -0,0 +1,97 @@
+//! Checks for names of new files and folders, so that they can be created on any common
+//! filesystem, including shared drives that are read from Windows.
+
+use std::io::{Error, ErrorKind};
+
+/// Characters that Windows doesn't allow in names, besides control characters and the path
+/// separators.
+const ILLEGAL_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// Names that Windows reserves for devices, even with an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The longest name most filesystems allow, in bytes.
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Checks that a name is a single path component that can be used on any common filesystem.
+///
+/// # Errors
+///
+/// If the name is empty, too long, has a path separator or another character that isn't
+/// allowed, ends in a dot or a space or is reserved, an [InvalidInput](ErrorKind::InvalidInput)
+/// error is returned that says why.
+pub(crate) fn validate_name(name: &str) -> Result<(), Error> {
+    let invalid = |message: String| Err(Error::new(ErrorKind::InvalidInput, message));
+    if name.trim().is_empty() {
+        return invalid("a name can't be empty".to_owned());
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return invalid(format!(
+            "`{}` is longer than {} bytes",
+            name, MAX_NAME_LENGTH
+        ));
+    }
+    if let Some(character) = name.chars().find(|character| {
+        character.is_control()
+            || ['/', '\\'].contains(character)
+            || ILLEGAL_CHARACTERS.contains(character)
+    }) {
+        return invalid(format!(
+            "`{}` has the character {:?}, which names can't have",
+            name, character
+        ));
+    }
+    if name == "." || name == ".." || name.ends_with(['.', ' ']) {
+        return invalid(format!("`{}` ends in a dot or a space", name));
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return invalid(format!("`{}` is a reserved name", name));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::names::validate_name;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn ensure_ordinary_names_are_allowed() {
+        assert!(validate_name("Holidays 2024").is_ok());
+        assert!(validate_name(".hidden").is_ok());
+        assert!(validate_name("console.log").is_ok());
+        assert!(validate_name("Ünïcödé 照片").is_ok());
+    }
+
+    #[test]
+    fn ensure_invalid_names_are_rejected_with_a_reason() {
+        let message = |name: &str| validate_name(name).unwrap_err().to_string();
+
+        assert_eq!(
+            validate_name("a/b").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(message(" "), "a name can't be empty");
+        assert_eq!(
+            message("a:b"),
+            "`a:b` has the character ':', which names can't have"
+        );
+        assert_eq!(
+            message("tab\t"),
+            "`tab\t` has the character '\\t', which names can't have"
+        );
+        assert_eq!(message(".."), "`..` ends in a dot or a space");
+        assert_eq!(message("draft."), "`draft.` ends in a dot or a space");
+        assert_eq!(message("nul.txt"), "`nul.txt` is a reserved name");
+        assert_eq!(message("Com1"), "`Com1` is a reserved name");
+        assert!(validate_name(&"x".repeat(256)).is_err());
+    }
+}
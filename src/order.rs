@@ -0,0 +1,311 @@
+//! Orderings of the file queue.
+
+use crate::metadata::Metadata;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What the files are sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// The full path, byte by byte, which is how files are loaded.
+    Path,
+    /// The file name, ignoring case and comparing runs of digits by their numeric value so that
+    /// `img2` comes before `img10`.
+    NaturalName,
+    /// When the file was last modified.
+    Modified,
+    /// When the file was created, where the filesystem keeps track of it.
+    Created,
+    /// The size of the file.
+    Size,
+    /// When the photo was taken, according to its EXIF data.
+    CaptureDate,
+    /// A random order that is the same every time for the same seed.
+    Random { seed: u64 },
+}
+
+impl SortKey {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Path => "path",
+            SortKey::NaturalName => "name",
+            SortKey::Modified => "modified",
+            SortKey::Created => "created",
+            SortKey::Size => "size",
+            SortKey::CaptureDate => "captured",
+            SortKey::Random { .. } => "random",
+        }
+    }
+
+    /// Returns the key with the name returned by [SortKey::as_str]. The random order gets the
+    /// given seed.
+    pub fn from_name(name: &str, seed: u64) -> Option<SortKey> {
+        let key = match name {
+            "path" => SortKey::Path,
+            "name" => SortKey::NaturalName,
+            "modified" => SortKey::Modified,
+            "created" => SortKey::Created,
+            "size" => SortKey::Size,
+            "captured" => SortKey::CaptureDate,
+            "random" => SortKey::Random { seed },
+            _ => return None,
+        };
+
+        Some(key)
+    }
+}
+
+/// How [Backend::sort_files](crate::Backend::sort_files) orders the files.
+///
+/// Files missing what they're sorted by, such as photos without a capture date or files whose
+/// metadata can't be read, always come last in their original order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl SortOrder {
+    pub fn ascending(key: SortKey) -> SortOrder {
+        SortOrder {
+            key,
+            descending: false,
+        }
+    }
+
+    pub fn descending(key: SortKey) -> SortOrder {
+        SortOrder {
+            key,
+            descending: true,
+        }
+    }
+
+    /// Sorts the files in place. The sort is stable, so files that compare equal keep their
+    /// order.
+    pub fn sort(&self, files: &mut Vec<PathBuf>) {
+        if let SortKey::Random { seed } = self.key {
+            shuffle(files, seed);
+            return;
+        }
+
+        let mut keyed: Vec<(Option<Key>, PathBuf)> = files
+            .drain(..)
+            .map(|file| (self.key_of(&file), file))
+            .collect();
+        keyed.sort_by(|(first, _), (second, _)| match (first, second) {
+            (Some(first), Some(second)) if self.descending => second.cmp(first),
+            (Some(first), Some(second)) => first.cmp(second),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        files.extend(keyed.into_iter().map(|(_, file)| file));
+    }
+
+    fn key_of(&self, file: &Path) -> Option<Key> {
+        let key = match self.key {
+            SortKey::Path => Key::Path(file.to_path_buf()),
+            SortKey::NaturalName => Key::Name(
+                file.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            SortKey::Modified => Key::Time(fs::metadata(file).ok()?.modified().ok()?),
+            SortKey::Created => Key::Time(fs::metadata(file).ok()?.created().ok()?),
+            SortKey::Size => Key::Size(fs::metadata(file).ok()?.len()),
+            SortKey::CaptureDate => Key::Date(Metadata::of_file(file).ok()?.capture_date?),
+            SortKey::Random { .. } => return None,
+        };
+
+        Some(key)
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum Key {
+    Path(PathBuf),
+    Name(String),
+    Time(SystemTime),
+    Size(u64),
+    Date(String),
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        match (self, other) {
+            (Key::Path(first), Key::Path(second)) => first.cmp(second),
+            (Key::Name(first), Key::Name(second)) => natural_cmp(first, second),
+            (Key::Time(first), Key::Time(second)) => first.cmp(second),
+            (Key::Size(first), Key::Size(second)) => first.cmp(second),
+            (Key::Date(first), Key::Date(second)) => first.cmp(second),
+            // keys of a single sort are always of the same kind
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Compares names case-insensitively, with runs of digits compared by their numeric value.
+/// Names that only differ in case or leading zeros are then compared as they are.
+pub fn natural_cmp(first: &str, second: &str) -> Ordering {
+    let mut first_chunks = Chunks(first);
+    let mut second_chunks = Chunks(second);
+    loop {
+        let ordering = match (first_chunks.next(), second_chunks.next()) {
+            (None, None) => return first.cmp(second),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(first_chunk), Some(second_chunk)) => {
+                match (is_digits(first_chunk), is_digits(second_chunk)) {
+                    (true, true) => {
+                        let first_number = first_chunk.trim_start_matches('0');
+                        let second_number = second_chunk.trim_start_matches('0');
+                        first_number
+                            .len()
+                            .cmp(&second_number.len())
+                            .then_with(|| first_number.cmp(second_number))
+                    }
+                    _ => first_chunk
+                        .chars()
+                        .flat_map(char::to_lowercase)
+                        .cmp(second_chunk.chars().flat_map(char::to_lowercase)),
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn is_digits(chunk: &str) -> bool {
+    chunk.starts_with(|character: char| character.is_ascii_digit())
+}
+
+/// Splits a name into alternating runs of digits and other characters.
+struct Chunks<'a>(&'a str);
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let starts_with_digit = is_digits(self.0);
+        let length = self
+            .0
+            .find(|character: char| character.is_ascii_digit() != starts_with_digit)
+            .unwrap_or(self.0.len());
+        if length == 0 {
+            return None;
+        }
+        let (chunk, rest) = self.0.split_at(length);
+        self.0 = rest;
+
+        Some(chunk)
+    }
+}
+
+/// Shuffles the files with a Fisher-Yates shuffle driven by a SplitMix64 generator.
+fn shuffle(files: &mut [PathBuf], seed: u64) {
+    let mut state = seed;
+    let mut next_random = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut random = state;
+        random = (random ^ (random >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        random = (random ^ (random >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        random ^ (random >> 31)
+    };
+    for index in (1..files.len()).rev() {
+        let other = (next_random() % (index as u64 + 1)) as usize;
+        files.swap(index, other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::{natural_cmp, SortKey, SortOrder};
+    use std::cmp::Ordering;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    fn build_files(names: &[&str]) -> Vec<PathBuf> {
+        names
+            .iter()
+            .map(|name| PathBuf::from("./images").join(name))
+            .collect()
+    }
+
+    #[test]
+    fn ensure_numbers_in_names_are_compared_by_value() {
+        assert_eq!(natural_cmp("img2.jpg", "img10.jpg"), Ordering::Less);
+        assert_eq!(natural_cmp("IMG_010.jpg", "img_9.jpg"), Ordering::Greater);
+        assert_eq!(natural_cmp("a.jpg", "B.jpg"), Ordering::Less);
+        assert_eq!(natural_cmp("img", "img1"), Ordering::Less);
+        assert_ne!(natural_cmp("img01", "img1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn ensure_files_are_sorted_naturally_in_both_directions() {
+        let mut files = build_files(&["img10.jpg", "img2.jpg", "img1.jpg"]);
+
+        SortOrder::ascending(SortKey::NaturalName).sort(&mut files);
+        assert_eq!(files, build_files(&["img1.jpg", "img2.jpg", "img10.jpg"]));
+
+        SortOrder::descending(SortKey::NaturalName).sort(&mut files);
+        assert_eq!(files, build_files(&["img10.jpg", "img2.jpg", "img1.jpg"]));
+    }
+
+    #[test]
+    fn ensure_files_are_sorted_by_size_with_unreadable_files_last() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let small = dir.path().join("small.jpg");
+        let large = dir.path().join("large.jpg");
+        let missing = dir.path().join("missing.jpg");
+        fs::write(&small, [0; 10]).unwrap();
+        fs::write(&large, [0; 100]).unwrap();
+        let mut files = vec![missing.clone(), small.clone(), large.clone()];
+
+        SortOrder::descending(SortKey::Size).sort(&mut files);
+
+        assert_eq!(files, vec![large, small, missing]);
+    }
+
+    #[test]
+    fn ensure_key_names_survive_a_round_trip() {
+        for key in [
+            SortKey::Path,
+            SortKey::NaturalName,
+            SortKey::Modified,
+            SortKey::Created,
+            SortKey::Size,
+            SortKey::CaptureDate,
+            SortKey::Random { seed: 7 },
+        ] {
+            assert_eq!(SortKey::from_name(key.as_str(), 7), Some(key));
+        }
+        assert_eq!(SortKey::from_name("date", 7), None);
+    }
+
+    #[test]
+    fn ensure_shuffle_is_repeatable_for_a_seed() {
+        let original = build_files(&["1", "2", "3", "4", "5", "6", "7", "8"]);
+        let mut first = original.clone();
+        let mut second = original.clone();
+
+        SortOrder::ascending(SortKey::Random { seed: 42 }).sort(&mut first);
+        SortOrder::ascending(SortKey::Random { seed: 42 }).sort(&mut second);
+
+        assert_eq!(first, second);
+        assert_ne!(first, original);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, original);
+    }
+}
@@ -0,0 +1,148 @@
+//! Grouping images that look alike, such as burst shots or resized copies of the same photo.
+//!
+//! Every image is reduced to a difference hash: it is shrunk to 9 by 8 grey pixels and each bit
+//! tells whether a pixel is brighter than its right neighbour. Images that look alike have
+//! hashes that differ in only a few bits, regardless of their size or compression.
+
+use image::imageops::FilterType;
+use image::ImageReader;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// A Hamming distance at which only small edits, re-encodings and resizes are grouped together.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// Returns the difference hash of an image. JPEG, PNG, GIF and BMP images can be decoded, and
+/// their format is told by their content rather than their extension.
+///
+/// # Errors
+///
+/// If the file can't be read or decoded, an error variant will be returned.
+pub fn difference_hash(file: &Path) -> Result<u64, Error> {
+    let image = ImageReader::open(file)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+    let pixels = image.resize_exact(9, 8, FilterType::Triangle).into_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = pixels.get_pixel(x, y)[0] > pixels.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | u64::from(brighter);
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Returns how many bits two hashes differ in.
+pub fn hamming_distance(first: u64, second: u64) -> u32 {
+    (first ^ second).count_ones()
+}
+
+/// Groups hashed items whose hashes are at most `threshold` bits apart, directly or through
+/// other items of the group. Only groups of two or more items are returned, each in the order
+/// the items were given and ordered by their first item.
+pub fn group_by_distance<T: Copy>(hashes: &[(T, u64)], threshold: u32) -> Vec<Vec<T>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for first in 0..hashes.len() {
+        for second in first + 1..hashes.len() {
+            if hamming_distance(hashes[first].1, hashes[second].1) <= threshold {
+                let first_root = root(&mut parents, first);
+                let second_root = root(&mut parents, second);
+                // the earlier item becomes the root so that groups keep the given order
+                parents[second_root.max(first_root)] = second_root.min(first_root);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<T>> = Vec::new();
+    let mut group_of_root = HashMap::new();
+    for (index, (item, _)) in hashes.iter().enumerate() {
+        let index_root = root(&mut parents, index);
+        let group = *group_of_root.entry(index_root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(*item);
+    }
+    groups.retain(|group| group.len() > 1);
+
+    groups
+}
+
+fn root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perceptual::{difference_hash, group_by_distance, hamming_distance};
+    use image::{GrayImage, Luma};
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// Saves a horizontal gradient, optionally reversed, at the given size.
+    fn save_gradient(path: &std::path::Path, width: u32, height: u32, reversed: bool) {
+        let image = GrayImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            Luma([if reversed { 255 - value } else { value }])
+        });
+        image.save(path).unwrap();
+    }
+
+    #[test]
+    fn ensure_resized_copies_have_close_hashes() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let original = dir.path().join("original.png");
+        let resized = dir.path().join("resized.bmp");
+        let reversed = dir.path().join("reversed.png");
+        save_gradient(&original, 360, 240, false);
+        save_gradient(&resized, 90, 60, false);
+        save_gradient(&reversed, 360, 240, true);
+
+        let original = difference_hash(&original).unwrap();
+        let resized = difference_hash(&resized).unwrap();
+        let reversed = difference_hash(&reversed).unwrap();
+
+        assert!(hamming_distance(original, resized) <= 2);
+        assert!(hamming_distance(original, reversed) > 32);
+    }
+
+    #[test]
+    fn ensure_undecodable_files_are_invalid_data() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("broken.jpg");
+        fs::write(&file, b"not an image").unwrap();
+
+        let actual_error = difference_hash(&file).unwrap_err();
+
+        assert_eq!(actual_error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn ensure_hashes_are_grouped_transitively() {
+        let hashes = [
+            (0, 0b0000),
+            (1, 0b1111_0000),
+            (2, 0b0011),
+            (3, 0b1111_0001),
+            (4, 0b0111),
+        ];
+
+        let groups = group_by_distance(&hashes, 1);
+
+        assert_eq!(groups, vec![vec![1, 3], vec![2, 4]]);
+        assert_eq!(
+            group_by_distance(&hashes, 2),
+            vec![vec![0, 2, 4], vec![1, 3]]
+        );
+    }
+}
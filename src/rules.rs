@@ -0,0 +1,642 @@
+//! Rules that sort files into folders automatically, so that only the files no rule matches are
+//! left to be sorted by hand.
+//!
+//! Rules can be shared as plain text files, which are described at
+//! [load_rules](crate::Backend::load_rules).
+
+use crate::exif;
+use crate::glob::Glob;
+use crate::metadata::Metadata;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Sorts the files that match all of its conditions into a folder.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    /// What a file has to match. A rule without conditions matches every file.
+    pub conditions: Vec<Condition>,
+    /// The folder matching files are moved into, which has to be one of the
+    /// [folders](crate::Backend::folders) to sort into.
+    pub destination: PathBuf,
+}
+
+/// Something a file has to match for a [Rule] to sort it.
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// The file name matches the glob.
+    Name(Glob),
+    /// The file name matches the regular expression somewhere, with the `regex` feature.
+    #[cfg(feature = "regex")]
+    NameRegex(regex::Regex),
+    /// The extension is one of these, compared case-insensitively and without the leading dot.
+    Extension(Vec<String>),
+    /// The size in bytes is within the bounds.
+    Size(Bounds<u64>),
+    /// The width of the image in pixels is within the bounds. Files that aren't images of a
+    /// known format never match.
+    Width(Bounds<u32>),
+    /// The height of the image in pixels is within the bounds, like the width.
+    Height(Bounds<u32>),
+    /// The camera model in the EXIF data is this one, compared case-insensitively.
+    CameraModel(String),
+    /// The photo was taken within the bounds, given as `YYYY-MM-DD` dates that are both
+    /// included. Files without a capture date are judged by when they were last modified.
+    Date(Bounds<String>),
+}
+
+/// An inclusive range where either end may be left open.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bounds<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: PartialOrd> Bounds<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        self.min.as_ref().is_none_or(|min| value >= min)
+            && self.max.as_ref().is_none_or(|max| value <= max)
+    }
+}
+
+/// A file that a rule would sort, as listed by a [dry run](crate::Backend::plan_rules).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposedMove {
+    /// The index of the file in the [files](crate::Backend::files).
+    pub index: usize,
+    pub file: PathBuf,
+    pub destination: PathBuf,
+}
+
+/// A problem with a rule file, pointing at the character where it was found.
+///
+/// Rules only conflict when they have the same conditions, in any order. Rules whose conditions
+/// merely overlap, such as `ext:png` and `name:*.png`, are both loaded, and a file goes to the
+/// first of them that it matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleError {
+    /// The line of the problem, starting at 1.
+    pub line: usize,
+    /// The character of the problem in its line, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl From<RuleError> for Error {
+    fn from(error: RuleError) -> Error {
+        Error::new(ErrorKind::InvalidData, error)
+    }
+}
+
+/// A rule read from a rule file, along with where its destination was written.
+pub(crate) struct ParsedRule {
+    pub rule: Rule,
+    pub line: usize,
+    pub destination_column: usize,
+}
+
+impl Rule {
+    /// Returns whether the file matches all of the rule's conditions.
+    ///
+    /// # Errors
+    ///
+    /// If a condition needs to read the file or its metadata and that fails, an error variant
+    /// will be returned.
+    pub fn matches(&self, file: &Path) -> Result<bool, Error> {
+        for condition in &self.conditions {
+            if !condition.matches(file)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl Condition {
+    /// Returns whether the file matches the condition.
+    ///
+    /// # Errors
+    ///
+    /// If the file or its metadata can't be read, an error variant will be returned.
+    pub fn matches(&self, file: &Path) -> Result<bool, Error> {
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let matches = match self {
+            Condition::Name(glob) => glob.matches(&name),
+            #[cfg(feature = "regex")]
+            Condition::NameRegex(regex) => regex.is_match(&name),
+            Condition::Extension(extensions) => match file.extension() {
+                Some(extension) => extensions.iter().any(|listed| {
+                    listed
+                        .trim_start_matches('.')
+                        .eq_ignore_ascii_case(&extension.to_string_lossy())
+                }),
+                None => false,
+            },
+            Condition::Size(bounds) => bounds.contains(&fs::metadata(file)?.len()),
+            Condition::Width(bounds) => Metadata::of_file(file)?
+                .dimensions
+                .is_some_and(|(width, _)| bounds.contains(&width)),
+            Condition::Height(bounds) => Metadata::of_file(file)?
+                .dimensions
+                .is_some_and(|(_, height)| bounds.contains(&height)),
+            Condition::CameraModel(model) => Metadata::of_file(file)?
+                .camera_model
+                .is_some_and(|camera_model| camera_model.eq_ignore_ascii_case(model.trim())),
+            Condition::Date(bounds) => {
+                let date = match Metadata::of_file(file)?.capture_date {
+                    Some(date) => date,
+                    None => exif::format_time(fs::metadata(file)?.modified()?),
+                };
+                // capture dates are written as `YYYY:MM:DD HH:MM:SS`, malformed ones match nothing
+                date.get(..10)
+                    .is_some_and(|day| bounds.contains(&day.replace(':', "-")))
+            }
+        };
+
+        Ok(matches)
+    }
+}
+
+/// Parses the rules of a rule file, resolving relative destinations against `base`.
+///
+/// Besides lines that can't be parsed, a rule is rejected if an earlier rule has the same
+/// conditions, since it could never sort a file.
+pub(crate) fn parse_rules(text: &str, base: &Path) -> Result<Vec<ParsedRule>, RuleError> {
+    let mut parsed_rules: Vec<ParsedRule> = Vec::new();
+    let mut written_conditions: Vec<Vec<String>> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut parser = LineParser {
+            characters: line.chars().collect(),
+            position: 0,
+            line: index + 1,
+        };
+        parser.skip_whitespace();
+        if parser.peek().is_none() || parser.peek() == Some('#') {
+            continue;
+        }
+        let start_column = parser.position + 1;
+
+        let mut conditions = Vec::new();
+        let mut written = Vec::new();
+        loop {
+            parser.skip_whitespace();
+            match parser.peek() {
+                None => return Err(parser.error("expected `->` and a destination folder")),
+                Some('-') if parser.characters.get(parser.position + 1) == Some(&'>') => {
+                    parser.position += 2;
+                    break;
+                }
+                Some(_) => {
+                    let (condition, text) = parser.condition()?;
+                    conditions.push(condition);
+                    written.push(text);
+                }
+            }
+        }
+        parser.skip_whitespace();
+        let destination_column = parser.position + 1;
+        let destination: String = parser.characters[parser.position..].iter().collect();
+        let destination = destination.trim_end();
+        if destination.is_empty() {
+            return Err(parser.error("expected a destination folder"));
+        }
+        let destination = base.join(destination);
+
+        written.sort();
+        if let Some(earlier) = written_conditions
+            .iter()
+            .position(|other| *other == written)
+        {
+            let earlier = &parsed_rules[earlier];
+            let message = match earlier.rule.destination == destination {
+                true => format!("repeats the rule on line {}", earlier.line),
+                false => format!(
+                    "has the same conditions as the rule on line {}, which sorts the files into {}",
+                    earlier.line,
+                    earlier.rule.destination.display()
+                ),
+            };
+            return Err(RuleError {
+                line: parser.line,
+                column: start_column,
+                message,
+            });
+        }
+        written_conditions.push(written);
+        parsed_rules.push(ParsedRule {
+            rule: Rule {
+                conditions,
+                destination,
+            },
+            line: parser.line,
+            destination_column,
+        });
+    }
+
+    Ok(parsed_rules)
+}
+
+struct LineParser {
+    characters: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl LineParser {
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> RuleError {
+        self.error_at(self.position, message.to_owned())
+    }
+
+    fn error_at(&self, position: usize, message: String) -> RuleError {
+        RuleError {
+            line: self.line,
+            column: position + 1,
+            message,
+        }
+    }
+
+    /// Parses a `key:value` condition and returns it along with how it was written.
+    fn condition(&mut self) -> Result<(Condition, String), RuleError> {
+        let key_position = self.position;
+        while self
+            .peek()
+            .is_some_and(|character| character.is_ascii_alphabetic())
+        {
+            self.position += 1;
+        }
+        let key: String = self.characters[key_position..self.position]
+            .iter()
+            .collect();
+        if key.is_empty() || self.peek() != Some(':') {
+            return Err(self.error_at(
+                key_position,
+                "expected a condition like `name:<glob>`".to_owned(),
+            ));
+        }
+        self.position += 1;
+        let value_position = self.position;
+        let value = self.value()?;
+        if value.is_empty() {
+            return Err(self.error_at(value_position, format!("`{}` needs a value", key)));
+        }
+        // the value itself starts after its opening quote, if it has one
+        let content_position = match self.characters[value_position] {
+            '"' => value_position + 1,
+            _ => value_position,
+        };
+        let invalid = |message: String| self.error_at(value_position, message);
+
+        let condition = match key.as_str() {
+            "name" => Condition::Name(Glob::parse(&value).map_err(|(position, message)| {
+                self.error_at(content_position + position, message.to_owned())
+            })?),
+            #[cfg(feature = "regex")]
+            "regex" => Condition::NameRegex(regex::Regex::new(&value).map_err(|error| {
+                let message = error.to_string();
+                let message = message.lines().last().unwrap_or_default().trim();
+                invalid(format!("invalid regular expression: {}", message))
+            })?),
+            #[cfg(not(feature = "regex"))]
+            "regex" => {
+                return Err(invalid(
+                    "regular expressions need the `regex` feature".to_owned(),
+                ))
+            }
+            "ext" => Condition::Extension(
+                value
+                    .split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.').to_owned())
+                    .filter(|extension| !extension.is_empty())
+                    .collect(),
+            ),
+            "size" => Condition::Size(parse_bounds(&value, parse_size).ok_or_else(|| {
+                invalid(format!(
+                    "`{}` is not a size like `10M` or a range of sizes",
+                    value
+                ))
+            })?),
+            "width" | "height" => {
+                let bounds =
+                    parse_bounds(&value, |value, _| value.parse().ok()).ok_or_else(|| {
+                        invalid(format!(
+                            "`{}` is not a number of pixels or a range of them",
+                            value
+                        ))
+                    })?;
+                match key.as_str() {
+                    "width" => Condition::Width(bounds),
+                    _ => Condition::Height(bounds),
+                }
+            }
+            "camera" => Condition::CameraModel(value.clone()),
+            "date" => Condition::Date(parse_bounds(&value, parse_date).ok_or_else(|| {
+                invalid(format!(
+                    "`{}` is not a date like `2024-07-31` or a range of dates",
+                    value
+                ))
+            })?),
+            _ => return Err(self.error_at(key_position, format!("unknown condition `{}`", key))),
+        };
+
+        Ok((condition, format!("{}:{}", key, value)))
+    }
+
+    /// Parses a value that ends at the next whitespace or, if it starts with a quote, at the
+    /// closing quote.
+    fn value(&mut self) -> Result<String, RuleError> {
+        let mut value = String::new();
+        if self.peek() != Some('"') {
+            while let Some(character) = self.peek().filter(|character| !character.is_whitespace()) {
+                value.push(character);
+                self.position += 1;
+            }
+            return Ok(value);
+        }
+
+        let opening_position = self.position;
+        self.position += 1;
+        loop {
+            match self.peek() {
+                Some('"') => break,
+                Some('\\') if self.position + 1 < self.characters.len() => {
+                    value.push(self.characters[self.position + 1]);
+                    self.position += 2;
+                }
+                Some(character) => {
+                    value.push(character);
+                    self.position += 1;
+                }
+                None => return Err(self.error_at(opening_position, "unclosed `\"`".to_owned())),
+            }
+        }
+        self.position += 1;
+
+        Ok(value)
+    }
+}
+
+/// Parses a single value or a range of them, where the parser is told whether the value is the
+/// end of the range.
+fn parse_bounds<T: PartialOrd>(
+    value: &str,
+    parse: impl Fn(&str, bool) -> Option<T>,
+) -> Option<Bounds<T>> {
+    let bounds = match value.split_once("..") {
+        Some(("", "")) => return None,
+        Some((min, max)) => Bounds {
+            min: match min.is_empty() {
+                true => None,
+                false => Some(parse(min, false)?),
+            },
+            max: match max.is_empty() {
+                true => None,
+                false => Some(parse(max, true)?),
+            },
+        },
+        None => Bounds {
+            min: Some(parse(value, false)?),
+            max: Some(parse(value, true)?),
+        },
+    };
+
+    match (&bounds.min, &bounds.max) {
+        (Some(min), Some(max)) if min > max => None,
+        _ => Some(bounds),
+    }
+}
+
+/// Parses a number of bytes with an optional `k`, `M` or `G` suffix, which may end in `B`.
+fn parse_size(value: &str, _: bool) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let value = value.strip_suffix('b').unwrap_or(&value);
+    let (number, unit) = match value.char_indices().last()? {
+        (index, 'k') => (&value[..index], 1 << 10),
+        (index, 'm') => (&value[..index], 1 << 20),
+        (index, 'g') => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Parses a `YYYY`, `YYYY-MM` or `YYYY-MM-DD` date into a full date, which is the first day it
+/// covers or, at the end of a range, the last one.
+fn parse_date(value: &str, is_end: bool) -> Option<String> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let is_number = |part: &str, length: usize, range: std::ops::RangeInclusive<u32>| {
+        part.len() == length
+            && part.chars().all(|character| character.is_ascii_digit())
+            && range.contains(&part.parse().unwrap_or_default())
+    };
+    let valid = match parts.as_slice() {
+        [year] => is_number(year, 4, 0..=9999),
+        [year, month] => is_number(year, 4, 0..=9999) && is_number(month, 2, 1..=12),
+        [year, month, day] => {
+            is_number(year, 4, 0..=9999) && is_number(month, 2, 1..=12) && is_number(day, 2, 1..=31)
+        }
+        _ => false,
+    };
+    if !valid {
+        return None;
+    }
+
+    // dates compare as text, so the last day of any month can be written as the 31st
+    let missing = match is_end {
+        true => &"-12-31"[(parts.len() - 1) * 3..],
+        false => &"-01-01"[(parts.len() - 1) * 3..],
+    };
+    Some(format!("{}{}", value, missing))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glob::Glob;
+    use crate::rules::{parse_rules, Bounds, Condition, Rule, RuleError};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_all_conditions_have_to_match() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let screenshot = dir.path().join("Screenshot 1.PNG");
+        let photo = dir.path().join("IMG_0001.jpg");
+        fs::write(&screenshot, "12345").unwrap();
+        fs::write(&photo, "12345").unwrap();
+        let rule = Rule {
+            conditions: vec![
+                Condition::Name(Glob::new("Screenshot *").unwrap()),
+                Condition::Extension(vec![".png".to_owned()]),
+                Condition::Size(Bounds {
+                    min: Some(5),
+                    max: None,
+                }),
+            ],
+            destination: PathBuf::from("Screenshots"),
+        };
+
+        assert!(rule.matches(&screenshot).unwrap());
+        assert!(!rule.matches(&photo).unwrap());
+        let too_large = Condition::Size(Bounds {
+            min: None,
+            max: Some(4),
+        });
+        assert!(!too_large.matches(&screenshot).unwrap());
+        assert!(Rule {
+            conditions: Vec::new(),
+            destination: PathBuf::from("Everything"),
+        }
+        .matches(&photo)
+        .unwrap());
+    }
+
+    fn parse_error(text: &str) -> RuleError {
+        match parse_rules(text, Path::new("/photos")) {
+            Ok(_) => panic!("expected `{}` to be rejected", text),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn ensure_rule_files_are_parsed() {
+        let text = "# comment\n\n  name:\"Screen shot *\" ext:.PNG,jpg -> Screenshots \n\
+                    size:10k..2M width:4000.. date:2024-03 -> /archive/Large\n\
+                    camera:\"Canon \\\"R5\\\"\" date:..2023 -> Canon";
+
+        let parsed_rules = parse_rules(text, Path::new("/photos")).unwrap();
+
+        assert_eq!(parsed_rules.len(), 3);
+        assert_eq!(parsed_rules[0].line, 3);
+        assert_eq!(parsed_rules[0].destination_column, 40);
+        let rule = &parsed_rules[0].rule;
+        assert_eq!(rule.destination, PathBuf::from("/photos/Screenshots"));
+        match &rule.conditions[..] {
+            [Condition::Name(glob), Condition::Extension(extensions)] => {
+                assert_eq!(glob.as_str(), "Screen shot *");
+                assert_eq!(extensions, &["PNG", "jpg"]);
+            }
+            conditions => panic!("unexpected conditions {:?}", conditions),
+        }
+        let rule = &parsed_rules[1].rule;
+        assert_eq!(rule.destination, PathBuf::from("/archive/Large"));
+        match &rule.conditions[..] {
+            [Condition::Size(size), Condition::Width(width), Condition::Date(date)] => {
+                assert_eq!((size.min, size.max), (Some(10 << 10), Some(2 << 20)));
+                assert_eq!((width.min, width.max), (Some(4000), None));
+                assert_eq!(date.min.as_deref(), Some("2024-03-01"));
+                assert_eq!(date.max.as_deref(), Some("2024-03-31"));
+            }
+            conditions => panic!("unexpected conditions {:?}", conditions),
+        }
+        match &parsed_rules[2].rule.conditions[..] {
+            [Condition::CameraModel(model), Condition::Date(date)] => {
+                assert_eq!(model, "Canon \"R5\"");
+                assert_eq!(
+                    (date.min.as_deref(), date.max.as_deref()),
+                    (None, Some("2023-12-31"))
+                );
+            }
+            conditions => panic!("unexpected conditions {:?}", conditions),
+        }
+    }
+
+    #[test]
+    fn ensure_bad_rules_are_reported_where_they_are() {
+        let error = parse_error("ext:png -> Images\n  colour:red -> Red");
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.message, "unknown condition `colour`");
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 3: unknown condition `colour`"
+        );
+
+        let error = parse_error("name:IMG_[0-9 -> Photos");
+        assert_eq!(
+            (error.line, error.column, error.message.as_str()),
+            (1, 10, "unclosed `[`")
+        );
+        let error = parse_error("size:10x -> Large");
+        assert_eq!((error.line, error.column), (1, 6));
+        assert_eq!(parse_error("date:2024-13 -> Later").column, 6);
+        assert_eq!(parse_error("width:9..1 -> Odd").column, 7);
+        assert_eq!(parse_error("camera:\"Canon -> Canon").column, 8);
+        assert_eq!(
+            parse_error("ext:png").message,
+            "expected `->` and a destination folder"
+        );
+        assert_eq!(parse_error("ext:png ->  ").column, 13);
+        assert_eq!(parse_error("png -> Images").column, 1);
+    }
+
+    #[test]
+    fn ensure_rules_with_the_same_conditions_are_rejected() {
+        let error = parse_error("ext:png size:1M.. -> Large\n size:1M.. ext:png -> Images");
+        assert_eq!((error.line, error.column), (2, 2));
+        assert_eq!(
+            error.message,
+            "has the same conditions as the rule on line 1, which sorts the files into /photos/Large"
+        );
+
+        let error = parse_error("ext:png -> Images\next:png -> /photos/Images");
+        assert_eq!(error.message, "repeats the rule on line 1");
+    }
+
+    #[test]
+    fn ensure_dates_fall_back_to_the_modification_time() {
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "").unwrap();
+        let this_year = crate::exif::format_time(std::time::SystemTime::now())[..4].to_owned();
+
+        let since_this_year = Condition::Date(Bounds {
+            min: Some(format!("{}-01-01", this_year)),
+            max: None,
+        });
+        let before_2000 = Condition::Date(Bounds {
+            min: None,
+            max: Some("1999-12-31".to_owned()),
+        });
+
+        assert!(since_this_year.matches(&file).unwrap());
+        assert!(!before_2000.matches(&file).unwrap());
+        assert!(!Condition::Width(Bounds::default()).matches(&file).unwrap());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn ensure_names_match_regular_expressions() {
+        let condition = Condition::NameRegex(regex::Regex::new(r"^IMG_\d{4}\.").unwrap());
+
+        assert!(condition
+            .matches(&PathBuf::from("photos/IMG_0001.jpg"))
+            .unwrap());
+        assert!(!condition.matches(&PathBuf::from("IMG_001.jpg")).unwrap());
+    }
+}
@@ -0,0 +1,1165 @@
+//! A small JSON-over-HTTP API around a [Backend], meant to be bound to localhost so that a
+//! browser frontend can drive the sorter.
+//!
+//! Connections are handled one after another on the calling thread, so all access to the single
+//! [Backend] is serialized without any locking. Every response closes its connection, and a
+//! client that is idle for ten seconds is dropped so that it can't hold up the others.
+//!
+//! | Route                  | Body                      | Response                          |
+//! |------------------------|---------------------------|-----------------------------------|
+//! | `GET /state`           |                           | the [state](state_json)           |
+//! | `GET /current`         |                           | the current file, its index, source and [format](FileFormat) |
+//! | `GET /folders`         |                           | the folders to sort into          |
+//! | `GET /current/content` |                           | the bytes of the current file     |
+//! | `GET /current/metadata` |                          | the [metadata](Metadata) of the current file |
+//! | `POST /move`           | `{"folder": <index>}` or `{"path": "<folder>"}` | the state and the `duplicate` |
+//! | `POST /move/template`  | `{"template": "<template>"}` | the state and the `duplicate` |
+//! | `POST /current/rename` | `{"name": "<name>"}` or `{"template": "<template>"}` | the state |
+//! | `POST /sort_into`      | `{"destinations": [{"folder": <index>, "mode": "<mode>"}, ...]}` | the state and the `outcomes` |
+//! | `POST /skip`           |                           | the state                         |
+//! | `POST /delete`         |                           | the state                         |
+//! | `POST /undo`           |                           | the state                         |
+//! | `POST /redo`           |                           | the state                         |
+//! | `POST /load`           | `{"directory": "<path>"}` | the state and the `excluded_count` |
+//! | `POST /load_external`  | `{"directory": "<path>"}` | the state                         |
+//! | `POST /folders`        | `{"path": "<folder>"}`    | the state                         |
+//! | `POST /folders/create` | `{"name": "<name>", "parent": "<folder>"}` | the state |
+//! | `POST /sources`        | `{"directory": "<path>"}` | the state and the `excluded_count` |
+//! | `POST /sources/remove` | `{"directory": "<path>"}` | the state                         |
+//! | `POST /transfer_mode`  | `{"mode": "<mode>"}`      | the state                         |
+//! | `POST /rules`          | `{"path": "<file>"}`      | the state                         |
+//! | `GET /rules/plan`      |                           | the `proposed` moves              |
+//! | `POST /rules/apply`    |                           | the state and the `outcomes`      |
+//! | `POST /sort`           | `{"key": "<key>", "descending": <bool>, "seed": <number>}` | the state |
+//! | `POST /near_duplicates` | `{"threshold": <number>}`, optionally | the `groups` of file indexes |
+//!
+//! Sort keys are named like [SortKey::as_str] names them. `/near_duplicates` is only served with
+//! the `perceptual` feature, see [Backend::near_duplicate_groups].
+//!
+//! `/move` moves, copies or links the current file depending on the [TransferMode], which is
+//! part of the state. Modes are named like [TransferMode::as_str] names them.
+//!
+//! `/move/template` moves the current file into the folder a [FolderTemplate] gives it, such as
+//! `{root}/{exif.year}/{exif.month}`, creating the folder if it is missing.
+//!
+//! `/current/rename` renames the current file, which stays the current one, either to the `name`
+//! or by a [NameTemplate] such as `{date}_{counter}{ext}`. See [Backend::rename_current].
+//!
+//! `/folders/create` creates a folder and adds it to the folders, see [Backend::create_folder].
+//! The `parent` defaults to the loaded directory.
+//!
+//! `/sort_into` sends the current file to several folders as one action, see
+//! [Backend::sort_into]. Each destination takes a `folder` index or a `path` like `/move` does,
+//! and a `mode` that defaults to the current one. There is an outcome for each destination, in
+//! the same order, such as `{"outcome": "moved", "path": "<file>"}`, where `moved` covers copies
+//! and links as well, `duplicate` gives the file with the same content as the `path` and
+//! `skipped` has none.
+//!
+//! The metadata has a member for each of its fields, which is `null` when the file doesn't have
+//! it. The `exposure_time` is written as a fraction such as `"1/250"`, the `gps` position has a
+//! `latitude`, `longitude` and `altitude` and the `dimensions` have a `width` and `height`.
+//!
+//! `/rules` loads a rule file, see [Backend::load_rules]. A proposed move is an object with the
+//! `index` and the `file` to be moved and its `destination`. Applying the rules has an outcome
+//! for each file that was proposed, like `/sort_into` has for each destination.
+//!
+//! The `duplicate` of a move is the file in the folder with the same content as the current
+//! file, which was then left where it is, or `null` otherwise. See
+//! [detect_duplicates](Backend::detect_duplicates).
+//!
+//! Since any web page can send requests to localhost, requests must name the server as `localhost`
+//! or `127.0.0.1` with its port in their `Host`, must not come from a page elsewhere by their
+//! `Origin`, and `POST` requests must have a `Content-Type` of `application/json`. This keeps
+//! other sites from moving or deleting files, directly or by rebinding their domain to localhost.
+//!
+//! Errors are returned as `{"error": "<message>"}` with a fitting status code. Reaching the end
+//! of the files is not treated as an error.
+
+use crate::json::Json;
+use crate::{
+    ActionDescription, Backend, FileFormat, FolderTemplate, Metadata, MoveOutcome, NameTemplate,
+    SortKey, SortOrder, TransferMode,
+};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MAX_BODY_LENGTH: usize = 1024 * 1024;
+/// The longest request line or header line that is read.
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+const MAX_HEADER_COUNT: usize = 100;
+/// How long a client may take to send its request or to take the response. Connections are
+/// handled one after another, so without it a single idle client would block every other one.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+enum Response {
+    Json(u16, Json),
+    File(PathBuf),
+}
+
+/// Serves requests from the listener until accepting a connection fails.
+///
+/// # Errors
+///
+/// If the listener fails to accept a connection, an error variant will be returned. Errors on
+/// a single connection only drop that connection.
+pub fn serve(listener: TcpListener, backend: &mut Backend) -> Result<(), Error> {
+    for stream in listener.incoming() {
+        // a client going away mid-request shouldn't take the server down with it
+        let _ = handle_connection(backend, stream?);
+    }
+
+    Ok(())
+}
+
+/// Reads a single request from the stream, performs it and writes the response.
+///
+/// # Errors
+///
+/// If there are any I/O errors reading the request or writing the response, an error variant
+/// will be returned.
+pub fn handle_connection(backend: &mut Backend, stream: TcpStream) -> Result<(), Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let port = stream.local_addr()?.port();
+    let response = match read_request(&mut reader) {
+        Ok(request) => match refuse_foreign(&request, port) {
+            Some(refusal) => refusal,
+            None => route(backend, &request),
+        },
+        Err(error) => error_response(400, &error.to_string()),
+    };
+
+    write_response(&stream, response)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, Error> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_owned());
+    let request_line = read_head_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+        _ => return Err(invalid("malformed request line")),
+    };
+
+    let mut content_length = 0;
+    let (mut host, mut origin, mut content_type) = (None, None, None);
+    for header_count in 0.. {
+        let header = read_head_line(reader)?;
+        if header.is_empty() {
+            return Err(invalid("unexpected end of headers"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if header_count == MAX_HEADER_COUNT {
+            return Err(invalid("too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {
+                    content_length = value
+                        .parse()
+                        .map_err(|_| invalid("invalid content length"))?
+                }
+                "host" => host = Some(value.to_owned()),
+                "origin" => origin = Some(value.to_owned()),
+                "content-type" => content_type = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+    }
+    if content_length > MAX_BODY_LENGTH {
+        return Err(invalid("request body is too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let path = path.split('?').next().unwrap_or_default().to_owned();
+
+    Ok(Request {
+        method,
+        path,
+        host,
+        origin,
+        content_type,
+        body,
+    })
+}
+
+/// Refuses a request that another site could have made, by its host, origin or content type.
+fn refuse_foreign(request: &Request, port: u16) -> Option<Response> {
+    let is_local = |host: &str| {
+        [format!("localhost:{}", port), format!("127.0.0.1:{}", port)]
+            .iter()
+            .any(|local| local.eq_ignore_ascii_case(host))
+    };
+    if !request.host.as_deref().is_some_and(is_local) {
+        return Some(error_response(403, "the host must be localhost"));
+    }
+    if let Some(origin) = &request.origin {
+        if !origin.strip_prefix("http://").is_some_and(is_local) {
+            return Some(error_response(
+                403,
+                "requests from other sites aren't allowed",
+            ));
+        }
+    }
+    let is_json = request.content_type.as_deref().is_some_and(|content_type| {
+        content_type
+            .split(';')
+            .next()
+            .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+    });
+    if request.method == "POST" && !is_json {
+        return Some(error_response(415, "expected an application/json body"));
+    }
+
+    None
+}
+
+/// Reads a line of the request line and headers, up to [MAX_LINE_LENGTH] bytes.
+fn read_head_line(reader: &mut impl BufRead) -> Result<String, Error> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE_LENGTH).read_line(&mut line)?;
+    if line.len() as u64 == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "request line is too long",
+        ));
+    }
+
+    Ok(line)
+}
+
+fn route(backend: &mut Backend, request: &Request) -> Response {
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/state") => return Response::Json(200, state_json(backend)),
+        ("GET", "/current") => {
+            return Response::Json(
+                200,
+                Json::object([
+                    ("index", Json::from(backend.current_file_index)),
+                    ("file", path_json(backend.get_current_file())),
+                    (
+                        "source",
+                        backend
+                            .get_current_file()
+                            .and_then(|file| backend.source_of(file))
+                            .map(|source| source.to_string_lossy().into_owned())
+                            .into(),
+                    ),
+                    (
+                        "format",
+                        backend
+                            .file_format(backend.current_file_index)
+                            .ok()
+                            .map(FileFormat::as_str)
+                            .into(),
+                    ),
+                ]),
+            )
+        }
+        ("GET", "/folders") => return Response::Json(200, folders_json(backend)),
+        ("GET", "/current/content") => {
+            return match backend.get_current_file() {
+                Some(file) => Response::File(file.clone()),
+                None => error_response(404, "there is no current file"),
+            }
+        }
+        ("GET", "/current/metadata") => {
+            if backend.get_current_file().is_none() {
+                return error_response(404, "there is no current file");
+            }
+            return match backend.metadata(backend.current_file_index) {
+                Ok(metadata) => Response::Json(200, metadata_json(&metadata)),
+                Err(error) => error_response(status_for(&error), &error.to_string()),
+            };
+        }
+        ("POST", "/move") => {
+            let result = folder_from_body(backend, &request.body)
+                .and_then(|folder| backend.sort_file(folder));
+            return move_response(backend, result);
+        }
+        ("POST", "/move/template") => {
+            let result = string_from_body(&request.body, "template")
+                .and_then(|template| FolderTemplate::new(&template))
+                .and_then(|template| backend.move_file_by_template(&template));
+            return move_response(backend, result);
+        }
+        ("POST", "/current/rename") => body_json(&request.body)
+            .and_then(|body| match body.get("template").and_then(Json::as_str) {
+                Some(template) => NameTemplate::new(template)
+                    .and_then(|template| backend.rename_current_by_template(&template)),
+                None => string_from_body(&request.body, "name")
+                    .and_then(|name| backend.rename_current(&name)),
+            })
+            .map(|_| ()),
+        ("POST", "/sort_into") => {
+            let result = destinations_from_body(backend, &request.body)
+                .and_then(|destinations| backend.sort_into(&destinations));
+            return outcomes_response(backend, result);
+        }
+        ("POST", "/rules") => {
+            string_from_body(&request.body, "path").and_then(|path| backend.load_rules(path))
+        }
+        ("GET", "/rules/plan") => {
+            return match backend.plan_rules() {
+                Ok(proposed_moves) => {
+                    let proposed_moves = proposed_moves
+                        .iter()
+                        .map(|proposed| {
+                            Json::object([
+                                ("index", Json::from(proposed.index)),
+                                ("file", path_json(Some(&proposed.file))),
+                                ("destination", path_json(Some(&proposed.destination))),
+                            ])
+                        })
+                        .collect();
+                    Response::Json(
+                        200,
+                        Json::object([("proposed", Json::Array(proposed_moves))]),
+                    )
+                }
+                Err(error) => error_response(status_for(&error), &error.to_string()),
+            };
+        }
+        ("POST", "/rules/apply") => {
+            let result = backend
+                .apply_rules()
+                .map(|applied| applied.into_iter().map(|(_, outcome)| outcome).collect());
+            return outcomes_response(backend, result);
+        }
+        ("POST", "/skip") => backend.skip(),
+        ("POST", "/delete") => backend.delete_file(),
+        ("POST", "/undo") => backend.undo(),
+        ("POST", "/redo") => backend.redo(),
+        ("POST", "/load") => {
+            let result = string_from_body(&request.body, "directory")
+                .and_then(|directory| backend.load_folders_and_files(directory));
+            return loading_response(backend, result);
+        }
+        ("POST", "/sources") => {
+            let result = string_from_body(&request.body, "directory")
+                .and_then(|directory| backend.add_source(directory));
+            return loading_response(backend, result);
+        }
+        ("POST", "/sources/remove") => string_from_body(&request.body, "directory")
+            .and_then(|directory| backend.remove_source(directory)),
+        ("POST", "/transfer_mode") => string_from_body(&request.body, "mode").and_then(|mode| {
+            backend.transfer_mode = TransferMode::from_name(&mode)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "expected a known \"mode\""))?;
+            Ok(())
+        }),
+        ("POST", "/sort") => {
+            sort_order_from_body(&request.body).and_then(|order| backend.sort_files(order))
+        }
+        ("POST", "/folders/create") => string_from_body(&request.body, "name").and_then(|name| {
+            let parent = match body_json(&request.body)?.get("parent") {
+                Some(parent) => parent.as_str().map(PathBuf::from).ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "expected a \"parent\" string")
+                })?,
+                None => PathBuf::from(backend.pwd.trim()),
+            };
+            backend.create_folder(parent, &name).map(|_| ())
+        }),
+        ("POST", "/load_external") => string_from_body(&request.body, "directory")
+            .and_then(|directory| backend.load_external_folders(directory)),
+        ("POST", "/folders") => {
+            string_from_body(&request.body, "path").and_then(|folder| backend.add_folder(folder))
+        }
+        #[cfg(feature = "perceptual")]
+        ("POST", "/near_duplicates") => {
+            let body = match request.body.is_empty() {
+                true => Ok(Json::Null),
+                false => body_json(&request.body),
+            };
+            return match body {
+                Ok(body) => Response::Json(200, near_duplicates_json(backend, &body)),
+                Err(error) => error_response(status_for(&error), &error.to_string()),
+            };
+        }
+        #[cfg(feature = "perceptual")]
+        (_, "/near_duplicates") => return error_response(405, "method not allowed"),
+        (
+            _,
+            "/state" | "/current" | "/folders" | "/current/content" | "/current/metadata" | "/move"
+            | "/move/template" | "/sort_into" | "/skip" | "/delete" | "/undo" | "/redo" | "/load"
+            | "/load_external" | "/sources" | "/sources/remove" | "/folders/create" | "/sort"
+            | "/transfer_mode" | "/current/rename" | "/rules" | "/rules/plan" | "/rules/apply",
+        ) => return error_response(405, "method not allowed"),
+        _ => return error_response(404, "no such route"),
+    };
+
+    match result {
+        Ok(()) => Response::Json(200, state_json(backend)),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => {
+            Response::Json(200, state_json(backend))
+        }
+        Err(error) => error_response(status_for(&error), &error.to_string()),
+    }
+}
+
+/// Responds with the state and the number of files excluded while loading.
+fn loading_response(backend: &Backend, result: Result<usize, Error>) -> Response {
+    match result {
+        Ok(excluded_count) => {
+            let mut state = state_json(backend);
+            if let Json::Object(members) = &mut state {
+                members.push(("excluded_count".to_owned(), excluded_count.into()));
+            }
+            Response::Json(200, state)
+        }
+        Err(error) => error_response(status_for(&error), &error.to_string()),
+    }
+}
+
+/// Responds with the state and the file the current file duplicates, if it wasn't moved for it.
+fn move_response(backend: &Backend, result: Result<MoveOutcome, Error>) -> Response {
+    let duplicate = match result {
+        Ok(MoveOutcome::Duplicate(duplicate)) => Some(duplicate),
+        Ok(_) => None,
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => None,
+        Err(error) => return error_response(status_for(&error), &error.to_string()),
+    };
+    let mut state = state_json(backend);
+    if let Json::Object(members) = &mut state {
+        members.push(("duplicate".to_owned(), path_json(duplicate.as_ref())));
+    }
+
+    Response::Json(200, state)
+}
+
+/// Responds with the state and an outcome for each file or destination that was sorted.
+fn outcomes_response(backend: &Backend, result: Result<Vec<MoveOutcome>, Error>) -> Response {
+    match result {
+        Ok(outcomes) => {
+            let mut state = state_json(backend);
+            if let Json::Object(members) = &mut state {
+                let outcomes = outcomes.iter().map(outcome_json).collect();
+                members.push(("outcomes".to_owned(), Json::Array(outcomes)));
+            }
+            Response::Json(200, state)
+        }
+        Err(error) => error_response(status_for(&error), &error.to_string()),
+    }
+}
+
+fn outcome_json(outcome: &MoveOutcome) -> Json {
+    let (name, path) = match outcome {
+        MoveOutcome::Moved(path) => ("moved", Some(path)),
+        MoveOutcome::Skipped => ("skipped", None),
+        MoveOutcome::Duplicate(path) => ("duplicate", Some(path)),
+    };
+
+    Json::object([("outcome", Json::from(name)), ("path", path_json(path))])
+}
+
+fn metadata_json(metadata: &Metadata) -> Json {
+    let text = |text: &Option<String>| Json::from(text.clone());
+    let number = |number: Option<f64>| Json::from(number);
+
+    Json::object([
+        ("capture_date", text(&metadata.capture_date)),
+        ("camera_make", text(&metadata.camera_make)),
+        ("camera_model", text(&metadata.camera_model)),
+        ("lens_model", text(&metadata.lens_model)),
+        (
+            "exposure_time",
+            metadata
+                .exposure_time
+                .map(|(numerator, denominator)| format!("{}/{}", numerator, denominator))
+                .into(),
+        ),
+        ("f_number", number(metadata.f_number)),
+        ("iso", number(metadata.iso.map(f64::from))),
+        ("focal_length", number(metadata.focal_length)),
+        ("orientation", number(metadata.orientation.map(f64::from))),
+        (
+            "gps",
+            metadata
+                .gps
+                .map(|gps| {
+                    Json::object([
+                        ("latitude", Json::from(gps.latitude)),
+                        ("longitude", Json::from(gps.longitude)),
+                        ("altitude", Json::from(gps.altitude)),
+                    ])
+                })
+                .into(),
+        ),
+        (
+            "dimensions",
+            metadata
+                .dimensions
+                .map(|(width, height)| {
+                    Json::object([
+                        ("width", Json::from(f64::from(width))),
+                        ("height", Json::from(f64::from(height))),
+                    ])
+                })
+                .into(),
+        ),
+    ])
+}
+
+fn body_json(body: &[u8]) -> Result<Json, Error> {
+    let text = std::str::from_utf8(body).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+    Json::parse(text)
+}
+
+fn string_from_body(body: &[u8], key: &str) -> Result<String, Error> {
+    match body_json(body)?.get(key).and_then(Json::as_str) {
+        Some(value) => Ok(value.to_owned()),
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("expected a \"{}\" string", key),
+        )),
+    }
+}
+
+fn folder_from_body(backend: &Backend, body: &[u8]) -> Result<PathBuf, Error> {
+    folder_from_json(backend, &body_json(body)?)
+}
+
+fn folder_from_json(backend: &Backend, body: &Json) -> Result<PathBuf, Error> {
+    if let Some(path) = body.get("path").and_then(Json::as_str) {
+        return Ok(PathBuf::from(path));
+    }
+    let index = body.get("folder").and_then(Json::as_f64).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "expected a \"folder\" index or a \"path\"",
+        )
+    })?;
+
+    match backend.folders.get(index as usize) {
+        Some(folder) if index >= 0.0 && index.fract() == 0.0 => Ok(folder.clone()),
+        _ => Err(Error::new(ErrorKind::NotFound, "there is no such folder")),
+    }
+}
+
+fn destinations_from_body(
+    backend: &Backend,
+    body: &[u8],
+) -> Result<Vec<(PathBuf, TransferMode)>, Error> {
+    let destinations = match body_json(body)?.get("destinations") {
+        Some(Json::Array(destinations)) => destinations.clone(),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "expected a \"destinations\" array",
+            ))
+        }
+    };
+
+    destinations
+        .iter()
+        .map(|destination| {
+            let transfer_mode = match destination.get("mode").and_then(Json::as_str) {
+                Some(mode) => TransferMode::from_name(mode).ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "expected a known \"mode\"")
+                })?,
+                None => backend.transfer_mode,
+            };
+            Ok((folder_from_json(backend, destination)?, transfer_mode))
+        })
+        .collect()
+}
+
+fn sort_order_from_body(body: &[u8]) -> Result<SortOrder, Error> {
+    let body = body_json(body)?;
+    let seed = body.get("seed").and_then(Json::as_f64).unwrap_or_default();
+    let key = body
+        .get("key")
+        .and_then(Json::as_str)
+        .and_then(|name| SortKey::from_name(name, seed as u64))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "expected a known \"key\""))?;
+
+    Ok(SortOrder {
+        key,
+        descending: body.get("descending") == Some(&Json::Bool(true)),
+    })
+}
+
+/// Returns the groups of files left to sort that look alike, as arrays of file indexes.
+#[cfg(feature = "perceptual")]
+fn near_duplicates_json(backend: &Backend, body: &Json) -> Json {
+    let threshold = body
+        .get("threshold")
+        .and_then(Json::as_f64)
+        .map_or(crate::NEAR_DUPLICATE_THRESHOLD, |threshold| {
+            threshold as u32
+        });
+    let groups = backend
+        .near_duplicate_groups(threshold)
+        .into_iter()
+        .map(|group| Json::Array(group.into_iter().map(Json::from).collect()))
+        .collect();
+
+    Json::object([("groups", Json::Array(groups))])
+}
+
+fn status_for(error: &Error) -> u16 {
+    match error.kind() {
+        ErrorKind::NotFound => 404,
+        ErrorKind::AlreadyExists => 409,
+        ErrorKind::InvalidInput | ErrorKind::InvalidData => 400,
+        ErrorKind::PermissionDenied => 403,
+        _ => 500,
+    }
+}
+
+fn error_response(status: u16, message: &str) -> Response {
+    Response::Json(status, Json::object([("error", Json::from(message))]))
+}
+
+fn path_json(path: Option<&PathBuf>) -> Json {
+    path.map(|path| path.to_string_lossy().into_owned()).into()
+}
+
+fn folders_json(backend: &Backend) -> Json {
+    Json::Array(
+        backend
+            .folders
+            .iter()
+            .map(|folder| path_json(Some(folder)))
+            .collect(),
+    )
+}
+
+fn history_json(history: Vec<ActionDescription>) -> Json {
+    Json::Array(
+        history
+            .into_iter()
+            .map(|description| {
+                Json::object([
+                    ("kind", Json::from(description.kind.as_str())),
+                    ("source", path_json(Some(&description.source))),
+                    ("destination", path_json(description.destination.as_ref())),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Returns the backend's state as a JSON object with the current directory, the source
+/// directories, the current file and its index, the number of files, the folders, the transfer
+/// mode and the undo and redo history.
+pub fn state_json(backend: &Backend) -> Json {
+    Json::object([
+        ("pwd", Json::from(backend.pwd.as_str())),
+        (
+            "sources",
+            Json::Array(
+                backend
+                    .sources
+                    .iter()
+                    .map(|source| path_json(Some(source)))
+                    .collect(),
+            ),
+        ),
+        ("current_file_index", Json::from(backend.current_file_index)),
+        ("current_file", path_json(backend.get_current_file())),
+        ("file_count", Json::from(backend.file_count())),
+        ("folders", folders_json(backend)),
+        ("transfer_mode", Json::from(backend.transfer_mode.as_str())),
+        ("undo_history", history_json(backend.undo_history())),
+        ("redo_history", history_json(backend.redo_history())),
+    ])
+}
+
+/// Returns the content type of a file from its content, falling back to its extension for
+/// formats that aren't detected.
+fn content_type(file: &Path) -> &'static str {
+    match FileFormat::of_file(file) {
+        Ok(FileFormat::Unknown) | Err(_) => match file
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("svg") => "image/svg+xml",
+            _ => FileFormat::from_path(file).mime_type(),
+        },
+        Ok(format) => format.mime_type(),
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        415 => "Unsupported Media Type",
+        _ => "Internal Server Error",
+    }
+}
+
+fn write_head(
+    mut stream: &TcpStream,
+    status: u16,
+    content_type: &str,
+    content_length: u64,
+) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason_phrase(status),
+        content_type,
+        content_length
+    )
+}
+
+fn write_response(mut stream: &TcpStream, response: Response) -> Result<(), Error> {
+    let (status, body) = match response {
+        Response::File(path) => match File::open(&path) {
+            Ok(mut file) => {
+                let length = file.metadata()?.len();
+                write_head(stream, 200, content_type(&path), length)?;
+                io::copy(&mut file, &mut stream)?;
+                return stream.flush();
+            }
+            Err(error) => (status_for(&error), error_json(&error)),
+        },
+        Response::Json(status, body) => (status, body),
+    };
+
+    let body = body.to_string();
+    write_head(
+        stream,
+        status,
+        "application/json; charset=utf-8",
+        body.len() as u64,
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+fn error_json(error: &Error) -> Json {
+    Json::object([("error", Json::from(error.to_string()))])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::server::{read_request, serve};
+    use crate::Backend;
+    use std::fs::{self, File};
+    use std::io::{Cursor, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::Path;
+    use std::sync::mpsc;
+    use std::thread;
+    use tempdir::TempDir;
+
+    /// Starts a server on a free local port in the background and returns its address.
+    fn start_server(directory: &Path) -> String {
+        let directory = directory.to_string_lossy().into_owned();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            sender.send(listener.local_addr().unwrap()).unwrap();
+            let mut backend = Backend::new();
+            backend.load_folders_and_files(directory).unwrap();
+            serve(listener, &mut backend).unwrap();
+        });
+
+        receiver.recv().unwrap().to_string()
+    }
+
+    fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            address,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let head_end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8(response[..head_end].to_vec()).unwrap();
+        let status = head[9..12].parse().unwrap();
+        (status, head, response[head_end + 4..].to_vec())
+    }
+
+    fn request_json(address: &str, method: &str, path: &str, body: &str) -> (u16, Json) {
+        let (status, _, body) = request(address, method, path, body);
+        (
+            status,
+            Json::parse(&String::from_utf8(body).unwrap()).unwrap(),
+        )
+    }
+
+    fn build_directory() -> TempDir {
+        let dir = TempDir::new("unit_test").unwrap();
+        fs::create_dir(dir.path().join("folder1")).unwrap();
+        fs::write(dir.path().join("file1.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        File::create(dir.path().join("file2.jpg")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn ensure_oversized_request_heads_are_rejected() {
+        let long_header = format!("GET /state HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(10_000));
+        let many_headers = format!("GET /state HTTP/1.1\r\n{}\r\n", "X: a\r\n".repeat(101));
+        let fine = "GET /state?x HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let error = read_request(&mut Cursor::new(long_header)).unwrap_err();
+        assert_eq!(error.to_string(), "request line is too long");
+        let error = read_request(&mut Cursor::new(many_headers)).unwrap_err();
+        assert_eq!(error.to_string(), "too many headers");
+        assert_eq!(read_request(&mut Cursor::new(fine)).unwrap().path, "/state");
+    }
+
+    #[test]
+    fn ensure_requests_other_sites_could_make_are_refused() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+        let port = address.rsplit(':').next().unwrap();
+        let send = |head: String| {
+            let mut stream = TcpStream::connect(&address).unwrap();
+            write!(stream, "{}Content-Length: 0\r\n\r\n", head).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response[9..12].parse::<u16>().unwrap()
+        };
+
+        let rebound = send("POST /skip HTTP/1.1\r\nHost: evil.example\r\n".to_owned());
+        let no_host = send("GET /state HTTP/1.1\r\n".to_owned());
+        let cross_site = send(format!(
+            "POST /skip HTTP/1.1\r\nHost: localhost:{}\r\nOrigin: https://evil.example\r\n\
+             Content-Type: application/json\r\n",
+            port
+        ));
+        let form = send(format!(
+            "POST /skip HTTP/1.1\r\nHost: localhost:{}\r\nContent-Type: text/plain\r\n",
+            port
+        ));
+        let same_site = send(format!(
+            "POST /skip HTTP/1.1\r\nHost: localhost:{0}\r\nOrigin: http://localhost:{0}\r\n\
+             Content-Type: application/json; charset=utf-8\r\n",
+            port
+        ));
+
+        assert_eq!((rebound, no_host, cross_site, form), (403, 403, 403, 415));
+        assert_eq!(same_site, 200);
+        assert!(dir.path().join("file1.png").exists());
+    }
+
+    #[test]
+    fn ensure_state_and_folders_are_returned() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let (status, state) = request_json(&address, "GET", "/state", "");
+        let (_, folders) = request_json(&address, "GET", "/folders", "");
+
+        assert_eq!(status, 200);
+        assert_eq!(state.get("file_count"), Some(&Json::from(2)));
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(0)));
+        let expected_folder = dir.path().join("folder1").to_string_lossy().into_owned();
+        assert_eq!(folders, Json::Array(vec![Json::from(expected_folder)]));
+    }
+
+    #[test]
+    fn ensure_current_file_is_streamed_with_its_content_type() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let (status, head, body) = request(&address, "GET", "/current/content", "");
+
+        assert_eq!(status, 200);
+        assert!(head.contains("Content-Type: image/png"));
+        assert_eq!(body, b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn ensure_metadata_of_current_file_is_returned() {
+        let dir = build_directory();
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 2, 128, 0, 0, 1, 224]);
+        fs::write(dir.path().join("file1.png"), png).unwrap();
+        let address = start_server(dir.path());
+
+        let (status, metadata) = request_json(&address, "GET", "/current/metadata", "");
+
+        assert_eq!(status, 200);
+        assert_eq!(metadata.get("capture_date"), Some(&Json::Null));
+        let dimensions = metadata.get("dimensions").unwrap();
+        assert_eq!(dimensions.get("width"), Some(&Json::from(640)));
+        assert_eq!(dimensions.get("height"), Some(&Json::from(480)));
+    }
+
+    #[test]
+    fn ensure_file_is_moved_and_move_is_undone() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let (status, state) = request_json(&address, "POST", "/move", r#"{"folder": 0}"#);
+        assert_eq!(status, 200);
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(1)));
+        assert_eq!(state.get("duplicate"), Some(&Json::Null));
+        assert!(dir.path().join("folder1/file1.png").exists());
+
+        let (status, state) = request_json(&address, "POST", "/undo", "");
+        assert_eq!(status, 200);
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(0)));
+        assert!(dir.path().join("file1.png").exists());
+    }
+
+    #[test]
+    fn ensure_file_is_moved_by_template_and_folders_are_removed_by_undo() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let body = r#"{"template": "sorted/{exif.year}"}"#;
+        let (status, state) = request_json(&address, "POST", "/move/template", body);
+        assert_eq!(status, 200);
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(1)));
+        let year_folder = fs::read_dir(dir.path().join("sorted"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        assert!(year_folder.join("file1.png").exists());
+
+        let (status, _) = request_json(&address, "POST", "/undo", "");
+        assert_eq!(status, 200);
+        assert!(!dir.path().join("sorted").exists());
+        let body = r#"{"template": "{exif.week}"}"#;
+        let (status, _) = request_json(&address, "POST", "/move/template", body);
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn ensure_current_file_is_renamed_by_name_or_template() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let body = r#"{"name": "logo.png"}"#;
+        let (status, state) = request_json(&address, "POST", "/current/rename", body);
+        let renamed = dir.path().join("logo.png");
+        assert_eq!(status, 200);
+        assert!(renamed.exists());
+        assert_eq!(
+            state.get("current_file"),
+            Some(&Json::from(renamed.to_string_lossy().into_owned()))
+        );
+
+        let body = r#"{"template": "{name}_{counter:2}{ext}"}"#;
+        let (status, _) = request_json(&address, "POST", "/current/rename", body);
+        assert_eq!(status, 200);
+        assert!(dir.path().join("logo_01.png").exists());
+        let body = r#"{"name": "file2.jpg"}"#;
+        let (status, _) = request_json(&address, "POST", "/current/rename", body);
+        assert_eq!(status, 409);
+    }
+
+    #[test]
+    fn ensure_folder_is_created_and_creating_it_is_undone() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let body = r#"{"name": "Holidays"}"#;
+        let (status, _) = request_json(&address, "POST", "/folders/create", body);
+        let (_, folders) = request_json(&address, "GET", "/folders", "");
+        assert_eq!(status, 200);
+        assert!(dir.path().join("Holidays").is_dir());
+        let expected_folder = dir.path().join("Holidays").to_string_lossy().into_owned();
+        assert!(
+            matches!(folders, Json::Array(folders) if folders.contains(&expected_folder.into()))
+        );
+
+        let (status, _) = request_json(&address, "POST", "/undo", "");
+        assert_eq!(status, 200);
+        assert!(!dir.path().join("Holidays").exists());
+        let body = r#"{"name": "a|b"}"#;
+        let (status, error) = request_json(&address, "POST", "/folders/create", body);
+        assert_eq!(status, 400);
+        assert!(error.get("error").is_some());
+    }
+
+    #[test]
+    fn ensure_file_is_copied_in_copy_mode() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let body = r#"{"mode": "copy"}"#;
+        let (status, state) = request_json(&address, "POST", "/transfer_mode", body);
+        assert_eq!(status, 200);
+        assert_eq!(state.get("transfer_mode"), Some(&Json::from("copy")));
+
+        let (status, _) = request_json(&address, "POST", "/move", r#"{"folder": 0}"#);
+        assert_eq!(status, 200);
+        assert!(dir.path().join("file1.png").exists());
+        assert!(dir.path().join("folder1/file1.png").exists());
+
+        let body = r#"{"mode": "teleport"}"#;
+        let (status, _) = request_json(&address, "POST", "/transfer_mode", body);
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn ensure_file_is_sent_to_several_folders_and_undone_at_once() {
+        let dir = build_directory();
+        fs::create_dir(dir.path().join("folder2")).unwrap();
+        let address = start_server(dir.path());
+
+        let folder = Json::from(dir.path().join("folder2").to_str().unwrap());
+        let body = Json::object([(
+            "destinations",
+            Json::Array(vec![
+                Json::object([("path", folder)]),
+                Json::object([("folder", Json::from(0)), ("mode", Json::from("copy"))]),
+            ]),
+        )]);
+        let (status, state) = request_json(&address, "POST", "/sort_into", &body.to_string());
+        assert_eq!(status, 200);
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(1)));
+        let outcomes = match state.get("outcomes") {
+            Some(Json::Array(outcomes)) => outcomes.clone(),
+            outcomes => panic!("unexpected outcomes {:?}", outcomes),
+        };
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[1].get("outcome"), Some(&Json::from("moved")));
+        assert!(dir.path().join("folder1/file1.png").exists());
+        assert!(dir.path().join("folder2/file1.png").exists());
+        assert!(!dir.path().join("file1.png").exists());
+
+        let (status, state) = request_json(&address, "POST", "/undo", "");
+        assert_eq!(status, 200);
+        assert_eq!(state.get("current_file_index"), Some(&Json::from(0)));
+        assert!(dir.path().join("file1.png").exists());
+        assert!(!dir.path().join("folder1/file1.png").exists());
+        assert!(!dir.path().join("folder2/file1.png").exists());
+
+        let body = r#"{"destinations": [{"folder": 0}, {"folder": 0}]}"#;
+        let (status, _) = request_json(&address, "POST", "/sort_into", body);
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn ensure_rules_are_loaded_planned_and_applied() {
+        let dir = build_directory();
+        let rules_file = dir.path().join("team.rules");
+        fs::write(&rules_file, "ext:png -> folder1\n").unwrap();
+        let address = start_server(dir.path());
+
+        let body = Json::object([("path", Json::from(rules_file.to_str().unwrap()))]);
+        let (status, _) = request_json(&address, "POST", "/rules", &body.to_string());
+        assert_eq!(status, 200);
+        let (status, plan) = request_json(&address, "GET", "/rules/plan", "");
+        assert_eq!(status, 200);
+        match plan.get("proposed") {
+            Some(Json::Array(proposed)) => {
+                assert_eq!(proposed.len(), 1);
+                assert_eq!(proposed[0].get("index"), Some(&Json::from(0)));
+            }
+            proposed => panic!("unexpected proposed moves {:?}", proposed),
+        }
+
+        let (status, _) = request_json(&address, "POST", "/rules/apply", "");
+        assert_eq!(status, 200);
+        assert!(dir.path().join("folder1/file1.png").exists());
+
+        let (status, _) = request_json(&address, "GET", "/rules", "");
+        assert_eq!(status, 405);
+        let (status, _) = request_json(&address, "POST", "/rules/plan", "");
+        assert_eq!(status, 405);
+        let (status, _) = request_json(&address, "GET", "/rules/apply", "");
+        assert_eq!(status, 405);
+
+        fs::write(&rules_file, "ext:png -> missing\n").unwrap();
+        let (status, error) = request_json(&address, "POST", "/rules", &body.to_string());
+        assert_eq!(status, 404);
+        let message = error.get("error").and_then(Json::as_str).unwrap();
+        assert!(message.starts_with("line 1, column 12: "));
+    }
+
+    #[test]
+    fn ensure_sources_are_added_and_removed() {
+        let dir = build_directory();
+        let other = TempDir::new("unit_test").unwrap();
+        File::create(other.path().join("file3.jpg")).unwrap();
+        let address = start_server(dir.path());
+        let body = Json::object([("directory", Json::from(other.path().to_str().unwrap()))]);
+
+        let (status, state) = request_json(&address, "POST", "/sources", &body.to_string());
+        assert_eq!(status, 200);
+        assert_eq!(state.get("file_count"), Some(&Json::from(3)));
+        assert_eq!(state.get("excluded_count"), Some(&Json::from(0)));
+
+        let (status, state) = request_json(&address, "POST", "/sources/remove", &body.to_string());
+        assert_eq!(status, 200);
+        assert_eq!(state.get("file_count"), Some(&Json::from(2)));
+    }
+
+    #[test]
+    fn ensure_files_left_are_sorted() {
+        let dir = build_directory();
+        File::create(dir.path().join("file0.gif")).unwrap();
+        let address = start_server(dir.path());
+
+        let (status, state) = request_json(
+            &address,
+            "POST",
+            "/sort",
+            r#"{"key": "name", "descending": true}"#,
+        );
+        let (_, error) = request_json(&address, "POST", "/sort", r#"{"key": "colour"}"#);
+
+        assert_eq!(status, 200);
+        let expected_file = dir.path().join("file0.gif").to_string_lossy().into_owned();
+        assert_eq!(state.get("current_file"), Some(&Json::from(expected_file)));
+        assert!(error.get("error").is_some());
+    }
+
+    #[test]
+    fn ensure_errors_are_reported_with_status_codes() {
+        let dir = build_directory();
+        let address = start_server(dir.path());
+
+        let (status, error) = request_json(&address, "POST", "/move", r#"{"folder": 5}"#);
+        assert_eq!(status, 404);
+        assert!(error.get("error").is_some());
+
+        let (status, _) = request_json(&address, "POST", "/load", "not json");
+        assert_eq!(status, 400);
+
+        let (status, _) = request_json(&address, "GET", "/skip", "");
+        assert_eq!(status, 405);
+
+        let (status, _) = request_json(&address, "GET", "/nothing", "");
+        assert_eq!(status, 404);
+    }
+}
@@ -0,0 +1,372 @@
+//! Templates for the folders files are sorted into and for new names of files, resolved for each
+//! file from when it was taken, so that a photo library can be kept in folders such as `2024/07`
+//! with names such as `2024-07-04_1.jpg`.
+//!
+//! A folder template is a path with placeholders in braces, such as
+//! `{root}/{exif.year}/{exif.month}`:
+//! - `{root}` is the directory the files were loaded from, which relative templates start from
+//!   as well. It can only start a template, and folders always have to end up inside of it
+//! - `{exif.year}`, `{exif.month}` and `{exif.day}` are parts of the capture date, with the
+//!   month and day written as two digits
+//! - `{exif.date}` is the whole capture date as `YYYY-MM-DD`
+//!
+//! A name template is a file name with placeholders, such as `{date}_{counter}{ext}`:
+//! - `{name}` is the current name of the file without its extension
+//! - `{ext}` is the extension of the file along with its dot, or nothing if it has none
+//! - `{date}` is the capture date as `YYYY-MM-DD`
+//! - `{counter}` is the lowest number from 1 up that gives a name no other file in the folder
+//!   has, and `{counter:3}` pads it with zeros to at least three digits
+//!
+//! Files without a capture date use their modification time instead. Braces themselves are
+//! written as `{{` and `}}`.
+
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+/// A compiled folder template that files can be sorted by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderTemplate {
+    template: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Root,
+    Year,
+    Month,
+    Day,
+    Date,
+}
+
+impl FolderTemplate {
+    /// Compiles a template.
+    ///
+    /// # Errors
+    ///
+    /// If the template has an unknown placeholder, a brace that isn't part of one or `{root}`
+    /// anywhere but at its start, an [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub fn new(template: &str) -> Result<FolderTemplate, Error> {
+        let tokens = compile(template, |name| match name {
+            "root" => Some(Token::Root),
+            "exif.year" => Some(Token::Year),
+            "exif.month" => Some(Token::Month),
+            "exif.day" => Some(Token::Day),
+            "exif.date" => Some(Token::Date),
+            _ => None,
+        })?;
+        if tokens.iter().skip(1).any(|token| *token == Token::Root) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{{root}}` can only start the template in `{}`", template),
+            ));
+        }
+
+        Ok(FolderTemplate {
+            template: template.to_owned(),
+            tokens,
+        })
+    }
+
+    /// Returns the template it was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the folder for a file captured at the date, written as `YYYY:MM:DD HH:MM:SS`.
+    ///
+    /// # Errors
+    ///
+    /// If the folder isn't inside the root, such as with `{root}/../{exif.year}` or
+    /// `/elsewhere/{exif.year}`, an [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub(crate) fn resolve(&self, root: &Path, date: &str) -> Result<PathBuf, Error> {
+        let part = |range| date.get(range).unwrap_or_default();
+        let mut folder = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(literal) => folder.push_str(literal),
+                Token::Root => folder.push_str(&root.to_string_lossy()),
+                Token::Year => folder.push_str(part(0..4)),
+                Token::Month => folder.push_str(part(5..7)),
+                Token::Day => folder.push_str(part(8..10)),
+                Token::Date => folder.push_str(&part(0..10).replace(':', "-")),
+            }
+        }
+
+        let folder = match self.tokens.first() {
+            Some(Token::Root) => PathBuf::from(folder),
+            _ => root.join(folder),
+        };
+        let inside_root = folder.strip_prefix(root).is_ok_and(|relative| {
+            relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        });
+        match inside_root {
+            true => Ok(folder),
+            false => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} is outside of {} in `{}`",
+                    folder.display(),
+                    root.display(),
+                    self.template
+                ),
+            )),
+        }
+    }
+}
+
+/// A compiled template for new names of files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+    tokens: Vec<NameToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NameToken {
+    Literal(String),
+    Name,
+    Extension,
+    Date,
+    /// The number of digits the counter is padded to.
+    Counter(usize),
+}
+
+impl NameTemplate {
+    /// Compiles a template.
+    ///
+    /// # Errors
+    ///
+    /// If the template has an unknown placeholder or a brace that isn't part of one, an
+    /// [InvalidInput](ErrorKind::InvalidInput) error is returned.
+    pub fn new(template: &str) -> Result<NameTemplate, Error> {
+        let tokens = compile(template, |name| match name {
+            "name" => Some(NameToken::Name),
+            "ext" => Some(NameToken::Extension),
+            "date" => Some(NameToken::Date),
+            "counter" => Some(NameToken::Counter(1)),
+            name => name
+                .strip_prefix("counter:")
+                .and_then(|digits| digits.parse().ok())
+                .filter(|digits| (1..=9).contains(digits))
+                .map(NameToken::Counter),
+        })?;
+
+        Ok(NameTemplate {
+            template: template.to_owned(),
+            tokens,
+        })
+    }
+
+    /// Returns the template it was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns whether names from the template have a counter, which keeps them apart.
+    pub(crate) fn has_counter(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| matches!(token, NameToken::Counter(_)))
+    }
+
+    /// Returns the new name of a file captured at the date, written as `YYYY:MM:DD HH:MM:SS`.
+    pub(crate) fn resolve(&self, file: &Path, date: &str, counter: usize) -> String {
+        let mut name = String::new();
+        for token in &self.tokens {
+            match token {
+                NameToken::Literal(literal) => name.push_str(literal),
+                NameToken::Name => {
+                    if let Some(stem) = file.file_stem() {
+                        name.push_str(&stem.to_string_lossy());
+                    }
+                }
+                NameToken::Extension => {
+                    if let Some(extension) = file.extension() {
+                        name.push('.');
+                        name.push_str(&extension.to_string_lossy());
+                    }
+                }
+                NameToken::Date => {
+                    name.push_str(&date.get(0..10).unwrap_or_default().replace(':', "-"))
+                }
+                NameToken::Counter(digits) => {
+                    name.push_str(&format!("{:01$}", counter, digits));
+                }
+            }
+        }
+
+        name
+    }
+}
+
+/// Compiles a template into its tokens, saying where the problem is if it can't be parsed.
+fn compile<T>(template: &str, placeholder: impl Fn(&str) -> Option<T>) -> Result<Vec<T>, Error>
+where
+    T: From<String>,
+{
+    parse(template, placeholder).map_err(|message| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} in `{}`", message, template),
+        )
+    })
+}
+
+/// Splits a template into its literal text and placeholders, which `placeholder` names.
+fn parse<T>(template: &str, placeholder: impl Fn(&str) -> Option<T>) -> Result<Vec<T>, String>
+where
+    T: From<String>,
+{
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '{' if characters.peek() == Some(&'{') => {
+                characters.next();
+                literal.push('{');
+            }
+            '}' if characters.peek() == Some(&'}') => {
+                characters.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match characters.next() {
+                        Some('}') => break,
+                        Some(character) => name.push(character),
+                        None => return Err("unclosed `{`".to_owned()),
+                    }
+                }
+                let token = placeholder(&name)
+                    .ok_or_else(|| format!("unknown placeholder `{{{}}}`", name))?;
+                if !literal.is_empty() {
+                    tokens.push(T::from(std::mem::take(&mut literal)));
+                }
+                tokens.push(token);
+            }
+            '}' => return Err("unmatched `}`".to_owned()),
+            character => literal.push(character),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(T::from(literal));
+    }
+
+    Ok(tokens)
+}
+
+impl From<String> for Token {
+    fn from(literal: String) -> Token {
+        Token::Literal(literal)
+    }
+}
+
+impl From<String> for NameToken {
+    fn from(literal: String) -> NameToken {
+        NameToken::Literal(literal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{FolderTemplate, NameTemplate};
+    use std::io::ErrorKind;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn ensure_placeholders_are_resolved_from_the_date() {
+        let root = Path::new("/photos");
+        let date = "2021:07:04 18:30:00";
+
+        let by_month = FolderTemplate::new("{root}/{exif.year}/{exif.month}").unwrap();
+        let relative = FolderTemplate::new("{root}/{exif.year}").unwrap();
+        let by_event = FolderTemplate::new("{exif.date} Trip {{2}}").unwrap();
+
+        assert_eq!(
+            by_month.resolve(root, date).unwrap(),
+            PathBuf::from("/photos/2021/07")
+        );
+        assert_eq!(
+            by_event.resolve(root, date).unwrap(),
+            PathBuf::from("/photos/2021-07-04 Trip {2}")
+        );
+        assert_eq!(
+            relative.resolve(Path::new("./photos"), date).unwrap(),
+            PathBuf::from("./photos/2021")
+        );
+        assert_eq!(by_event.as_str(), "{exif.date} Trip {{2}}");
+    }
+
+    #[test]
+    fn ensure_folders_outside_of_the_root_are_refused() {
+        let root = Path::new("/photos");
+        let date = "2021:07:04 18:30:00";
+
+        let parent = FolderTemplate::new("{root}/../{exif.year}").unwrap();
+        let sibling = FolderTemplate::new("{root}2/{exif.year}").unwrap();
+
+        let error = parent.resolve(root, date).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            error.to_string(),
+            "/photos/../2021 is outside of /photos in `{root}/../{exif.year}`"
+        );
+        assert!(sibling.resolve(root, date).is_err());
+        let relative = FolderTemplate::new("../{exif.year}").unwrap();
+        assert!(relative.resolve(root, date).is_err());
+        let absolute = FolderTemplate::new("/elsewhere/{exif.year}").unwrap();
+        assert!(absolute.resolve(root, date).is_err());
+    }
+
+    #[test]
+    fn ensure_invalid_templates_are_rejected() {
+        let unknown = FolderTemplate::new("{root}/{exif.week}").unwrap_err();
+        let unmatched = FolderTemplate::new("{root}/}").unwrap_err();
+
+        assert_eq!(unknown.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            unknown.to_string(),
+            "unknown placeholder `{exif.week}` in `{root}/{exif.week}`"
+        );
+        assert_eq!(unmatched.to_string(), "unmatched `}` in `{root}/}`");
+        let unclosed = FolderTemplate::new("{root").unwrap_err();
+        assert_eq!(unclosed.to_string(), "unclosed `{` in `{root`");
+        let late_root = FolderTemplate::new("{exif.year}/{root}").unwrap_err();
+        assert_eq!(late_root.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            late_root.to_string(),
+            "`{root}` can only start the template in `{exif.year}/{root}`"
+        );
+    }
+
+    #[test]
+    fn ensure_names_are_resolved_from_the_file_date_and_counter() {
+        let file = Path::new("/photos/IMG_1234.jpeg");
+        let date = "2021:07:04 18:30:00";
+
+        let dated = NameTemplate::new("{date}_{counter}{ext}").unwrap();
+        let padded = NameTemplate::new("{name} {{{counter:3}}}").unwrap();
+        let fixed = NameTemplate::new("{date}{ext}").unwrap();
+
+        assert_eq!(dated.resolve(file, date, 2), "2021-07-04_2.jpeg");
+        assert_eq!(padded.resolve(file, date, 7), "IMG_1234 {007}");
+        assert_eq!(
+            fixed.resolve(Path::new("/photos/notes"), date, 1),
+            "2021-07-04"
+        );
+        assert!(dated.has_counter());
+        assert!(!fixed.has_counter());
+        assert_eq!(padded.as_str(), "{name} {{{counter:3}}}");
+        let unknown = NameTemplate::new("{date}_{counter:0}").unwrap_err();
+        assert_eq!(
+            unknown.to_string(),
+            "unknown placeholder `{counter:0}` in `{date}_{counter:0}`"
+        );
+    }
+}
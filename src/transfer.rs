@@ -0,0 +1,69 @@
+//! How files are put into the folders they're sorted into.
+
+/// What [Backend::sort_file](crate::Backend::sort_file) does with the current file.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferMode {
+    /// [Move](crate::Backend::move_file) the file into the folder.
+    #[default]
+    Move,
+    /// [Copy](crate::Backend::copy_file) the file into the folder and leave the original
+    /// untouched.
+    Copy,
+    /// [Link](crate::Backend::link_file) the file into the folder, so that it can be sorted into
+    /// several folders without taking up more space.
+    Link(LinkKind),
+}
+
+impl TransferMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::Link(kind) => kind.as_str(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TransferMode> {
+        let mode = match name {
+            "move" => TransferMode::Move,
+            "copy" => TransferMode::Copy,
+            name => TransferMode::Link(LinkKind::from_name(name)?),
+        };
+
+        Some(mode)
+    }
+}
+
+/// What kind of link is put in a folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// A hard link, which only works within the same filesystem and keeps the file alive even if
+    /// the original is removed.
+    Hard,
+    /// A symbolic link pointing at the absolute path of the file.
+    Symbolic,
+    /// A symbolic link pointing at the file relative to the folder, which keeps working when the
+    /// folders are moved together, such as on a drive mounted elsewhere.
+    RelativeSymbolic,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Hard => "hardlink",
+            LinkKind::Symbolic => "symlink",
+            LinkKind::RelativeSymbolic => "relative_symlink",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LinkKind> {
+        let kind = match name {
+            "hardlink" => LinkKind::Hard,
+            "symlink" => LinkKind::Symbolic,
+            "relative_symlink" => LinkKind::RelativeSymbolic,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
@@ -0,0 +1,503 @@
+//! Trash implementations used by [Filesystem](crate::filesystem::Filesystem) to make deletions
+//! reversible.
+//!
+//! Two kinds of trash are supported:
+//! - a private staging folder next to each deleted file, which the backend purges once the
+//!   deletion can no longer be undone
+//! - the [FreeDesktop.org trash](https://specifications.freedesktop.org/trash-spec/latest/),
+//!   which makes deleted files show up in the desktop's file manager
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the hidden folder that deleted files are staged in until they are purged.
+///
+/// Each file is staged in a trash folder next to it so that deleting is a cheap rename on the
+/// same filesystem. These folders are never loaded as folders to sort into.
+pub const TRASH_FOLDER_NAME: &str = ".sorter_trash";
+
+const TRASH_INFO_EXTENSION: &str = "trashinfo";
+
+/// Where deleted files are put.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub enum TrashMode {
+    /// Files are staged in a hidden folder next to them and purged by the backend once they can
+    /// no longer be restored by undoing.
+    #[default]
+    Staging,
+    /// Files are moved into the FreeDesktop.org trash of the given data home (usually
+    /// `$XDG_DATA_HOME`), or into the `.Trash-$uid` folder of their mount when they live on a
+    /// different filesystem. The desktop owns these files afterwards, so they are never purged.
+    FreeDesktop { data_home: PathBuf },
+}
+
+impl TrashMode {
+    /// Returns the FreeDesktop.org trash mode for the current user.
+    ///
+    /// The data home is `$XDG_DATA_HOME`, falling back to `$HOME/.local/share` as the
+    /// specification requires.
+    ///
+    /// # Errors
+    ///
+    /// If neither environment variable is set, a [NotFound](ErrorKind::NotFound) error is
+    /// returned.
+    pub fn free_desktop() -> Result<TrashMode, Error> {
+        let data_home = data_home_from(env::var_os("XDG_DATA_HOME"), env::var_os("HOME"))
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        Ok(TrashMode::FreeDesktop { data_home })
+    }
+}
+
+fn data_home_from(xdg_data_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    match xdg_data_home {
+        Some(data_home) if Path::new(&data_home).is_absolute() => Some(PathBuf::from(data_home)),
+        _ => home.map(|home| Path::new(&home).join(".local").join("share")),
+    }
+}
+
+fn split_file(file: &Path) -> Result<(&Path, &OsStr), Error> {
+    match (file.parent(), file.file_name()) {
+        (Some(parent), Some(file_name)) => Ok((parent, file_name)),
+        _ => Err(Error::from(ErrorKind::InvalidInput)),
+    }
+}
+
+fn numbered_name(file_name: &OsStr, counter: usize) -> OsString {
+    let mut numbered_name = file_name.to_os_string();
+    if counter > 0 {
+        numbered_name.push(format!(".{}", counter));
+    }
+    numbered_name
+}
+
+/// Moves a file into the staging folder next to it.
+pub fn stage_file(file: &Path) -> Result<PathBuf, Error> {
+    let (parent, file_name) = split_file(file)?;
+    let trash_folder = parent.join(TRASH_FOLDER_NAME);
+    fs::create_dir_all(&trash_folder)?;
+
+    let mut counter = 0;
+    let mut trashed_file = trash_folder.join(file_name);
+    while trashed_file.exists() {
+        counter += 1;
+        trashed_file = trash_folder.join(numbered_name(file_name, counter));
+    }
+    fs::rename(file, &trashed_file)?;
+
+    Ok(trashed_file)
+}
+
+/// Returns whether a trashed file was staged rather than put in the desktop's trash.
+pub fn is_staged(trashed_file: &Path) -> bool {
+    trashed_file
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|folder| folder == TRASH_FOLDER_NAME)
+}
+
+/// Moves a staged file back and removes the staging folder if it is empty.
+pub fn restore_staged_file(trashed_file: &Path, original_file: &Path) -> Result<(), Error> {
+    if original_file.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+    fs::rename(trashed_file, original_file)?;
+    remove_empty_staging_folder(trashed_file);
+
+    Ok(())
+}
+
+/// Removes a staged file for good and removes the staging folder if it is empty.
+pub fn purge_staged_file(trashed_file: &Path) -> Result<(), Error> {
+    fs::remove_file(trashed_file)?;
+    remove_empty_staging_folder(trashed_file);
+
+    Ok(())
+}
+
+fn remove_empty_staging_folder(trashed_file: &Path) {
+    if let Some(trash_folder) = trashed_file.parent() {
+        // only succeeds once the trash folder is empty
+        let _ = fs::remove_dir(trash_folder);
+    }
+}
+
+/// Moves a file into the FreeDesktop.org trash and writes its `.trashinfo` file.
+///
+/// The info file is created first so that its name is reserved before the file is moved, as the
+/// specification requires.
+pub fn free_desktop_trash_file(file: &Path, data_home: &Path) -> Result<PathBuf, Error> {
+    let file = absolute(file)?;
+    let (_, file_name) = split_file(&file)?;
+    let (trash_folder, top_folder) = free_desktop_trash_folder(&file, data_home)?;
+    let files_folder = trash_folder.join("files");
+    let info_folder = trash_folder.join("info");
+    fs::create_dir_all(&files_folder)?;
+    fs::create_dir_all(&info_folder)?;
+
+    let info_path = match &top_folder {
+        Some(top_folder) => file.strip_prefix(top_folder).unwrap_or(&file),
+        None => &file,
+    };
+    let now = SystemTime::now();
+    let info_contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(info_path),
+        format_deletion_date(now, local_utc_offset(now))
+    );
+
+    let mut counter = 0;
+    loop {
+        let trashed_name = numbered_name(file_name, counter);
+        let info_file = info_file_for(&info_folder, &trashed_name);
+        let trashed_file = files_folder.join(&trashed_name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_file)
+        {
+            Ok(mut info) if !trashed_file.exists() => {
+                let written = info
+                    .write_all(info_contents.as_bytes())
+                    .and_then(|_| info.sync_all())
+                    .and_then(|_| fs::rename(&file, &trashed_file));
+                if let Err(error) = written {
+                    let _ = fs::remove_file(&info_file);
+                    return Err(error);
+                }
+                return Ok(trashed_file);
+            }
+            // the info file is free, but a file without one is in the way
+            Ok(_) => fs::remove_file(&info_file)?,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error),
+        }
+        counter += 1;
+    }
+}
+
+/// Moves a file out of the FreeDesktop.org trash and removes its `.trashinfo` file.
+pub fn free_desktop_restore_file(trashed_file: &Path, original_file: &Path) -> Result<(), Error> {
+    if original_file.exists() {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+    let (files_folder, trashed_name) = split_file(trashed_file)?;
+    let trash_folder = files_folder
+        .parent()
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))?;
+
+    fs::rename(trashed_file, original_file)?;
+    match fs::remove_file(info_file_for(&trash_folder.join("info"), trashed_name)) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+fn info_file_for(info_folder: &Path, trashed_name: &OsStr) -> PathBuf {
+    let mut info_name = trashed_name.to_os_string();
+    info_name.push(".");
+    info_name.push(TRASH_INFO_EXTENSION);
+    info_folder.join(info_name)
+}
+
+fn absolute(file: &Path) -> Result<PathBuf, Error> {
+    let (parent, file_name) = split_file(file)?;
+    let parent = match parent.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => parent,
+    };
+
+    Ok(fs::canonicalize(parent)?.join(file_name))
+}
+
+/// Returns the trash folder to use for the file and, for per-mount trash folders, the top
+/// folder of the mount that paths in `.trashinfo` files are relative to.
+#[cfg(unix)]
+fn free_desktop_trash_folder(
+    file: &Path,
+    data_home: &Path,
+) -> Result<(PathBuf, Option<PathBuf>), Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let home_trash = data_home.join("Trash");
+    fs::create_dir_all(&home_trash)?;
+    let home_metadata = fs::metadata(&home_trash)?;
+    let file_device = fs::symlink_metadata(file)?.dev();
+    if file_device == home_metadata.dev() {
+        return Ok((home_trash, None));
+    }
+
+    let top_folder = mount_top_folder(file, file_device);
+    // SAFETY: getuid has no preconditions and always succeeds
+    let uid = unsafe { libc::getuid() };
+
+    Ok((mount_trash_folder(&top_folder, uid), Some(top_folder)))
+}
+
+/// Returns the trash folder of a user on a mount: their folder in the administrator's `.Trash`
+/// if that is a real folder with the sticky bit set, or else `.Trash-$uid`.
+#[cfg(unix)]
+fn mount_trash_folder(top_folder: &Path, uid: u32) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let admin_trash = top_folder.join(".Trash");
+    let is_valid_admin_trash = fs::symlink_metadata(&admin_trash)
+        .map(|metadata| metadata.is_dir() && metadata.mode() & 0o1000 != 0)
+        .unwrap_or(false);
+    match is_valid_admin_trash {
+        true => admin_trash.join(uid.to_string()),
+        false => top_folder.join(format!(".Trash-{}", uid)),
+    }
+}
+
+#[cfg(not(unix))]
+fn free_desktop_trash_folder(
+    _file: &Path,
+    data_home: &Path,
+) -> Result<(PathBuf, Option<PathBuf>), Error> {
+    Ok((data_home.join("Trash"), None))
+}
+
+/// Walks up from the file until the parent lies on another device.
+#[cfg(unix)]
+fn mount_top_folder(file: &Path, device: u64) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top_folder = file.parent().unwrap_or(file).to_path_buf();
+    while let Some(parent) = top_folder.parent() {
+        match fs::metadata(parent) {
+            Ok(metadata) if metadata.dev() == device => top_folder = parent.to_path_buf(),
+            _ => break,
+        }
+    }
+    top_folder
+}
+
+/// Percent-encodes a path the way `.trashinfo` files expect, keeping `/` as is.
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Returns how many seconds the local time zone is ahead of UTC at the given time.
+#[cfg(unix)]
+pub fn local_utc_offset(time: SystemTime) -> i64 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0) as libc::time_t;
+    // SAFETY: tm is plain data that localtime_r fills in, and both pointers are valid
+    unsafe {
+        let mut local_time: libc::tm = std::mem::zeroed();
+        match libc::localtime_r(&seconds, &mut local_time).is_null() {
+            true => 0,
+            false => local_time.tm_gmtoff as i64,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn local_utc_offset(_time: SystemTime) -> i64 {
+    0
+}
+
+/// Formats a deletion date as `YYYY-MM-DDThh:mm:ss` in local time, shifted from UTC by the
+/// offset in seconds, since the specification wants local time without a time zone.
+fn format_deletion_date(time: SystemTime, utc_offset: i64) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0) as i64
+        + utc_offset;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let seconds_of_day = seconds.rem_euclid(86_400);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Converts days since the unix epoch into a (year, month, day) date, see
+/// <http://howardhinnant.github.io/date_algorithms.html>.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trash::{
+        data_home_from, format_deletion_date, free_desktop_restore_file, free_desktop_trash_file,
+        percent_encode,
+    };
+    #[cfg(unix)]
+    use crate::trash::{mount_top_folder, mount_trash_folder};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempdir::TempDir;
+
+    #[test]
+    fn ensure_file_and_info_are_written_to_the_home_trash() {
+        let data_home = TempDir::new("unit_test").unwrap();
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file 1.jpg");
+        fs::write(&file, "content").unwrap();
+
+        let trashed = free_desktop_trash_file(&file, data_home.path()).unwrap();
+
+        let trash = data_home.path().join("Trash");
+        assert_eq!(trashed, trash.join("files").join("file 1.jpg"));
+        assert_eq!(fs::read_to_string(&trashed).unwrap(), "content");
+        assert!(!file.exists());
+        let info = fs::read_to_string(trash.join("info").join("file 1.jpg.trashinfo")).unwrap();
+        let expected_path =
+            percent_encode(&fs::canonicalize(dir.path()).unwrap().join("file 1.jpg"));
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains(&format!("Path={}\n", expected_path)));
+        assert!(expected_path.ends_with("/file%201.jpg"));
+        assert!(info.contains("DeletionDate="));
+    }
+
+    #[test]
+    fn ensure_trashed_files_with_the_same_name_get_distinct_info_files() {
+        let data_home = TempDir::new("unit_test").unwrap();
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.jpg");
+        fs::write(&file, "first").unwrap();
+        let first = free_desktop_trash_file(&file, data_home.path()).unwrap();
+        fs::write(&file, "second").unwrap();
+
+        let second = free_desktop_trash_file(&file, data_home.path()).unwrap();
+
+        let info = data_home.path().join("Trash").join("info");
+        assert_eq!(second, first.with_file_name("file1.jpg.1"));
+        assert!(info.join("file1.jpg.trashinfo").exists());
+        assert!(info.join("file1.jpg.1.trashinfo").exists());
+    }
+
+    #[test]
+    fn ensure_file_and_info_are_removed_from_trash_when_restoring() {
+        let data_home = TempDir::new("unit_test").unwrap();
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.jpg");
+        fs::write(&file, "content").unwrap();
+        let trashed = free_desktop_trash_file(&file, data_home.path()).unwrap();
+
+        free_desktop_restore_file(&trashed, &file).unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+        assert!(!trashed.exists());
+        assert!(!data_home
+            .path()
+            .join("Trash/info/file1.jpg.trashinfo")
+            .exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_mount_trash_is_the_admin_trash_only_if_it_is_sticky() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let top_folder = TempDir::new("unit_test").unwrap();
+        let top_folder = top_folder.path();
+        let admin_trash = top_folder.join(".Trash");
+        let own_trash = top_folder.join(".Trash-1000");
+
+        assert_eq!(mount_trash_folder(top_folder, 1000), own_trash);
+        symlink(top_folder, &admin_trash).unwrap();
+        assert_eq!(mount_trash_folder(top_folder, 1000), own_trash);
+        fs::remove_file(&admin_trash).unwrap();
+        fs::create_dir(&admin_trash).unwrap();
+        fs::set_permissions(&admin_trash, fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(mount_trash_folder(top_folder, 1000), own_trash);
+        fs::set_permissions(&admin_trash, fs::Permissions::from_mode(0o1777)).unwrap();
+        assert_eq!(
+            mount_trash_folder(top_folder, 1000),
+            admin_trash.join("1000")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_mount_top_folder_is_the_highest_folder_on_the_device() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new("unit_test").unwrap();
+        let file = dir.path().join("file1.jpg");
+        fs::write(&file, "content").unwrap();
+        let device = fs::metadata(&file).unwrap().dev();
+
+        let top_folder = mount_top_folder(&file, device);
+
+        assert!(file.starts_with(&top_folder));
+        assert_eq!(fs::metadata(&top_folder).unwrap().dev(), device);
+        if let Some(parent) = top_folder.parent() {
+            assert_ne!(fs::metadata(parent).unwrap().dev(), device);
+        }
+    }
+
+    #[test]
+    fn ensure_data_home_falls_back_to_home() {
+        let home = Some(OsString::from("/home/user"));
+
+        assert_eq!(
+            data_home_from(Some(OsString::from("/data")), home.clone()),
+            Some(PathBuf::from("/data"))
+        );
+        assert_eq!(
+            data_home_from(Some(OsString::from("relative")), home.clone()),
+            Some(Path::new("/home/user/.local/share").to_path_buf())
+        );
+        assert_eq!(
+            data_home_from(None, home),
+            Some(Path::new("/home/user/.local/share").to_path_buf())
+        );
+        assert_eq!(data_home_from(None, None), None);
+    }
+
+    #[test]
+    fn ensure_deletion_date_is_formatted_as_specified() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_093_991_528);
+
+        assert_eq!(format_deletion_date(time, 0), "2004-08-31T22:32:08");
+        assert_eq!(format_deletion_date(time, 7200), "2004-09-01T00:32:08");
+        assert_eq!(
+            format_deletion_date(time, -23 * 3600),
+            "2004-08-30T23:32:08"
+        );
+    }
+}